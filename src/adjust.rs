@@ -0,0 +1,503 @@
+use image::{ColorType, DynamicImage, Rgba};
+use rayon::prelude::*;
+
+use crate::hdr;
+use crate::transform::{convert_to, parse_color};
+
+/// Applies `f` to every pixel's RGB, as 0-1 floats, keeping alpha, depth and
+/// the gray or color layout of `image`.
+pub fn map_rgb(image: DynamicImage, f: impl Fn([f32; 3]) -> [f32; 3] + Sync) -> DynamicImage {
+    let color = image.color();
+    let mut pixels = image.into_rgba32f();
+    pixels.par_chunks_mut(4).for_each(|pixel| {
+        let [r, g, b] = f([pixel[0], pixel[1], pixel[2]]);
+        pixel[0] = r.clamp(0.0, 1.0);
+        pixel[1] = g.clamp(0.0, 1.0);
+        pixel[2] = b.clamp(0.0, 1.0);
+    });
+    convert_to(DynamicImage::ImageRgba32F(pixels), color)
+}
+
+/// Gives a gray `image` RGB channels, so an adjustment can tint it, keeping
+/// its depth and alpha; color images are returned as they are.
+pub fn promote_gray(image: DynamicImage) -> DynamicImage {
+    match image.color() {
+        ColorType::L8 => DynamicImage::ImageRgb8(image.into_rgb8()),
+        ColorType::La8 => DynamicImage::ImageRgba8(image.into_rgba8()),
+        ColorType::L16 => DynamicImage::ImageRgb16(image.into_rgb16()),
+        ColorType::La16 => DynamicImage::ImageRgba16(image.into_rgba16()),
+        _ => image,
+    }
+}
+
+/// Stretches the histogram to the full range, ignoring the darkest and
+/// lightest `clip` percent of samples so a few specks don't pin the ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    /// Stretches each channel on its own (auto-level), which also removes
+    /// color casts; otherwise all channels share one range (auto-contrast)
+    /// and hues stay put.
+    pub per_channel: bool,
+    pub clip: f32,
+}
+
+impl Levels {
+    pub const DEFAULT_CLIP: f32 = 0.1;
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        let mut histograms = [[0u64; 256]; 3];
+        for pixel in image.to_rgb8().pixels() {
+            for channel in 0..3 {
+                histograms[channel][usize::from(pixel[channel])] += 1;
+            }
+        }
+        let ranges = if self.per_channel {
+            histograms.map(|histogram| self.range(&histogram))
+        } else {
+            let mut combined = [0u64; 256];
+            for histogram in &histograms {
+                for (total, count) in combined.iter_mut().zip(histogram) {
+                    *total += count;
+                }
+            }
+            [self.range(&combined); 3]
+        };
+        if ranges.iter().all(|&range| range == (0.0, 1.0)) {
+            return image;
+        }
+
+        map_rgb(image, |rgb| {
+            let mut stretched = rgb;
+            for (value, (low, high)) in stretched.iter_mut().zip(ranges) {
+                *value = (*value - low) / (high - low);
+            }
+            stretched
+        })
+    }
+
+    /// The levels, as 0-1, below and above which `clip` percent of the
+    /// samples lie; the full range when that would leave nothing.
+    fn range(self, histogram: &[u64; 256]) -> (f32, f32) {
+        let total: u64 = histogram.iter().sum();
+        let clipped = (total as f64 * f64::from(self.clip) / 100.0) as u64;
+        let mut seen = 0;
+        let low = histogram.iter().position(|&count| {
+            seen += count;
+            seen > clipped
+        });
+        seen = 0;
+        let high = histogram.iter().rposition(|&count| {
+            seen += count;
+            seen > clipped
+        });
+        match (low, high) {
+            (Some(low), Some(high)) if low < high => (low as f32 / 255.0, high as f32 / 255.0),
+            _ => (0.0, 1.0),
+        }
+    }
+}
+
+/// Basic exposure fixes on the encoded (sRGB) values, as image editors
+/// apply them: brightness, then contrast around mid-gray, then gamma.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// -100 (black) to 100 (white); shifts every value by that percentage.
+    pub brightness: f32,
+    /// -100 (flat gray) to 100 (hard threshold at mid-gray).
+    pub contrast: f32,
+    /// Above 1 brightens mid-tones, below 1 darkens them.
+    pub gamma: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Tone {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if self.is_identity() {
+            return image;
+        }
+        let offset = self.brightness / 100.0;
+        let contrast = self.contrast / 100.0;
+        // Symmetric in feel: -50 halves the spread around mid-gray, +50
+        // doubles it.
+        let slope = if contrast < 0.0 { 1.0 + contrast } else { 1.0 / (1.0 - contrast).max(0.001) };
+        let exponent = 1.0 / self.gamma;
+        map_rgb(image, |rgb| {
+            rgb.map(|value| {
+                let value = ((value + offset - 0.5) * slope + 0.5).clamp(0.0, 1.0);
+                value.powf(exponent)
+            })
+        })
+    }
+}
+
+/// OKLab chroma of the most saturated sRGB colors, roughly; vibrance
+/// leaves colors this vivid alone.
+const MAX_CHROMA: f32 = 0.32;
+
+/// Color adjustments in OKLCh, where changing chroma or hue keeps
+/// perceived lightness, unlike HSL.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Hue {
+    /// -100 (gray) to 100 (double chroma).
+    pub saturation: f32,
+    /// Degrees around the hue circle.
+    pub rotate: f32,
+    /// -100 to 100; like saturation, but weighted towards muted colors so
+    /// already vivid ones (and skin tones) don't clip.
+    pub vibrance: f32,
+}
+
+impl Hue {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if self.is_identity() || !image.color().has_color() {
+            return image;
+        }
+        let saturation = 1.0 + self.saturation / 100.0;
+        let vibrance = self.vibrance / 100.0;
+        let (sin, cos) = self.rotate.to_radians().sin_cos();
+        map_rgb(image, |rgb| {
+            let [l, a, b] = to_oklab(rgb.map(hdr::decode_srgb));
+            let chroma = a.hypot(b);
+            let scale = saturation * (1.0 + vibrance * (1.0 - chroma / MAX_CHROMA).max(0.0));
+            let (a, b) = ((a * cos - b * sin) * scale, (a * sin + b * cos) * scale);
+            from_oklab([l, a, b]).map(hdr::encode_srgb)
+        })
+    }
+}
+
+/// Linear sRGB to OKLab, per Björn Ottosson's reference.
+pub fn to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+pub fn from_oklab([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let long = (l + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let medium = (l - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let short = (l - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+    [
+        4.076_741_7 * long - 3.307_711_6 * medium + 0.230_969_94 * short,
+        -1.268_438 * long + 2.609_757_4 * medium - 0.341_319_38 * short,
+        -0.004_196_086_3 * long - 0.703_418_6 * medium + 1.707_614_7 * short,
+    ]
+}
+
+/// Stylistic per-pixel filters for branded assets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Sepia,
+    Invert,
+    /// Levels per channel, 2-255.
+    Posterize(u8),
+    /// Maps luminance from a shadow to a highlight color.
+    Duotone(Rgba<u8>, Rgba<u8>),
+}
+
+impl Effect {
+    /// Parses `sepia`, `invert`, `posterize=N` or `duotone=SHADOW,HIGHLIGHT`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, argument) = match spec.split_once('=') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (spec, None),
+        };
+        match (name.to_lowercase().as_str(), argument) {
+            ("sepia", None) => Ok(Effect::Sepia),
+            ("invert", None) => Ok(Effect::Invert),
+            ("posterize", Some(levels)) => levels
+                .parse()
+                .ok()
+                .filter(|&levels| levels >= 2)
+                .map(Effect::Posterize)
+                .ok_or_else(|| format!("Invalid posterize levels: {} (expected 2-255)", levels)),
+            ("duotone", Some(colors)) => {
+                let (shadow, highlight) = colors
+                    .split_once(',')
+                    .ok_or_else(|| format!("Invalid duotone colors: {} (expected SHADOW,HIGHLIGHT)", colors))?;
+                Ok(Effect::Duotone(parse_color(shadow)?, parse_color(highlight)?))
+            }
+            _ => Err(format!(
+                "Invalid filter: {} (expected sepia, invert, posterize=N or duotone=#rrggbb,#rrggbb)",
+                spec
+            )),
+        }
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            // Sepia and duotone add color to gray images.
+            Effect::Sepia | Effect::Duotone(..) if !image.color().has_color() => self.apply(promote_gray(image)),
+            Effect::Sepia => map_rgb(image, |[r, g, b]| {
+                [
+                    0.393 * r + 0.769 * g + 0.189 * b,
+                    0.349 * r + 0.686 * g + 0.168 * b,
+                    0.272 * r + 0.534 * g + 0.131 * b,
+                ]
+            }),
+            Effect::Invert => map_rgb(image, |rgb| rgb.map(|value| 1.0 - value)),
+            Effect::Posterize(levels) => {
+                let steps = f32::from(levels - 1);
+                map_rgb(image, |rgb| rgb.map(|value| (value * steps).round() / steps))
+            }
+            Effect::Duotone(shadow, highlight) => {
+                let (shadow, highlight) = (shadow.0.map(|c| f32::from(c) / 255.0), highlight.0.map(|c| f32::from(c) / 255.0));
+                map_rgb(image, |[r, g, b]| {
+                    let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+                    std::array::from_fn(|c| shadow[c] + (highlight[c] - shadow[c]) * luma)
+                })
+            }
+        }
+    }
+}
+
+/// Samples in a precomputed curve; finer than 16-bit output needs.
+const CURVE_SAMPLES: usize = 4096;
+
+/// A tone curve through control points, interpolated with a monotone cubic
+/// spline so it never overshoots between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    table: Vec<f32>,
+}
+
+impl Curve {
+    /// Parses space-separated `in,out` pairs in 0-1, e.g.
+    /// `0,0 0.25,0.2 0.75,0.85 1,1`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid curve: {} (expected at least two in,out points in 0-1, e.g. 0,0 0.5,0.6 1,1)", spec);
+        let mut points = spec
+            .split_whitespace()
+            .map(|point| {
+                let (x, y) = point.split_once(',')?;
+                let (x, y): (f32, f32) = (x.trim().parse().ok()?, y.trim().parse().ok()?);
+                ((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)).then_some((x, y))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points.dedup_by(|a, b| a.0 == b.0);
+        if points.len() < 2 {
+            return Err(invalid());
+        }
+        Ok(Self::through(&points))
+    }
+
+    /// Fritsch-Carlson monotone cubic interpolation; flat outside the first
+    /// and last points.
+    fn through(points: &[(f32, f32)]) -> Self {
+        let count = points.len();
+        let slopes: Vec<f32> = points.windows(2).map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0)).collect();
+        let mut tangents = vec![0.0; count];
+        tangents[0] = slopes[0];
+        tangents[count - 1] = slopes[count - 2];
+        for i in 1..count - 1 {
+            tangents[i] = if slopes[i - 1] * slopes[i] <= 0.0 { 0.0 } else { (slopes[i - 1] + slopes[i]) / 2.0 };
+        }
+        for (i, &slope) in slopes.iter().enumerate() {
+            if slope == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (tangents[i] / slope, tangents[i + 1] / slope);
+            let length = a.hypot(b);
+            if length > 3.0 {
+                tangents[i] = 3.0 * a / length * slope;
+                tangents[i + 1] = 3.0 * b / length * slope;
+            }
+        }
+
+        let table = (0..CURVE_SAMPLES)
+            .map(|sample| {
+                let x = sample as f32 / (CURVE_SAMPLES - 1) as f32;
+                if x <= points[0].0 {
+                    return points[0].1;
+                }
+                if x >= points[count - 1].0 {
+                    return points[count - 1].1;
+                }
+                let i = points.windows(2).position(|pair| x <= pair[1].0).unwrap_or(count - 2);
+                let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+                let h = x1 - x0;
+                let t = (x - x0) / h;
+                let (t2, t3) = (t * t, t * t * t);
+                (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+                    + (t3 - 2.0 * t2 + t) * h * tangents[i]
+                    + (-2.0 * t3 + 3.0 * t2) * y1
+                    + (t3 - t2) * h * tangents[i + 1]
+            })
+            .collect();
+        Self { table }
+    }
+
+    fn evaluate(&self, value: f32) -> f32 {
+        let position = value.clamp(0.0, 1.0) * (CURVE_SAMPLES - 1) as f32;
+        let index = (position as usize).min(CURVE_SAMPLES - 2);
+        let fraction = position - index as f32;
+        self.table[index] * (1.0 - fraction) + self.table[index + 1] * fraction
+    }
+}
+
+/// Tone curves for all channels together and for each one alone; a
+/// channel's own curve is applied first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Curves {
+    pub master: Option<Curve>,
+    pub channels: [Option<Curve>; 3],
+}
+
+impl Curves {
+    /// Parses a curve optionally prefixed with the channel it applies to:
+    /// `r:`, `g:` or `b:`.
+    pub fn add(&mut self, spec: &str) -> Result<(), String> {
+        let (slot, points) = match spec.split_once(':') {
+            Some((channel, points)) => {
+                let index = match channel.trim().to_lowercase().as_str() {
+                    "r" | "red" => 0,
+                    "g" | "green" => 1,
+                    "b" | "blue" => 2,
+                    _ => return Err(format!("Invalid curve channel: {} (expected r, g or b)", channel)),
+                };
+                (&mut self.channels[index], points)
+            }
+            None => (&mut self.master, spec),
+        };
+        *slot = Some(Curve::parse(points)?);
+        Ok(())
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.master.is_none() && self.channels.iter().all(Option::is_none)
+    }
+
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        // Per-channel curves can tint a gray image.
+        let image = if self.channels.iter().all(Option::is_none) { image } else { promote_gray(image) };
+        map_rgb(image, |rgb| {
+            std::array::from_fn(|c| {
+                let value = self.channels[c].as_ref().map_or(rgb[c], |curve| curve.evaluate(rgb[c]));
+                self.master.as_ref().map_or(value, |curve| curve.evaluate(value))
+            })
+        })
+    }
+}
+
+/// How `--white-balance` estimates the color of the light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Illuminant {
+    /// Assumes the scene averages to gray.
+    GrayWorld,
+    /// Assumes the brightest tones are white; better for scans and
+    /// documents with paper showing.
+    WhitePatch,
+}
+
+impl Illuminant {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "auto" | "gray-world" | "grey-world" => Ok(Illuminant::GrayWorld),
+            "white-patch" => Ok(Illuminant::WhitePatch),
+            _ => Err(format!("Invalid white balance: {} (expected auto, gray-world or white-patch)", value)),
+        }
+    }
+}
+
+/// Fraction of the brightest samples white-patch ignores, so specular
+/// highlights and clipped pixels don't decide the white point.
+const WHITE_PATCH_IGNORED: f64 = 0.01;
+
+/// Removes color casts with per-channel gains in linear light: estimated
+/// from the image, set by hand, or both (manual on top of automatic).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WhiteBalance {
+    pub auto: Option<Illuminant>,
+    /// -100 (cooler, bluer) to 100 (warmer, more orange).
+    pub temperature: f32,
+    /// -100 (greener) to 100 (more magenta).
+    pub tint: f32,
+}
+
+impl WhiteBalance {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if self.is_identity() || !image.color().has_color() {
+            return image;
+        }
+        let [mut red, mut green, mut blue] = match self.auto {
+            Some(illuminant) => estimate_gains(&image, illuminant),
+            None => [1.0; 3],
+        };
+        red *= 1.0 + 0.3 * self.temperature / 100.0;
+        blue *= 1.0 - 0.3 * self.temperature / 100.0;
+        green *= 1.0 - 0.3 * self.tint / 100.0;
+        // Rec. 709 luminance of the gains, divided out so exposure stays put.
+        let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+        let gains = [red, green, blue].map(|gain| gain / luminance);
+
+        map_rgb(image, |rgb| {
+            std::array::from_fn(|c| hdr::encode_srgb(hdr::decode_srgb(rgb[c]) * gains[c]))
+        })
+    }
+}
+
+/// Per-channel gains that make the estimated light neutral.
+fn estimate_gains(image: &DynamicImage, illuminant: Illuminant) -> [f32; 3] {
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in image.to_rgb8().pixels() {
+        for channel in 0..3 {
+            histograms[channel][usize::from(pixel[channel])] += 1;
+        }
+    }
+    let linear: Vec<f32> = (0..=255).map(|level| hdr::decode_srgb(level as f32 / 255.0)).collect();
+
+    let reference = match illuminant {
+        Illuminant::GrayWorld => histograms.map(|histogram| {
+            let total: u64 = histogram.iter().sum();
+            let sum: f64 = histogram.iter().zip(&linear).map(|(&count, &value)| count as f64 * f64::from(value)).sum();
+            (sum / total.max(1) as f64) as f32
+        }),
+        Illuminant::WhitePatch => histograms.map(|histogram| {
+            let total: u64 = histogram.iter().sum();
+            let ignored = (total as f64 * WHITE_PATCH_IGNORED) as u64;
+            let mut seen = 0;
+            let level = histogram
+                .iter()
+                .rposition(|&count| {
+                    seen += count;
+                    seen > ignored
+                })
+                .unwrap_or(255);
+            linear[level]
+        }),
+    };
+    if reference.iter().any(|&value| value <= 0.0) {
+        return [1.0; 3];
+    }
+    let target = reference.iter().copied().fold(0.0, f32::max);
+    reference.map(|value| target / value)
+}
@@ -0,0 +1,254 @@
+use std::io::Cursor;
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{AnimationDecoder, DynamicImage, Frame, ImageError, ImageFormat};
+
+use crate::{metadata, ImageConverter, SupportedFormat};
+
+/// GIF application extension carrying the loop count.
+const NETSCAPE_LOOP: &[u8] = b"NETSCAPE2.0\x03\x01";
+
+/// A decoded animation. Every frame is composited onto the full canvas, so
+/// the source's disposal and blend methods are already applied and each
+/// frame can be written as a plain replacement of the previous one.
+pub struct Animation {
+    pub frames: Vec<Frame>,
+    /// Total number of times the animation plays; 0 means forever.
+    pub plays: u16,
+}
+
+/// Whether `format` can store an animation (PNG as APNG).
+pub fn supports(format: SupportedFormat) -> bool {
+    matches!(format, SupportedFormat::Gif | SupportedFormat::WebP | SupportedFormat::Png)
+}
+
+/// Whether `data` is a GIF, WebP or APNG with more than one frame.
+pub fn is_animated(data: &[u8]) -> bool {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Gif) => GifDecoder::new(Cursor::new(data))
+            .map(|decoder| decoder.into_frames().take(2).count() > 1)
+            .unwrap_or(false),
+        Ok(ImageFormat::WebP) => metadata::webp_chunks(data)
+            .map(|chunks| chunks.iter().filter(|(id, _)| id == b"ANMF").count() > 1)
+            .unwrap_or(false),
+        Ok(ImageFormat::Png) => apng_control(data).map(|(frames, _)| frames > 1).unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Number of frames in a GIF, WebP or APNG (a plain PNG or WebP counts as
+/// one), without decoding them; `None` for other formats.
+pub fn frame_count(data: &[u8]) -> Option<usize> {
+    match image::guess_format(data).ok()? {
+        ImageFormat::Gif => {
+            let mut decoder = gif::DecodeOptions::new().read_info(Cursor::new(data)).ok()?;
+            let mut count = 0;
+            while let Ok(Some(_)) = decoder.next_frame_info() {
+                count += 1;
+            }
+            Some(count)
+        }
+        ImageFormat::WebP => metadata::webp_chunks(data)
+            .map(|chunks| chunks.iter().filter(|(id, _)| id == b"ANMF").count().max(1)),
+        ImageFormat::Png => Some(apng_control(data).map_or(1, |(frames, _)| frames as usize)),
+        _ => None,
+    }
+}
+
+/// Decodes every frame of an animated GIF, WebP or APNG along with its
+/// loop count.
+pub fn decode(data: &[u8]) -> Result<Animation, ImageError> {
+    match image::guess_format(data)? {
+        ImageFormat::Gif => Ok(Animation {
+            frames: GifDecoder::new(Cursor::new(data))?.into_frames().collect_frames()?,
+            plays: gif_plays(data),
+        }),
+        ImageFormat::WebP => Ok(Animation {
+            frames: WebPDecoder::new(Cursor::new(data))?.into_frames().collect_frames()?,
+            plays: webp_plays(data),
+        }),
+        ImageFormat::Png => Ok(Animation {
+            frames: PngDecoder::new(Cursor::new(data))?.apng().into_frames().collect_frames()?,
+            plays: apng_control(data).map_or(1, |(_, plays)| plays.min(u32::from(u16::MAX)) as u16),
+        }),
+        format => Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+            ImageFormatHint::Exact(format),
+            UnsupportedErrorKind::Format(ImageFormatHint::Exact(format)),
+        ))),
+    }
+}
+
+/// Encodes `animation` as an animated GIF, WebP or APNG, WebP frames with
+/// the converter's WebP settings.
+pub fn encode(converter: &ImageConverter, animation: &Animation, format: SupportedFormat) -> Result<Vec<u8>, ImageError> {
+    if animation.frames.is_empty() {
+        return Err(encoding_error(ImageFormatHint::Name(format.extension().to_string()), "animation has no frames"));
+    }
+    match format {
+        SupportedFormat::Gif => encode_gif(animation),
+        SupportedFormat::WebP => encode_webp(converter, animation),
+        _ => encode_apng(animation),
+    }
+}
+
+fn encoding_error(hint: ImageFormatHint, message: impl std::fmt::Display) -> ImageError {
+    ImageError::Encoding(EncodingError::new(hint, message.to_string()))
+}
+
+/// Frame duration in whole milliseconds.
+fn delay_ms(frame: &Frame) -> u32 {
+    let (numerator, denominator) = frame.delay().numer_denom_ms();
+    numerator / denominator.max(1)
+}
+
+/// GIF stores the number of *repeats*; no extension means play once.
+fn gif_plays(data: &[u8]) -> u16 {
+    let Some(at) = data.windows(NETSCAPE_LOOP.len()).position(|window| window == NETSCAPE_LOOP) else {
+        return 1;
+    };
+    match data.get(at + NETSCAPE_LOOP.len()..at + NETSCAPE_LOOP.len() + 2) {
+        Some(&[low, high]) => match u16::from_le_bytes([low, high]) {
+            0 => 0,
+            repeats => repeats.saturating_add(1),
+        },
+        _ => 1,
+    }
+}
+
+fn webp_plays(data: &[u8]) -> u16 {
+    metadata::webp_chunks(data)
+        .and_then(|chunks| chunks.into_iter().find(|(id, _)| id == b"ANIM"))
+        .and_then(|(_, body)| body.get(4..6).map(|count| u16::from_le_bytes([count[0], count[1]])))
+        .unwrap_or(0)
+}
+
+/// Frame and play counts from the `acTL` chunk, which must precede the
+/// image data.
+fn apng_control(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 8;
+    while offset + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[offset..offset + 4].try_into().ok()?) as usize;
+        let kind = &data[offset + 4..offset + 8];
+        if kind == b"IDAT" {
+            return None;
+        }
+        if kind == b"acTL" {
+            let body = data.get(offset + 8..offset + 16)?;
+            return Some((
+                u32::from_be_bytes(body[..4].try_into().ok()?),
+                u32::from_be_bytes(body[4..].try_into().ok()?),
+            ));
+        }
+        offset += 12 + size;
+    }
+    None
+}
+
+fn encode_gif(animation: &Animation) -> Result<Vec<u8>, ImageError> {
+    let mut output = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut output, 10);
+        encoder.set_repeat(match animation.plays {
+            0 => Repeat::Infinite,
+            plays => Repeat::Finite(plays - 1),
+        })?;
+        encoder.encode_frames(animation.frames.iter().cloned())?;
+    }
+    Ok(output)
+}
+
+/// Each frame is encoded by libwebp as a still, lossy at the WebP quality or
+/// lossless with `--webp-lossless` and `--webp-near-lossless`, and its
+/// bitstream (with any alpha chunk) moved into an `ANMF` chunk.
+fn encode_webp(converter: &ImageConverter, animation: &Animation) -> Result<Vec<u8>, ImageError> {
+    let error = |message: &str| encoding_error(ImageFormatHint::Exact(ImageFormat::WebP), message);
+    let (width, height) = animation.frames[0].buffer().dimensions();
+
+    let mut bodies = Vec::with_capacity(animation.frames.len());
+    let mut has_alpha = false;
+    for frame in &animation.frames {
+        let buffer = frame.buffer();
+        if buffer.dimensions() != (width, height) {
+            return Err(error("animation frames differ in size"));
+        }
+        let translucent = buffer.pixels().any(|pixel| pixel[3] != 255);
+        has_alpha |= translucent;
+
+        let image = if translucent {
+            DynamicImage::ImageRgba8(buffer.clone())
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(buffer.clone()).to_rgb8())
+        };
+        let still = converter.encode_webp(&image, converter.quality_for(SupportedFormat::WebP))?;
+        // A lossy frame with alpha is an ALPH chunk followed by VP8.
+        let mut bitstream = Vec::new();
+        for (id, body) in metadata::webp_chunks(&still).unwrap_or_default() {
+            if matches!(&id, b"ALPH" | b"VP8 " | b"VP8L") {
+                bitstream.extend_from_slice(&id);
+                bitstream.extend_from_slice(&(body.len() as u32).to_le_bytes());
+                bitstream.extend_from_slice(body);
+                if body.len() % 2 == 1 {
+                    bitstream.push(0);
+                }
+            }
+        }
+        if bitstream.is_empty() {
+            return Err(error("encoder produced no frame bitstream"));
+        }
+
+        let duration = delay_ms(frame).min(0xFF_FFFF);
+
+        // ANMF header: offset, size, duration, then "do not blend" with no
+        // disposal, since every frame already covers the whole canvas.
+        let mut body = vec![0; 6];
+        body.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        body.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        body.extend_from_slice(&duration.to_le_bytes()[..3]);
+        body.push(0x02);
+        body.extend_from_slice(&bitstream);
+        bodies.push(body);
+    }
+
+    let mut vp8x = vec![0x02 | if has_alpha { 0x10 } else { 0 }, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    // Transparent background, then the loop count.
+    let mut anim = vec![0u8; 4];
+    anim.extend_from_slice(&animation.plays.to_le_bytes());
+
+    let mut chunks: Vec<(&[u8; 4], &[u8])> = vec![(b"VP8X", &vp8x), (b"ANIM", &anim)];
+    chunks.extend(bodies.iter().map(|body| (b"ANMF", body.as_slice())));
+    Ok(metadata::webp_container(&chunks))
+}
+
+/// Writes full-canvas RGBA frames that replace one another (`APNG_BLEND_OP_SOURCE`,
+/// `APNG_DISPOSE_OP_NONE`).
+fn encode_apng(animation: &Animation) -> Result<Vec<u8>, ImageError> {
+    let error = |e: png::EncodingError| encoding_error(ImageFormatHint::Exact(ImageFormat::Png), e);
+    let (width, height) = animation.frames[0].buffer().dimensions();
+
+    let mut output = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut output, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(animation.frames.len() as u32, u32::from(animation.plays))
+            .map_err(error)?;
+        let mut writer = encoder.write_header().map_err(error)?;
+        for frame in &animation.frames {
+            if frame.buffer().dimensions() != (width, height) {
+                return Err(encoding_error(ImageFormatHint::Exact(ImageFormat::Png), "animation frames differ in size"));
+            }
+            writer.set_frame_delay(delay_ms(frame).min(u32::from(u16::MAX)) as u16, 1000).map_err(error)?;
+            writer.set_blend_op(png::BlendOp::Source).map_err(error)?;
+            writer.set_dispose_op(png::DisposeOp::None).map_err(error)?;
+            writer.write_image_data(frame.buffer().as_raw()).map_err(error)?;
+        }
+        writer.finish().map_err(error)?;
+    }
+    Ok(output)
+}
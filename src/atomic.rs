@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Numbers temporary files so threads writing the same output don't share one.
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary files being written right now, for [`remove_temporaries`].
+static WRITING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Writes `contents` to `path` so that readers only ever see the old file or
+/// the complete new one: the bytes go to a hidden temporary file in the same
+/// directory, which is renamed over `path` once fully written and removed
+/// if anything fails. A process killed midway, or a crash before the data
+/// reaches the disk, leaves at most that temporary file, never a truncated
+/// `path`.
+///
+/// A `path` that already exists keeps its permissions. When it's a symbolic
+/// link to an existing file, that file is replaced, not the link.
+pub fn write(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let resolved;
+    let path = if path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        resolved.as_path()
+    } else {
+        path
+    };
+    let permissions = std::fs::metadata(path).ok().map(|metadata| metadata.permissions());
+
+    let temp = temp_path(path);
+    WRITING.lock().unwrap_or_else(PoisonError::into_inner).push(temp.clone());
+    let result = File::create(&temp)
+        .and_then(|file| {
+            let mut output = BufWriter::new(file);
+            output.write_all(contents)?;
+            let file = output.into_inner().map_err(|e| e.into_error())?;
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    WRITING.lock().unwrap_or_else(PoisonError::into_inner).retain(|writing| *writing != temp);
+    result
+}
+
+/// Removes the temporary files of writes still in progress, for when the
+/// process is about to exit without finishing them.
+pub fn remove_temporaries() {
+    for temp in WRITING.lock().unwrap_or_else(PoisonError::into_inner).drain(..) {
+        let _ = std::fs::remove_file(temp);
+    }
+}
+
+/// `.photo.webp.<pid>-<n>.tmp` beside `path`. The leading dot keeps it out
+/// of directory listings and the extension out of batch inputs.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
@@ -0,0 +1,213 @@
+use image::error::{EncodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat};
+use rav1e::color::{ChromaSampling, ColorDescription, ColorPrimaries, MatrixCoefficients, PixelRange, TransferCharacteristics};
+use rav1e::config::SpeedSettings;
+use rav1e::data::FrameType;
+use rav1e::{Config, Context, EncoderConfig, EncoderStatus, Frame, Pixel};
+
+use crate::color::Subsampling;
+
+/// BT.601 luma weights, the matrix AVIF decoders assume without a `colr` box.
+const KR: f32 = 0.299;
+const KB: f32 = 0.114;
+
+/// AVIF encoder settings.
+#[derive(Debug, Clone, Copy)]
+pub struct AvifOptions {
+    /// rav1e speed preset, 0 (slowest, smallest) to 10 (fastest).
+    pub speed: u8,
+    /// Quality for the alpha plane; the color quality when unset.
+    pub alpha_quality: Option<u8>,
+    /// 8, 10 or 12 bits per sample.
+    pub bit_depth: u8,
+    pub subsampling: Subsampling,
+}
+
+impl Default for AvifOptions {
+    fn default() -> Self {
+        Self {
+            speed: 4,
+            alpha_quality: None,
+            bit_depth: 8,
+            subsampling: Subsampling::Yuv444,
+        }
+    }
+}
+
+fn error(message: impl std::fmt::Display) -> ImageError {
+    ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::Avif), message.to_string()))
+}
+
+/// Encodes `image` as AVIF with rav1e: full-range BT.601 YCbCr with sRGB
+/// primaries and transfer, plus a separate monochrome plane for alpha when
+/// any pixel is transparent.
+pub fn encode(image: &DynamicImage, quality: u8, options: AvifOptions) -> Result<Vec<u8>, ImageError> {
+    // 16-bit sources keep their precision for 10- and 12-bit output.
+    let pixels: Vec<[f32; 4]> = if image.color().bytes_per_pixel() / image.color().channel_count() > 1 {
+        image
+            .to_rgba16()
+            .pixels()
+            .map(|pixel| pixel.0.map(|sample| f32::from(sample) / 65535.0))
+            .collect()
+    } else {
+        image
+            .to_rgba8()
+            .pixels()
+            .map(|pixel| pixel.0.map(|sample| f32::from(sample) / 255.0))
+            .collect()
+    };
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let has_alpha = pixels.iter().any(|pixel| pixel[3] < 1.0);
+
+    let color_quantizer = quality_to_quantizer(quality);
+    let alpha_quantizer = quality_to_quantizer(options.alpha_quality.unwrap_or(quality));
+    let (color, alpha) = match options.bit_depth {
+        8 => encode_planes::<u8>(&pixels, width, height, options, color_quantizer, alpha_quantizer, has_alpha)?,
+        _ => encode_planes::<u16>(&pixels, width, height, options, color_quantizer, alpha_quantizer, has_alpha)?,
+    };
+
+    let mut avif = avif_serialize::Aviffy::new();
+    match options.subsampling {
+        // AV1 Main profile covers 4:2:0, High adds 4:4:4 and Professional 4:2:2.
+        Subsampling::Yuv420 => avif.set_seq_profile(0).set_chroma_subsampling((true, true)),
+        Subsampling::Yuv422 => avif.set_seq_profile(2).set_chroma_subsampling((true, false)),
+        Subsampling::Yuv444 => avif.set_seq_profile(1).set_chroma_subsampling((false, false)),
+    };
+    avif.set_matrix_coefficients(avif_serialize::constants::MatrixCoefficients::Bt601);
+    Ok(avif.to_vec(&color, alpha.as_deref(), width as u32, height as u32, options.bit_depth))
+}
+
+/// ravif's mapping from a 1-100 quality to rav1e's 0-255 quantizer.
+fn quality_to_quantizer(quality: u8) -> usize {
+    let quality = f32::from(quality.clamp(1, 100)) / 100.0;
+    let x = if quality >= 0.85 {
+        (1.0 - quality) * 3.0
+    } else if quality > 0.25 {
+        1.0 - 0.125 - quality * 0.5
+    } else {
+        1.0 - quality
+    };
+    (x * 255.0).round() as usize
+}
+
+fn encode_planes<P: Pixel + Default>(
+    pixels: &[[f32; 4]],
+    width: usize,
+    height: usize,
+    options: AvifOptions,
+    color_quantizer: usize,
+    alpha_quantizer: usize,
+    has_alpha: bool,
+) -> Result<(Vec<u8>, Option<Vec<u8>>), ImageError> {
+    let max = f32::from((1u16 << options.bit_depth) - 1);
+    let to_sample = |value: f32| P::cast_from((value.clamp(0.0, 1.0) * max).round() as u16);
+
+    let luma: Vec<f32> = pixels
+        .iter()
+        .map(|[r, g, b, _]| KR * r + (1.0 - KR - KB) * g + KB * b)
+        .collect();
+    let blue_difference = |index: usize| (pixels[index][2] - luma[index]) / (2.0 * (1.0 - KB)) + 0.5;
+    let red_difference = |index: usize| (pixels[index][0] - luma[index]) / (2.0 * (1.0 - KR)) + 0.5;
+
+    let chroma_sampling = match options.subsampling {
+        Subsampling::Yuv420 => ChromaSampling::Cs420,
+        Subsampling::Yuv422 => ChromaSampling::Cs422,
+        Subsampling::Yuv444 => ChromaSampling::Cs444,
+    };
+    let (step_x, step_y) = options.subsampling.factors();
+    let (chroma_width, chroma_height) = (width.div_ceil(step_x), height.div_ceil(step_y));
+    // Chroma samples average the pixels they cover.
+    let chroma = |difference: &dyn Fn(usize) -> f32, x: usize, y: usize| {
+        let (mut sum, mut count) = (0.0, 0.0);
+        for py in step_y * y..(step_y * y + step_y).min(height) {
+            for px in step_x * x..(step_x * x + step_x).min(width) {
+                sum += difference(py * width + px);
+                count += 1.0;
+            }
+        }
+        sum / count
+    };
+
+    let color_description = ColorDescription {
+        color_primaries: ColorPrimaries::BT709,
+        transfer_characteristics: TransferCharacteristics::SRGB,
+        matrix_coefficients: MatrixCoefficients::BT601,
+    };
+    let color = encode_frame::<P>(
+        width,
+        height,
+        options,
+        color_quantizer,
+        chroma_sampling,
+        Some(color_description),
+        |frame| {
+            fill(&mut frame.planes[0], width, height, |x, y| to_sample(luma[y * width + x]));
+            fill(&mut frame.planes[1], chroma_width, chroma_height, |x, y| to_sample(chroma(&blue_difference, x, y)));
+            fill(&mut frame.planes[2], chroma_width, chroma_height, |x, y| to_sample(chroma(&red_difference, x, y)));
+        },
+    )?;
+
+    let alpha = if has_alpha {
+        Some(encode_frame::<P>(width, height, options, alpha_quantizer, ChromaSampling::Cs400, None, |frame| {
+            fill(&mut frame.planes[0], width, height, |x, y| to_sample(pixels[y * width + x][3]));
+        })?)
+    } else {
+        None
+    };
+    Ok((color, alpha))
+}
+
+fn fill<P: Pixel>(plane: &mut rav1e::prelude::Plane<P>, width: usize, height: usize, sample: impl Fn(usize, usize) -> P) {
+    let mut slice = plane.mut_slice(Default::default());
+    for (y, row) in slice.rows_iter_mut().take(height).enumerate() {
+        for (x, value) in row[..width].iter_mut().enumerate() {
+            *value = sample(x, y);
+        }
+    }
+}
+
+/// Encodes a single still frame and returns its AV1 bitstream.
+fn encode_frame<P: Pixel>(
+    width: usize,
+    height: usize,
+    options: AvifOptions,
+    quantizer: usize,
+    chroma_sampling: ChromaSampling,
+    color_description: Option<ColorDescription>,
+    init: impl FnOnce(&mut Frame<P>),
+) -> Result<Vec<u8>, ImageError> {
+    let config = Config::new().with_encoder_config(EncoderConfig {
+        width,
+        height,
+        bit_depth: usize::from(options.bit_depth),
+        chroma_sampling,
+        pixel_range: PixelRange::Full,
+        color_description,
+        still_picture: true,
+        quantizer,
+        min_quantizer: quantizer as u8,
+        speed_settings: SpeedSettings::from_preset(options.speed),
+        ..EncoderConfig::default()
+    });
+    let mut context: Context<P> = config.new_context().map_err(error)?;
+
+    let mut frame = context.new_frame();
+    init(&mut frame);
+    context.send_frame(frame).map_err(error)?;
+    context.flush();
+
+    let mut bitstream = Vec::new();
+    loop {
+        match context.receive_packet() {
+            Ok(mut packet) => {
+                if packet.frame_type == FrameType::KEY {
+                    bitstream.append(&mut packet.data);
+                }
+            }
+            Err(EncoderStatus::Encoded) => continue,
+            Err(EncoderStatus::LimitReached) => break,
+            Err(e) => return Err(error(e)),
+        }
+    }
+    Ok(bitstream)
+}
@@ -0,0 +1,210 @@
+use std::f32::consts::PI;
+
+use image::DynamicImage;
+
+/// BlurHash's base-83 digit alphabet.
+const BASE83: &[u8; 83] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+/// Hashes are computed from a copy at most this large; the result only
+/// holds a handful of cosine components anyway.
+const ANALYSIS_SIZE: u32 = 64;
+
+fn base83(value: u32, digits: u32, output: &mut String) {
+    for digit in (0..digits).rev() {
+        output.push(BASE83[(value / 83u32.pow(digit) % 83) as usize] as char);
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = f32::from(value) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u32 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0 + 0.5) as u32
+}
+
+/// Encodes `image` as a BlurHash with `x` by `y` components (1-9 each).
+/// Transparency is ignored, as in the reference encoder.
+pub fn blurhash(image: &DynamicImage, x: u32, y: u32) -> String {
+    let pixels = image.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).into_rgb8();
+    let (width, height) = pixels.dimensions();
+    let linear: Vec<[f32; 3]> = pixels.pixels().map(|pixel| pixel.0.map(srgb_to_linear)).collect();
+
+    let mut factors = Vec::with_capacity((x * y) as usize);
+    for j in 0..y {
+        for i in 0..x {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0f32; 3];
+            for py in 0..height {
+                let basis_y = (PI * j as f32 * py as f32 / height as f32).cos();
+                for px in 0..width {
+                    let basis = basis_y * (PI * i as f32 * px as f32 / width as f32).cos();
+                    let color = linear[(py * width + px) as usize];
+                    for channel in 0..3 {
+                        factor[channel] += basis * color[channel];
+                    }
+                }
+            }
+            let scale = normalisation / (width * height) as f32;
+            factors.push(factor.map(|value| value * scale));
+        }
+    }
+
+    let mut hash = String::new();
+    base83((x - 1) + (y - 1) * 9, 1, &mut hash);
+
+    let (dc, ac) = factors.split_first().expect("at least one component");
+    let maximum = if ac.is_empty() {
+        base83(0, 1, &mut hash);
+        1.0
+    } else {
+        let actual = ac.iter().flatten().fold(0.0f32, |maximum, value| maximum.max(value.abs()));
+        let quantised = (actual * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+        base83(quantised, 1, &mut hash);
+        (quantised + 1) as f32 / 166.0
+    };
+
+    base83((linear_to_srgb(dc[0]) << 16) + (linear_to_srgb(dc[1]) << 8) + linear_to_srgb(dc[2]), 4, &mut hash);
+    for factor in ac {
+        let quantise = |value: f32| {
+            let scaled = value / maximum;
+            (scaled.signum() * scaled.abs().sqrt() * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u32
+        };
+        base83(quantise(factor[0]) * 19 * 19 + quantise(factor[1]) * 19 + quantise(factor[2]), 2, &mut hash);
+    }
+    hash
+}
+
+/// Encodes `image` as a ThumbHash, base64 without padding. Unlike BlurHash
+/// it keeps the aspect ratio and alpha.
+pub fn thumbhash(image: &DynamicImage) -> String {
+    let pixels = image.thumbnail(100, 100).into_rgba8();
+    let (w, h) = (pixels.width() as usize, pixels.height() as usize);
+    let rgba: Vec<[f32; 4]> = pixels.pixels().map(|pixel| pixel.0.map(f32::from)).collect();
+
+    // Average color, weighted by alpha.
+    let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+    for [r, g, b, a] in &rgba {
+        let alpha = a / 255.0;
+        avg_r += alpha / 255.0 * r;
+        avg_g += alpha / 255.0 * g;
+        avg_b += alpha / 255.0 * b;
+        avg_a += alpha;
+    }
+    if avg_a > 0.0 {
+        avg_r /= avg_a;
+        avg_g /= avg_a;
+        avg_b /= avg_a;
+    }
+
+    let has_alpha = avg_a < (w * h) as f32;
+    let l_limit = if has_alpha { 5.0 } else { 7.0 };
+    let longest = w.max(h) as f32;
+    let lx = ((l_limit * w as f32 / longest).round() as usize).max(1);
+    let ly = ((l_limit * h as f32 / longest).round() as usize).max(1);
+
+    // Luminance, yellow-blue, red-green and alpha, composited over the average.
+    let (mut l, mut p, mut q, mut a) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for [r, g, b, alpha] in &rgba {
+        let alpha = alpha / 255.0;
+        let r = avg_r * (1.0 - alpha) + alpha / 255.0 * r;
+        let g = avg_g * (1.0 - alpha) + alpha / 255.0 * g;
+        let b = avg_b * (1.0 - alpha) + alpha / 255.0 * b;
+        l.push((r + g + b) / 3.0);
+        p.push((r + g) / 2.0 - b);
+        q.push(r - g);
+        a.push(alpha);
+    }
+
+    let encode_channel = |channel: &[f32], nx: usize, ny: usize| -> (f32, Vec<f32>, f32) {
+        let (mut dc, mut ac, mut scale) = (0.0, Vec::new(), 0.0f32);
+        for cy in 0..ny {
+            let mut cx = 0;
+            while cx * ny < nx * (ny - cy) {
+                let fx: Vec<f32> = (0..w).map(|x| (PI / w as f32 * cx as f32 * (x as f32 + 0.5)).cos()).collect();
+                let mut f = 0.0;
+                for y in 0..h {
+                    let fy = (PI / h as f32 * cy as f32 * (y as f32 + 0.5)).cos();
+                    for x in 0..w {
+                        f += channel[x + y * w] * fx[x] * fy;
+                    }
+                }
+                f /= (w * h) as f32;
+                if cx > 0 || cy > 0 {
+                    ac.push(f);
+                    scale = scale.max(f.abs());
+                } else {
+                    dc = f;
+                }
+                cx += 1;
+            }
+        }
+        if scale > 0.0 {
+            for value in &mut ac {
+                *value = 0.5 + 0.5 / scale * *value;
+            }
+        }
+        (dc, ac, scale)
+    };
+
+    let (l_dc, l_ac, l_scale) = encode_channel(&l, lx.max(3), ly.max(3));
+    let (p_dc, p_ac, p_scale) = encode_channel(&p, 3, 3);
+    let (q_dc, q_ac, q_scale) = encode_channel(&q, 3, 3);
+    let alpha = has_alpha.then(|| encode_channel(&a, 5, 5));
+
+    let is_landscape = w > h;
+    let header24 = (63.0 * l_dc).round() as u32
+        | ((31.5 + 31.5 * p_dc).round() as u32) << 6
+        | ((31.5 + 31.5 * q_dc).round() as u32) << 12
+        | ((31.0 * l_scale).round() as u32) << 18
+        | u32::from(has_alpha) << 23;
+    let header16 = (if is_landscape { ly } else { lx }) as u32
+        | ((63.0 * p_scale).round() as u32) << 3
+        | ((63.0 * q_scale).round() as u32) << 9
+        | u32::from(is_landscape) << 15;
+    let mut hash = vec![
+        header24 as u8,
+        (header24 >> 8) as u8,
+        (header24 >> 16) as u8,
+        header16 as u8,
+        (header16 >> 8) as u8,
+    ];
+    if let Some((a_dc, _, a_scale)) = &alpha {
+        hash.push((15.0 * a_dc).round() as u8 | ((15.0 * a_scale).round() as u8) << 4);
+    }
+
+    let mut nibbles = l_ac.iter().chain(&p_ac).chain(&q_ac).collect::<Vec<_>>();
+    if let Some((_, a_ac, _)) = &alpha {
+        nibbles.extend(a_ac);
+    }
+    let start = hash.len();
+    for (index, value) in nibbles.into_iter().enumerate() {
+        if start + index / 2 >= hash.len() {
+            hash.push(0);
+        }
+        hash[start + index / 2] |= ((15.0 * value).round() as u8) << ((index & 1) * 4);
+    }
+    base64(&hash)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            output.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    output
+}
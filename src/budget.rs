@@ -0,0 +1,81 @@
+use image::error::{EncodingError, ImageFormatHint};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError};
+
+use crate::{ImageConverter, SupportedFormat};
+
+/// Lowest quality tried before giving up (or downscaling).
+const MIN_QUALITY: u8 = 10;
+/// Images aren't shrunk below this many pixels on their shorter side.
+const MIN_DIMENSION: u32 = 16;
+
+/// Encodes `image` to at most `max_size` bytes, metadata included. Formats
+/// with a quality setting are binary-searched for the highest quality (up
+/// to the converter's own for `format`) that fits; when even the lowest doesn't fit and
+/// `downscale` is set, the image is shrunk and the search repeated.
+pub fn encode(
+    converter: &ImageConverter,
+    image: &DynamicImage,
+    source: &[u8],
+    format: SupportedFormat,
+    max_size: u64,
+    downscale: bool,
+) -> Result<Vec<u8>, ImageError> {
+    let tunable = match format {
+        SupportedFormat::Jpeg | SupportedFormat::Avif => true,
+        SupportedFormat::WebP => !converter.webp_lossless && converter.webp_near_lossless.is_none(),
+        SupportedFormat::Jxl => !converter.jxl_lossless,
+        _ => false,
+    };
+    let fits = |encoded: &Vec<u8>| encoded.len() as u64 <= max_size;
+    let quality = converter.quality_for(format);
+
+    let mut image = image.clone();
+    loop {
+        let best = converter.encode(&image, source, format, quality)?;
+        if fits(&best) {
+            return Ok(best);
+        }
+        let mut smallest = best;
+
+        if tunable {
+            let (mut low, mut high) = (MIN_QUALITY, quality.saturating_sub(1));
+            let mut best = None;
+            while low <= high {
+                let quality = low + (high - low) / 2;
+                let encoded = converter.encode(&image, source, format, quality)?;
+                if fits(&encoded) {
+                    best = Some(encoded);
+                    low = quality + 1;
+                } else {
+                    smallest = encoded;
+                    high = quality - 1;
+                }
+            }
+            if let Some(encoded) = best {
+                return Ok(encoded);
+            }
+        }
+
+        let (width, height) = (image.width(), image.height());
+        if !downscale || width.min(height) <= MIN_DIMENSION {
+            return Err(ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Name(format.extension().to_string()),
+                format!(
+                    "can't fit {}x{} under {} bytes (smallest attempt: {} bytes){}",
+                    width,
+                    height,
+                    max_size,
+                    smallest.len(),
+                    if downscale { "" } else { "; --allow-downscale may help" },
+                ),
+            )));
+        }
+
+        // Size scales roughly with pixel count, so shrink by the square root
+        // of the overshoot, a little extra, and never by too much at once.
+        let factor = ((max_size as f64 / smallest.len() as f64).sqrt() * 0.95).clamp(0.5, 0.9);
+        let scaled = |side: u32| ((f64::from(side) * factor).round() as u32).max(MIN_DIMENSION.min(side));
+        image = image.resize_exact(scaled(width), scaled(height), FilterType::Lanczos3);
+    }
+}
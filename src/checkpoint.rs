@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{interrupt, remote, report};
+
+/// Where a batch keeps its checkpoint without `--checkpoint`, inside the
+/// output directory. The leading dot keeps it out of batch inputs.
+pub const FILE_NAME: &str = ".image-converter-checkpoint";
+
+/// Why `--resume` leaves an input out.
+pub const DONE: &str = "converted in an earlier run (--resume)";
+
+const HEADER: &str = "# image-converter checkpoint: inputs converted so far, one per line";
+
+/// The inputs a batch run has converted, written down as each one
+/// finishes so that `--resume` can carry on after a crash, a reboot or
+/// Ctrl+C without converting them again. The file is removed once a run
+/// converts everything without failures.
+pub struct Checkpoint {
+    path: PathBuf,
+    file: Mutex<File>,
+    completed: HashSet<PathBuf>,
+}
+
+impl Checkpoint {
+    /// Starts the checkpoint at `path` for a run, continuing the one there
+    /// when `resume` is set and starting afresh otherwise.
+    pub fn open(path: &Path, resume: bool) -> std::io::Result<Self> {
+        let completed = if resume && path.exists() {
+            completed(path)?
+        } else {
+            if resume {
+                eprintln!("Note: no checkpoint at {}; converting everything", path.display());
+            }
+            HashSet::new()
+        };
+        let mut file = if completed.is_empty() {
+            File::create(path)?
+        } else {
+            OpenOptions::new().append(true).open(path)?
+        };
+        if completed.is_empty() {
+            writeln!(file, "{}", HEADER)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            completed,
+        })
+    }
+
+    /// `inputs` without those an earlier run converted, which JSON mode
+    /// lists as skipped.
+    pub fn drop_completed(&self, inputs: Vec<PathBuf>, json: bool) -> Vec<PathBuf> {
+        if self.completed.is_empty() {
+            return inputs;
+        }
+        inputs
+            .into_iter()
+            .filter(|input| {
+                let done = self.completed.contains(&key(input));
+                if done && json {
+                    report::emit(&report::skipped(input, DONE));
+                }
+                !done
+            })
+            .collect()
+    }
+
+    /// Notes `input` as converted, straight to disk so a crash right after
+    /// doesn't lose it.
+    pub fn record(&self, input: &Path) {
+        let mut file = self.file.lock().unwrap();
+        let written = writeln!(file, "{}", key(input).display()).and_then(|()| file.flush());
+        if let Err(e) = written {
+            eprintln!("Warning: can't update checkpoint {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Ends the run, removing the checkpoint if nothing is left to resume:
+    /// no input failed and the run wasn't interrupted.
+    pub fn close(self, summary: &report::Summary, json: bool) {
+        if summary.failed == 0 && !interrupt::requested() {
+            let _ = std::fs::remove_file(&self.path);
+        } else if !json {
+            println!("Checkpoint kept at {}; add --resume to carry on", self.path.display());
+        }
+    }
+}
+
+/// The inputs recorded in the checkpoint at `path`.
+pub fn completed(path: &Path) -> std::io::Result<HashSet<PathBuf>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// How `input` is recorded: local files by absolute path, so a run resumed
+/// from another directory still recognises them, and URLs as they are.
+pub fn key(input: &Path) -> PathBuf {
+    if remote::is_url(&input.to_string_lossy()) {
+        return input.to_path_buf();
+    }
+    std::fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf())
+}
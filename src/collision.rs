@@ -0,0 +1,97 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::template;
+
+/// Hex digits of the content hash `--on-collision hash` appends.
+const HASH_LENGTH: usize = 8;
+
+/// What a batch run does when several of its inputs map to one output
+/// name, such as `photo.png` and `photo.jpg` both becoming `photo.webp`.
+/// Collisions are settled before anything is converted, so the outcome
+/// doesn't depend on which input finishes first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Convert nothing and list the colliding inputs.
+    #[default]
+    Error,
+    /// Keep the name for the first input and number the rest: `photo.webp`,
+    /// `photo-1.webp`, and so on.
+    Number,
+    /// Append a hash of each colliding input's contents, so names stay the
+    /// same from run to run whichever other inputs are present.
+    Hash,
+}
+
+impl Strategy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "error" => Ok(Strategy::Error),
+            "number" => Ok(Strategy::Number),
+            "hash" => Ok(Strategy::Hash),
+            _ => Err(format!("Invalid value for --on-collision: {} (expected error, number or hash)", value)),
+        }
+    }
+
+    /// Gives `inputs` (in run order) that share an entry in `outputs`
+    /// distinct output paths, or lists them under [`Strategy::Error`].
+    pub fn resolve(self, inputs: &[PathBuf], outputs: &mut HashMap<PathBuf, PathBuf>) -> Result<(), String> {
+        let mut sharing: HashMap<&Path, Vec<&PathBuf>> = HashMap::new();
+        for input in inputs {
+            sharing.entry(outputs[input].as_path()).or_default().push(input);
+        }
+        let mut collisions: Vec<(PathBuf, Vec<PathBuf>)> = sharing
+            .into_iter()
+            .filter(|(_, inputs)| inputs.len() > 1)
+            .map(|(output, inputs)| (output.to_path_buf(), inputs.into_iter().cloned().collect()))
+            .collect();
+        if collisions.is_empty() {
+            return Ok(());
+        }
+        collisions.sort();
+
+        if self == Strategy::Error {
+            let listed: Vec<String> = collisions
+                .iter()
+                .map(|(output, inputs)| {
+                    let inputs: Vec<String> = inputs.iter().map(|input| input.display().to_string()).collect();
+                    format!("  {} <- {}", output.display(), inputs.join(", "))
+                })
+                .collect();
+            return Err(format!(
+                "{} output(s) would be written by more than one input (use --on-collision number or hash to keep them all):\n{}",
+                collisions.len(),
+                listed.join("\n")
+            ));
+        }
+
+        let mut taken: HashSet<PathBuf> = outputs.values().cloned().collect();
+        for (output, colliding) in collisions {
+            for (index, input) in colliding.iter().enumerate() {
+                let renamed = match self {
+                    Strategy::Number if index == 0 => continue,
+                    Strategy::Number => (1..)
+                        .map(|number| suffixed(&output, &number.to_string()))
+                        .find(|candidate| !taken.contains(candidate))
+                        .unwrap_or_default(),
+                    _ => {
+                        let hash = format!("{:016x}", template::content_hash(input));
+                        suffixed(&output, &hash[..HASH_LENGTH])
+                    }
+                };
+                taken.insert(renamed.clone());
+                outputs.insert(input.clone(), renamed);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `photo.webp` with `suffix` added to the stem: `photo-<suffix>.webp`.
+fn suffixed(output: &Path, suffix: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    match output.extension() {
+        Some(extension) => output.with_file_name(format!("{}-{}.{}", stem, suffix, extension.to_string_lossy())),
+        None => output.with_file_name(format!("{}-{}", stem, suffix)),
+    }
+}
@@ -0,0 +1,56 @@
+use image::DynamicImage;
+use qcms::{DataType, Intent, Profile, Transform};
+
+/// Chroma resolution relative to luma in YCbCr encoders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsampling {
+    /// Half-resolution chroma both ways: smallest, fine for photos.
+    Yuv420,
+    /// Half horizontal chroma resolution.
+    Yuv422,
+    /// Full-resolution chroma, which keeps colored text and UI edges crisp.
+    Yuv444,
+}
+
+impl Subsampling {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "420" | "4:2:0" => Ok(Subsampling::Yuv420),
+            "422" | "4:2:2" => Ok(Subsampling::Yuv422),
+            "444" | "4:4:4" => Ok(Subsampling::Yuv444),
+            _ => Err(format!("Invalid chroma subsampling: {} (expected 420, 422 or 444)", value)),
+        }
+    }
+
+    /// Luma pixels per chroma sample, horizontally and vertically.
+    pub fn factors(self) -> (usize, usize) {
+        match self {
+            Subsampling::Yuv420 => (2, 2),
+            Subsampling::Yuv422 => (2, 1),
+            Subsampling::Yuv444 => (1, 1),
+        }
+    }
+}
+
+/// Converts pixel data tagged with the ICC profile `icc` to sRGB. 16-bit
+/// images are reduced to 8 bits, which is what the colour transform runs on.
+pub fn to_srgb(image: DynamicImage, icc: &[u8]) -> Result<DynamicImage, String> {
+    let source = Profile::new_from_slice(icc, false)
+        .ok_or_else(|| "unreadable ICC profile".to_string())?;
+    let srgb = Profile::new_sRGB();
+
+    let has_alpha = image.color().has_alpha();
+    let data_type = if has_alpha { DataType::RGBA8 } else { DataType::RGB8 };
+    let transform = Transform::new(&source, &srgb, data_type, Intent::Perceptual)
+        .ok_or_else(|| "ICC profile can't be converted to sRGB (only RGB profiles are supported)".to_string())?;
+
+    if has_alpha {
+        let mut pixels = image.into_rgba8();
+        transform.apply(&mut pixels);
+        Ok(DynamicImage::ImageRgba8(pixels))
+    } else {
+        let mut pixels = image.into_rgb8();
+        transform.apply(&mut pixels);
+        Ok(DynamicImage::ImageRgb8(pixels))
+    }
+}
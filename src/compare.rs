@@ -0,0 +1,240 @@
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use serde_json::{json, Value};
+
+/// Standard SSIM window: an 11x11 Gaussian with sigma 1.5.
+const WINDOW_RADIUS: usize = 5;
+const WINDOW_SIGMA: f32 = 1.5;
+/// SSIM stabilizers for 8-bit samples, (0.01 * 255)^2 and (0.03 * 255)^2.
+const C1: f32 = 6.5025;
+const C2: f32 = 58.5225;
+/// MS-SSIM scale weights from Wang, Simoncelli and Bovik (2003).
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// Similarity of two images of the same size.
+#[derive(Debug, Clone, Copy)]
+pub struct Scores {
+    /// Peak signal-to-noise ratio in dB over the color channels (and alpha
+    /// when either image has it); infinite for identical pixels.
+    pub psnr: f64,
+    /// Mean structural similarity of the luma planes, 1.0 when identical.
+    pub ssim: f64,
+    /// Multi-scale SSIM, which tracks perceived quality more closely by also
+    /// comparing coarser versions of the images.
+    pub ms_ssim: Option<f64>,
+    /// Fraction of pixels with any channel differing by more than the
+    /// threshold.
+    pub changed: f64,
+}
+
+impl Scores {
+    pub fn to_text(self) -> String {
+        let mut text = format!("PSNR: {}\nSSIM: {:.6}", format_psnr(self.psnr), self.ssim);
+        if let Some(ms_ssim) = self.ms_ssim {
+            text.push_str(&format!("\nMS-SSIM: {:.6}", ms_ssim));
+        }
+        text.push_str(&format!("\nChanged: {:.3}% of pixels", self.changed * 100.0));
+        text
+    }
+
+    pub fn to_json(self) -> Value {
+        let mut value = json!({
+            // JSON has no infinity; identical images report null.
+            "psnr": self.psnr.is_finite().then_some(self.psnr),
+            "ssim": self.ssim,
+            "changed_percent": self.changed * 100.0,
+        });
+        if let Some(ms_ssim) = self.ms_ssim {
+            value["ms_ssim"] = json!(ms_ssim);
+        }
+        value
+    }
+}
+
+fn format_psnr(psnr: f64) -> String {
+    if psnr.is_finite() {
+        format!("{:.2} dB", psnr)
+    } else {
+        "inf (identical)".to_string()
+    }
+}
+
+/// Scores `actual` against `reference`. With `perceptual`, MS-SSIM is
+/// computed as well; pixels count as changed when a channel differs by more
+/// than `threshold`.
+pub fn compare(reference: &DynamicImage, actual: &DynamicImage, perceptual: bool, threshold: u8) -> Result<Scores, String> {
+    if reference.dimensions() != actual.dimensions() {
+        let (a, b) = (reference.dimensions(), actual.dimensions());
+        return Err(format!("images differ in size: {}x{} and {}x{}", a.0, a.1, b.0, b.1));
+    }
+
+    let (width, height) = (reference.width() as usize, reference.height() as usize);
+    let (x, y) = (luma(reference), luma(actual));
+    let ssim = ssim(&x, &y, width, height).0;
+    let ms_ssim = perceptual.then(|| ms_ssim(x, y, width, height));
+
+    let differences = differences(reference, actual);
+    let changed = differences.iter().filter(|&&difference| difference > threshold).count();
+    Ok(Scores {
+        psnr: psnr(reference, actual),
+        ssim,
+        ms_ssim,
+        changed: changed as f64 / differences.len().max(1) as f64,
+    })
+}
+
+/// Largest channel difference of every pixel, alpha included.
+fn differences(reference: &DynamicImage, actual: &DynamicImage) -> Vec<u8> {
+    let (a, b) = (reference.to_rgba8(), actual.to_rgba8());
+    a.pixels()
+        .zip(b.pixels())
+        .map(|(p, q)| p.0.iter().zip(q.0).map(|(&x, y)| x.abs_diff(y)).max().unwrap_or(0))
+        .collect()
+}
+
+/// Renders where `actual` departs from `reference`: a faded grayscale copy
+/// of the reference for context, with changed pixels painted from yellow
+/// (slight) to red (large). Both images must be the same size.
+pub fn diff_image(reference: &DynamicImage, actual: &DynamicImage, threshold: u8) -> RgbImage {
+    let differences = differences(reference, actual);
+    let context = reference.to_luma8();
+    RgbImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let difference = differences[(y * reference.width() + x) as usize];
+        if difference > threshold {
+            // The square root keeps small differences visible.
+            let strength = (f32::from(difference) / 255.0).sqrt();
+            Rgb([255, (255.0 * (1.0 - strength)).round() as u8, 0])
+        } else {
+            let luma = f32::from(context.get_pixel(x, y)[0]);
+            let faded = (255.0 - (255.0 - luma) * 0.25).round() as u8;
+            Rgb([faded, faded, faded])
+        }
+    })
+}
+
+/// Peak signal-to-noise ratio in dB of two images of the same size.
+pub fn psnr(reference: &DynamicImage, actual: &DynamicImage) -> f64 {
+    let channels = if reference.color().has_alpha() || actual.color().has_alpha() { 4 } else { 3 };
+    let (a, b) = (reference.to_rgba8(), actual.to_rgba8());
+    let (mut sum, mut count) = (0.0f64, 0usize);
+    for (p, q) in a.pixels().zip(b.pixels()) {
+        for channel in 0..channels {
+            let difference = f64::from(p[channel]) - f64::from(q[channel]);
+            sum += difference * difference;
+        }
+        count += channels;
+    }
+    let mse = sum / count.max(1) as f64;
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+/// BT.601 luma in 0-255.
+fn luma(image: &DynamicImage) -> Vec<f32> {
+    image
+        .to_rgb8()
+        .pixels()
+        .map(|pixel| 0.299 * f32::from(pixel[0]) + 0.587 * f32::from(pixel[1]) + 0.114 * f32::from(pixel[2]))
+        .collect()
+}
+
+/// Separable Gaussian blur with clamped edges.
+fn blur(plane: &[f32], width: usize, height: usize, kernel: &[f32]) -> Vec<f32> {
+    let radius = kernel.len() / 2;
+    let clamp = |value: isize, limit: usize| value.clamp(0, limit as isize - 1) as usize;
+    let mut horizontal = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| weight * plane[y * width + clamp(x as isize + i as isize - radius as isize, width)])
+                .sum();
+        }
+    }
+    let mut output = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            output[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| weight * horizontal[clamp(y as isize + i as isize - radius as isize, height) * width + x])
+                .sum();
+        }
+    }
+    output
+}
+
+fn gaussian_kernel() -> Vec<f32> {
+    let kernel: Vec<f32> = (0..=2 * WINDOW_RADIUS)
+        .map(|i| {
+            let distance = i as f32 - WINDOW_RADIUS as f32;
+            (-distance * distance / (2.0 * WINDOW_SIGMA * WINDOW_SIGMA)).exp()
+        })
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    kernel.into_iter().map(|weight| weight / total).collect()
+}
+
+/// Mean SSIM and mean contrast-structure term of two luma planes.
+fn ssim(x: &[f32], y: &[f32], width: usize, height: usize) -> (f64, f64) {
+    let kernel = gaussian_kernel();
+    let product = |a: &[f32], b: &[f32]| -> Vec<f32> { a.iter().zip(b).map(|(a, b)| a * b).collect() };
+    let mu_x = blur(x, width, height, &kernel);
+    let mu_y = blur(y, width, height, &kernel);
+    let xx = blur(&product(x, x), width, height, &kernel);
+    let yy = blur(&product(y, y), width, height, &kernel);
+    let xy = blur(&product(x, y), width, height, &kernel);
+
+    let (mut ssim_sum, mut cs_sum) = (0.0f64, 0.0f64);
+    for i in 0..x.len() {
+        let (mx, my) = (mu_x[i], mu_y[i]);
+        let variance_x = xx[i] - mx * mx;
+        let variance_y = yy[i] - my * my;
+        let covariance = xy[i] - mx * my;
+        let cs = (2.0 * covariance + C2) / (variance_x + variance_y + C2);
+        let luminance = (2.0 * mx * my + C1) / (mx * mx + my * my + C1);
+        ssim_sum += f64::from(luminance * cs);
+        cs_sum += f64::from(cs);
+    }
+    let count = x.len().max(1) as f64;
+    (ssim_sum / count, cs_sum / count)
+}
+
+/// MS-SSIM over up to five dyadic scales, stopping early when the image
+/// gets smaller than the window; the weights of the scales used are
+/// renormalized.
+fn ms_ssim(mut x: Vec<f32>, mut y: Vec<f32>, mut width: usize, mut height: usize) -> f64 {
+    let mut terms = Vec::new();
+    for (scale, &weight) in MS_SSIM_WEIGHTS.iter().enumerate() {
+        let (ssim, cs) = ssim(&x, &y, width, height);
+        let last = scale + 1 == MS_SSIM_WEIGHTS.len() || width.min(height) / 2 < 2 * WINDOW_RADIUS + 1;
+        if last {
+            terms.push((ssim, weight));
+            break;
+        }
+        terms.push((cs, weight));
+        (x, y) = (downsample(&x, width, height), downsample(&y, width, height));
+        (width, height) = (width / 2, height / 2);
+    }
+    let total: f64 = terms.iter().map(|(_, weight)| weight).sum();
+    terms
+        .iter()
+        .map(|(value, weight)| value.max(0.0).powf(weight / total))
+        .product()
+}
+
+/// Halves a plane by averaging 2x2 blocks.
+fn downsample(plane: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let (half_width, half_height) = (width / 2, height / 2);
+    let mut output = Vec::with_capacity(half_width * half_height);
+    for y in 0..half_height {
+        for x in 0..half_width {
+            let at = |dx: usize, dy: usize| plane[(2 * y + dy) * width + 2 * x + dx];
+            output.push((at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) / 4.0);
+        }
+    }
+    output
+}
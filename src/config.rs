@@ -0,0 +1,100 @@
+use std::env;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::{CliOptions, SupportedFormat};
+
+/// Prefix of the environment variables read as defaults.
+const ENV_PREFIX: &str = "IMAGE_CONVERTER_";
+
+/// Settings used when the command line (or a preset) doesn't give them,
+/// from the config file and then the environment, which wins.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Defaults {
+    pub quality: Option<u8>,
+    pub jobs: Option<usize>,
+    pub format: Option<SupportedFormat>,
+}
+
+impl Defaults {
+    /// Reads `$IMAGE_CONVERTER_CONFIG`, or `config.toml` under
+    /// `$XDG_CONFIG_HOME/image-converter` (`~/.config` by default) when it
+    /// exists, then `IMAGE_CONVERTER_QUALITY`, `_JOBS` and `_FORMAT`.
+    pub fn load() -> Result<Self, String> {
+        let mut defaults = Defaults::default();
+        let path = match env::var_os(format!("{}CONFIG", ENV_PREFIX)) {
+            Some(path) => Some(PathBuf::from(path)),
+            None => config_path().filter(|path| path.is_file()),
+        };
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+            let invalid = |e: String| format!("Invalid config {}: {}", path.display(), e);
+            let Value::Object(table) = toml::from_str(&text).map_err(|e: toml::de::Error| invalid(e.to_string()))? else {
+                return Err(invalid("expected a table of settings".to_string()));
+            };
+            for (key, value) in &table {
+                let value = match value {
+                    Value::String(text) => text.clone(),
+                    Value::Number(number) => number.to_string(),
+                    _ => return Err(invalid(format!("{} must be a string or number", key))),
+                };
+                defaults.set(key, &value).map_err(invalid)?;
+            }
+        }
+
+        for key in ["quality", "jobs", "format"] {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Ok(value) = env::var(&name) {
+                defaults.set(key, &value).map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+        Ok(defaults)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "quality" => {
+                self.quality = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|quality| (1..=100).contains(quality))
+                        .ok_or_else(|| format!("Invalid value for quality: {} (expected 1-100)", value))?,
+                );
+            }
+            "jobs" => {
+                self.jobs = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid value for jobs: {} (expected a number)", value))?,
+                );
+            }
+            "format" => self.format = Some(SupportedFormat::from_extension(value.trim())?),
+            _ => return Err(format!("Unknown setting: {} (expected quality, jobs or format)", key)),
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, options: &mut CliOptions) {
+        if options.quality.is_none() {
+            options.quality = self.quality;
+        }
+        if options.jobs == 0 {
+            options.jobs = self.jobs.unwrap_or(0);
+        }
+        if options.default_format.is_none() {
+            options.default_format = self.format;
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|base| !base.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("image-converter").join("config.toml"))
+}
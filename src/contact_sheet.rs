@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+use ab_glyph::FontArc;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use rayon::prelude::*;
+
+use crate::text::Caption;
+use crate::transform::Anchor;
+use crate::{numbered_path, thumbnail, ImageConverter, SupportedFormat};
+
+/// Space around and between cells, in pixels.
+const GAP: u32 = 12;
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([48, 48, 48, 255]);
+
+/// Grid shape of a contact sheet page.
+#[derive(Clone)]
+pub struct Layout {
+    pub columns: u32,
+    /// Rows per page; further images continue on numbered pages.
+    pub rows: u32,
+    /// Thumbnails fit in a `cell`x`cell` square.
+    pub cell: u32,
+    /// File name labels are drawn under each thumbnail with this font.
+    pub font: Option<FontArc>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            columns: 5,
+            rows: 6,
+            cell: 200,
+            font: None,
+        }
+    }
+}
+
+impl Layout {
+    fn label_size(&self) -> f32 {
+        (self.cell as f32 / 12.0).max(11.0)
+    }
+
+    fn label_height(&self) -> u32 {
+        if self.font.is_some() {
+            (self.label_size() * 1.5).ceil() as u32
+        } else {
+            0
+        }
+    }
+}
+
+/// Tiles thumbnails of every selected image under `input_dir`, in file name
+/// order, into grid pages on a white background. Returns the pages written:
+/// `output_path` itself, or `sheet-1.jpg`, `sheet-2.jpg`, ... when the images
+/// need more than one.
+pub fn generate(
+    converter: &ImageConverter,
+    input_dir: &Path,
+    output_path: &Path,
+    format: SupportedFormat,
+    layout: &Layout,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut inputs = converter.selected_inputs(input_dir)?;
+    inputs.sort();
+
+    let thumbnails: Vec<(String, RgbaImage)> = inputs
+        .par_iter()
+        .filter_map(|input| {
+            let loaded = converter
+                .read_input(input)
+                .and_then(|data| thumbnail::decode(converter, input, &data, layout.cell));
+            match loaded {
+                Ok(image) => {
+                    let name = input.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    Some((name, thumbnail::shrink(image, layout.cell).into_rgba8()))
+                }
+                Err(e) => {
+                    eprintln!("Warning: {}: {}; left off the contact sheet", input.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+    if thumbnails.is_empty() {
+        return Err(format!("no images found in {}", input_dir.display()).into());
+    }
+
+    let per_page = (layout.columns * layout.rows) as usize;
+    let count = thumbnails.len().div_ceil(per_page);
+    let mut written = Vec::with_capacity(count);
+    for (index, page) in thumbnails.chunks(per_page).enumerate() {
+        let sheet = render_page(page, layout);
+        let path = if count == 1 { output_path.to_path_buf() } else { numbered_path(output_path, index + 1, count) };
+        converter.save_image(&DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(sheet).into_rgb8()), &[], &path, format)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn render_page(thumbnails: &[(String, RgbaImage)], layout: &Layout) -> RgbaImage {
+    let rows = (thumbnails.len() as u32).div_ceil(layout.columns);
+    let columns = layout.columns.min(thumbnails.len() as u32);
+    let (cell_width, cell_height) = (layout.cell + GAP, layout.cell + layout.label_height() + GAP);
+    let mut sheet = RgbaImage::from_pixel(columns * cell_width + GAP, rows * cell_height + GAP, BACKGROUND);
+
+    for (index, (name, thumbnail)) in thumbnails.iter().enumerate() {
+        let (column, row) = (index as u32 % layout.columns, index as u32 / layout.columns);
+        let (left, top) = (GAP + column * cell_width, GAP + row * cell_height);
+        // Centred horizontally, sitting on the label.
+        let x = left + (layout.cell - thumbnail.width()) / 2;
+        let y = top + (layout.cell - thumbnail.height());
+        imageops::overlay(&mut sheet, thumbnail, i64::from(x), i64::from(y));
+
+        if let Some(font) = &layout.font {
+            let label = render_label(name, font, layout);
+            let x = left + layout.cell.saturating_sub(label.width()) / 2;
+            imageops::overlay(&mut sheet, &label, i64::from(x), i64::from(top + layout.cell + 2));
+        }
+    }
+    sheet
+}
+
+/// Renders `name`, shortened with an ellipsis until it fits the cell.
+fn render_label(name: &str, font: &FontArc, layout: &Layout) -> RgbaImage {
+    let mut caption = Caption {
+        text: name.to_string(),
+        font: font.clone(),
+        size: layout.label_size(),
+        anchor: Anchor::Top,
+        margin: 0,
+        color: LABEL_COLOR,
+        outline: Rgba([0, 0, 0, 0]),
+        outline_width: 0,
+    };
+    let mut chars: Vec<char> = name.chars().collect();
+    loop {
+        let label = caption.render();
+        if label.width() <= layout.cell || chars.len() <= 1 {
+            return label;
+        }
+        chars.pop();
+        caption.text = chars.iter().collect::<String>() + "…";
+    }
+}
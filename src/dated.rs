@@ -0,0 +1,138 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Conversion specifiers `--rename-by-date` understands.
+const SPECIFIERS: [char; 7] = ['Y', 'm', 'd', 'H', 'M', 'S', '%'];
+
+/// `--rename-by-date`: names outputs after when each photo was taken, as
+/// recorded in EXIF DateTimeOriginal, or failing that when the file was
+/// last modified. The pattern takes strftime-style `%Y`, `%m`, `%d`, `%H`,
+/// `%M` and `%S`; slashes in it nest outputs in directories, so
+/// `%Y/%m/%Y%m%d_%H%M%S` sorts a shoot into year and month folders.
+#[derive(Debug, Clone)]
+pub struct DateNaming {
+    pattern: String,
+}
+
+/// A calendar date and time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateNaming {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c == '%' {
+                match chars.next() {
+                    Some(specifier) if SPECIFIERS.contains(&specifier) => {}
+                    Some(specifier) => {
+                        return Err(format!(
+                            "Invalid value for --rename-by-date: {} (unknown %{}; expected %Y, %m, %d, %H, %M or %S)",
+                            pattern, specifier
+                        ))
+                    }
+                    None => return Err(format!("Invalid value for --rename-by-date: {} (trailing %)", pattern)),
+                }
+            }
+        }
+        if pattern.trim_matches('/').is_empty() {
+            return Err("--rename-by-date can't be empty".to_string());
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+        })
+    }
+
+    /// The name, without extension, for the local file `input`, or `None`
+    /// when it can't be read.
+    pub fn name(&self, input: &Path) -> Option<String> {
+        let taken = capture_time(input).or_else(|| {
+            let modified = std::fs::metadata(input).ok()?.modified().ok()?;
+            Some(Timestamp::from_system_time(modified))
+        })?;
+        Some(taken.format(&self.pattern))
+    }
+}
+
+impl Timestamp {
+    /// `time` in UTC.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let seconds = match time.duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        };
+        let (days, time_of_day) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400) as u32);
+        // Howard Hinnant's days-to-civil conversion.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+        Self {
+            year: year_of_era + era * 400 + i64::from(month <= 2),
+            month,
+            day,
+            hour: time_of_day / 3600,
+            minute: time_of_day / 60 % 60,
+            second: time_of_day % 60,
+        }
+    }
+
+    /// Expands the strftime-style specifiers in `pattern`.
+    pub fn format(&self, pattern: &str) -> String {
+        let mut formatted = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                formatted.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => formatted.push_str(&format!("{:04}", self.year)),
+                Some('m') => formatted.push_str(&format!("{:02}", self.month)),
+                Some('d') => formatted.push_str(&format!("{:02}", self.day)),
+                Some('H') => formatted.push_str(&format!("{:02}", self.hour)),
+                Some('M') => formatted.push_str(&format!("{:02}", self.minute)),
+                Some('S') => formatted.push_str(&format!("{:02}", self.second)),
+                Some(other) => formatted.push(other),
+                None => formatted.push('%'),
+            }
+        }
+        formatted
+    }
+}
+
+/// EXIF DateTimeOriginal of `input`, in the camera's local time.
+fn capture_time(input: &Path) -> Option<Timestamp> {
+    let mut reader = BufReader::new(File::open(input).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let taken = exif::DateTime::from_ascii(values.first()?).ok()?;
+    // Cameras without a clock set write zeros.
+    if taken.year == 0 || taken.month == 0 || taken.day == 0 {
+        return None;
+    }
+    Some(Timestamp {
+        year: i64::from(taken.year),
+        month: u32::from(taken.month),
+        day: u32::from(taken.day),
+        hour: u32::from(taken.hour),
+        minute: u32::from(taken.minute),
+        second: u32::from(taken.second),
+    })
+}
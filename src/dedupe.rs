@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use rayon::prelude::*;
+
+use crate::{thumbnail, ImageConverter};
+
+/// Perceptual hash algorithm, each producing 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashKind {
+    /// aHash: 8x8 thumbnail pixels against their mean. Fastest, but thrown
+    /// by brightness and contrast edits.
+    Average,
+    /// dHash: brightness gradients between neighbours of a 9x8 thumbnail.
+    Difference,
+    /// pHash: signs of the low-frequency DCT coefficients of a 32x32
+    /// thumbnail. Slowest, and the most robust to recompression and edits.
+    #[default]
+    Perceptual,
+}
+
+impl HashKind {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "a" | "ahash" | "average" => Ok(HashKind::Average),
+            "d" | "dhash" | "difference" => Ok(HashKind::Difference),
+            "p" | "phash" | "perceptual" => Ok(HashKind::Perceptual),
+            _ => Err(format!("Invalid hash: {} (expected ahash, dhash or phash)", value)),
+        }
+    }
+}
+
+/// How near-duplicates are found.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub hash: HashKind,
+    /// Largest Hamming distance between hashes still counted as a duplicate.
+    pub distance: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            hash: HashKind::Perceptual,
+            distance: 6,
+        }
+    }
+}
+
+pub fn hash(image: &DynamicImage, kind: HashKind) -> u64 {
+    let gray = |width, height| image.resize_exact(width, height, FilterType::Triangle).into_luma8();
+    let bits = |values: &mut dyn Iterator<Item = bool>| values.fold(0u64, |hash, bit| hash << 1 | u64::from(bit));
+    match kind {
+        HashKind::Average => {
+            let pixels = gray(8, 8);
+            let mean = pixels.pixels().map(|pixel| u32::from(pixel[0])).sum::<u32>() / 64;
+            bits(&mut pixels.pixels().map(|pixel| u32::from(pixel[0]) > mean))
+        }
+        HashKind::Difference => {
+            let pixels = gray(9, 8);
+            bits(&mut (0..8).flat_map(|y| (0..8).map(move |x| (x, y))).map(|(x, y)| {
+                pixels.get_pixel(x + 1, y)[0] > pixels.get_pixel(x, y)[0]
+            }))
+        }
+        HashKind::Perceptual => {
+            let pixels = gray(32, 32);
+            let samples: Vec<f32> = pixels.pixels().map(|pixel| f32::from(pixel[0])).collect();
+            let coefficients = dct_low_frequencies(&samples);
+            // The DC term only carries overall brightness.
+            let mut sorted: Vec<f32> = coefficients[1..].to_vec();
+            sorted.sort_by(f32::total_cmp);
+            let median = sorted[sorted.len() / 2];
+            bits(&mut coefficients.iter().map(|&coefficient| coefficient > median))
+        }
+    }
+}
+
+/// The top-left 8x8 coefficients of the 2-D DCT-II of a 32x32 block.
+fn dct_low_frequencies(samples: &[f32]) -> Vec<f32> {
+    const N: usize = 32;
+    let basis: Vec<f32> = (0..8)
+        .flat_map(|k| (0..N).map(move |n| (std::f32::consts::PI / N as f32 * (n as f32 + 0.5) * k as f32).cos()))
+        .collect();
+    // Rows first, then columns, keeping only the 8 lowest frequencies.
+    let mut rows = vec![0.0f32; N * 8];
+    for y in 0..N {
+        for k in 0..8 {
+            rows[y * 8 + k] = (0..N).map(|x| samples[y * N + x] * basis[k * N + x]).sum();
+        }
+    }
+    let mut output = vec![0.0f32; 64];
+    for k in 0..8 {
+        for u in 0..8 {
+            output[k * 8 + u] = (0..N).map(|y| rows[y * 8 + u] * basis[k * N + y]).sum();
+        }
+    }
+    output
+}
+
+/// Hashes `inputs` and groups those within `settings.distance` of each
+/// other, transitively. Groups of one are left out; each group and the
+/// group list are in path order. Unreadable files are warned about and
+/// skipped.
+pub fn find_groups(converter: &ImageConverter, inputs: &[PathBuf], settings: Settings) -> Vec<Vec<PathBuf>> {
+    let mut inputs = inputs.to_vec();
+    inputs.sort();
+    let hashes: Vec<Option<u64>> = inputs
+        .par_iter()
+        .map(|input| {
+            let image = converter
+                .read_input(input)
+                .and_then(|data| thumbnail::decode(converter, input, &data, 64));
+            match image {
+                Ok(image) => Some(hash(&image, settings.hash)),
+                Err(e) => {
+                    eprintln!("Warning: {}: {}; not checked for duplicates", input.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    // Union-find over every close pair.
+    let mut parent: Vec<usize> = (0..inputs.len()).collect();
+    fn root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+    for (i, a) in hashes.iter().enumerate() {
+        let Some(a) = a else { continue };
+        for (j, b) in hashes.iter().enumerate().skip(i + 1) {
+            if b.is_some_and(|b| (a ^ b).count_ones() <= settings.distance) {
+                let (root_i, root_j) = (root(&mut parent, i), root(&mut parent, j));
+                parent[root_j.max(root_i)] = root_i.min(root_j);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<PathBuf>> = vec![Vec::new(); inputs.len()];
+    for (index, input) in inputs.into_iter().enumerate() {
+        let group = root(&mut parent, index);
+        groups[group].push(input);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Every member of `groups` but the first, which is the one kept.
+pub fn duplicates(groups: &[Vec<PathBuf>]) -> HashSet<&Path> {
+    groups.iter().flat_map(|group| group[1..].iter().map(PathBuf::as_path)).collect()
+}
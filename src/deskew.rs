@@ -0,0 +1,148 @@
+use image::{ColorType, DynamicImage, ImageBuffer, Rgba};
+use rayon::prelude::*;
+
+use crate::transform::convert_to;
+
+/// Angle detection runs on a copy at most this many pixels across.
+const ANALYSIS_SIZE: u32 = 1000;
+/// Largest skew looked for, either way, and the search step, in degrees.
+const MAX_ANGLE: f32 = 20.0;
+const ANGLE_STEP: f32 = 0.1;
+/// Skews smaller than this aren't worth resampling the image for.
+const MIN_ANGLE: f32 = 0.05;
+
+/// Straightens scans: finds the dominant near-horizontal line angle (text
+/// baselines, table rules, page edges) with a Hough transform over the
+/// edge map, then rotates it level, filling the revealed corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deskew {
+    pub color: Rgba<u8>,
+}
+
+impl Deskew {
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match detect_angle(&image) {
+            Some(angle) if angle.abs() >= MIN_ANGLE => rotate(image, angle, self.color),
+            _ => image,
+        }
+    }
+}
+
+/// The skew of `image` in degrees, positive when lines fall to the right,
+/// or `None` when it has too few edges to tell.
+pub fn detect_angle(image: &DynamicImage) -> Option<f32> {
+    let gray = image.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).into_luma8();
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    if width < 3 || height < 3 {
+        return None;
+    }
+    let at = |x: usize, y: usize| f32::from(gray.as_raw()[y * width + x]);
+
+    // Sobel gradients; only edges across horizontal-ish lines vote.
+    let mut edges = Vec::new();
+    let mut magnitudes = Vec::new();
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            let magnitude = gx.hypot(gy);
+            if gy.abs() > gx.abs() {
+                edges.push((x as f32, y as f32, magnitude));
+            }
+            magnitudes.push(magnitude);
+        }
+    }
+    // The strongest tenth of all edges, ignoring faint texture.
+    magnitudes.sort_unstable_by(|a, b| b.total_cmp(a));
+    let threshold = magnitudes[magnitudes.len() / 10].max(32.0);
+    edges.retain(|&(_, _, magnitude)| magnitude >= threshold);
+    if edges.len() < 50 {
+        return None;
+    }
+
+    // For each candidate angle, project the edges onto the line normal; at
+    // the true skew they pile up in few bins, maximizing the sum of squares.
+    let diagonal = (width as f32).hypot(height as f32);
+    let steps = (MAX_ANGLE / ANGLE_STEP).round() as i32;
+    let score = |step: i32| {
+        let angle = (step as f32 * ANGLE_STEP).to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let mut bins = vec![0u32; 2 * diagonal.ceil() as usize + 1];
+        for &(x, y, _) in &edges {
+            let rho = y * cos - x * sin + diagonal;
+            bins[rho.round() as usize] += 1;
+        }
+        bins.iter().map(|&count| u64::from(count) * u64::from(count)).sum::<u64>()
+    };
+    let (best, _) = (-steps..=steps)
+        .into_par_iter()
+        .map(|step| (step, score(step)))
+        // Ties go to the smallest correction.
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.abs().cmp(&a.0.abs())))?;
+    Some(best as f32 * ANGLE_STEP)
+}
+
+/// Rotates `image` about its centre so lines at `angle` degrees become
+/// level, keeping its size; uncovered areas get `fill`.
+pub fn rotate(image: DynamicImage, angle: f32, fill: Rgba<u8>) -> DynamicImage {
+    // A translucent fill needs somewhere to keep its alpha.
+    let color = match image.color() {
+        ColorType::L8 if fill[3] < 255 => ColorType::La8,
+        ColorType::L16 if fill[3] < 255 => ColorType::La16,
+        ColorType::Rgb8 if fill[3] < 255 => ColorType::Rgba8,
+        ColorType::Rgb16 if fill[3] < 255 => ColorType::Rgba16,
+        ColorType::Rgb32F if fill[3] < 255 => ColorType::Rgba32F,
+        color => color,
+    };
+    let source = image.into_rgba32f();
+    let (width, height) = source.dimensions();
+    let fill = fill.0.map(|channel| f32::from(channel) / 255.0);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    // Bilinear sampling in premultiplied alpha, with the fill outside.
+    let sample = |x: i64, y: i64| -> [f32; 4] {
+        let pixel = if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+            fill
+        } else {
+            source.get_pixel(x as u32, y as u32).0
+        };
+        [pixel[0] * pixel[3], pixel[1] * pixel[3], pixel[2] * pixel[3], pixel[3]]
+    };
+    let mut output = vec![0.0f32; width as usize * height as usize * 4];
+    output.par_chunks_mut(width as usize * 4).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.chunks_mut(4).enumerate() {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let (sx, sy) = (cx + cos * dx - sin * dy - 0.5, cy + sin * dx + cos * dy - 0.5);
+            let (x0, y0) = (sx.floor(), sy.floor());
+            let (fx, fy) = (sx - x0, sy - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+            let corners = [
+                (sample(x0, y0), (1.0 - fx) * (1.0 - fy)),
+                (sample(x0 + 1, y0), fx * (1.0 - fy)),
+                (sample(x0, y0 + 1), (1.0 - fx) * fy),
+                (sample(x0 + 1, y0 + 1), fx * fy),
+            ];
+            let mut blended = [0.0f32; 4];
+            for (value, weight) in corners {
+                for c in 0..4 {
+                    blended[c] += value[c] * weight;
+                }
+            }
+            let alpha = blended[3];
+            for c in 0..3 {
+                pixel[c] = if alpha > 0.0 { (blended[c] / alpha).clamp(0.0, 1.0) } else { 0.0 };
+            }
+            pixel[3] = alpha.clamp(0.0, 1.0);
+        }
+    });
+
+    let rotated = ImageBuffer::from_raw(width, height, output).expect("buffer sized to the image");
+    convert_to(DynamicImage::ImageRgba32F(rotated), color)
+}
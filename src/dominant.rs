@@ -0,0 +1,103 @@
+use image::DynamicImage;
+
+use crate::quantize;
+
+/// Colors are extracted from a copy at most this large.
+const ANALYSIS_SIZE: u32 = 64;
+/// Lloyd iterations after the median-cut seeding; it settles well before.
+const ITERATIONS: usize = 10;
+
+/// A color and the fraction of visible pixels nearest to it.
+#[derive(Debug, Clone, Copy)]
+pub struct Swatch {
+    pub color: [u8; 3],
+    pub share: f32,
+}
+
+pub fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn visible_pixels(image: &DynamicImage) -> Vec<[u8; 4]> {
+    let pixels = image.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).into_rgba8();
+    pixels.pixels().map(|pixel| pixel.0).filter(|pixel| pixel[3] >= 128).collect()
+}
+
+/// The mean color of the visible pixels, or `None` when all are
+/// transparent.
+pub fn average(image: &DynamicImage) -> Option<[u8; 3]> {
+    let pixels = visible_pixels(image);
+    if pixels.is_empty() {
+        return None;
+    }
+    let mut sums = [0u64; 3];
+    for pixel in &pixels {
+        for channel in 0..3 {
+            sums[channel] += u64::from(pixel[channel]);
+        }
+    }
+    let count = pixels.len() as u64;
+    Some(sums.map(|sum| ((sum + count / 2) / count) as u8))
+}
+
+/// Clusters the visible pixels into at most `count` colors with k-means,
+/// seeded by median cut so the result is deterministic. Swatches come most
+/// common first.
+pub fn dominant(image: &DynamicImage, count: usize) -> Vec<Swatch> {
+    let pixels = visible_pixels(image);
+    if pixels.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let opaque: Vec<[u8; 4]> = pixels.iter().map(|&[r, g, b, _]| [r, g, b, 255]).collect();
+    let mut centers: Vec<[f32; 3]> = quantize::median_cut(&opaque, count)
+        .into_iter()
+        .map(|[r, g, b, _]| [r, g, b].map(f32::from))
+        .collect();
+    let points: Vec<[f32; 3]> = opaque.iter().map(|&[r, g, b, _]| [r, g, b].map(f32::from)).collect();
+
+    let nearest = |centers: &[[f32; 3]], point: &[f32; 3]| {
+        centers
+            .iter()
+            .map(|center| (0..3).map(|c| (center[c] - point[c]).powi(2)).sum::<f32>())
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(index, _)| index)
+    };
+
+    let mut populations = vec![0usize; centers.len()];
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![[0.0f32; 3]; centers.len()];
+        populations = vec![0; centers.len()];
+        for point in &points {
+            let index = nearest(&centers, point);
+            populations[index] += 1;
+            for c in 0..3 {
+                sums[index][c] += point[c];
+            }
+        }
+        let mut moved = false;
+        for (index, center) in centers.iter_mut().enumerate() {
+            if populations[index] == 0 {
+                continue;
+            }
+            let mean = sums[index].map(|sum| sum / populations[index] as f32);
+            moved |= mean != *center;
+            *center = mean;
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    let mut swatches: Vec<Swatch> = centers
+        .iter()
+        .zip(&populations)
+        .filter(|(_, &population)| population > 0)
+        .map(|(center, &population)| Swatch {
+            color: center.map(|value| value.round().clamp(0.0, 255.0) as u8),
+            share: population as f32 / points.len() as f32,
+        })
+        .collect();
+    swatches.sort_by(|a, b| b.share.total_cmp(&a.share));
+    swatches
+}
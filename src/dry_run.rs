@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::{checkpoint, is_stdio, overwrite, report, walk, ImageConverter, SupportedFormat};
+
+/// One input and every file converting it would write.
+#[derive(Debug, Clone)]
+struct Planned {
+    input: PathBuf,
+    outputs: Vec<PathBuf>,
+    /// Where `--in-place` would move the input once converted, or
+    /// `Some(None)` when it would delete it.
+    original: Option<Option<PathBuf>>,
+}
+
+/// What a run would do, worked out from paths alone: nothing is decoded,
+/// created or written.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    planned: Vec<Planned>,
+    skipped: Vec<(PathBuf, &'static str)>,
+    /// Set when `--skip-duplicates` would drop more inputs, which takes
+    /// decoding to find out.
+    unchecked_duplicates: bool,
+    overwrite: overwrite::Policy,
+}
+
+impl Plan {
+    /// Plans a single-file conversion from `input` to `output_path`.
+    pub fn single(converter: &ImageConverter, input: &Path, output_path: &Path, target_format: SupportedFormat) -> Self {
+        let outputs = if is_stdio(output_path) {
+            vec![output_path.to_path_buf()]
+        } else {
+            converter.planned_outputs(output_path, target_format)
+        };
+        Self {
+            planned: vec![Planned {
+                input: input.to_path_buf(),
+                outputs,
+                original: None,
+            }],
+            overwrite: converter.overwrite,
+            ..Self::default()
+        }
+    }
+
+    /// Plans a `--batch` run over `input_dir`, keeping track of the files
+    /// that wouldn't be picked up and why.
+    pub fn directory(
+        converter: &ImageConverter,
+        input_dir: &Path,
+        output_dir: &Path,
+        target_format: SupportedFormat,
+    ) -> std::io::Result<Self> {
+        let mut plan = Self {
+            unchecked_duplicates: converter.skip_duplicates.is_some(),
+            // `--in-place` never replaces a file that isn't the input.
+            overwrite: match converter.overwrite {
+                overwrite::Policy::Overwrite if converter.in_place.is_some() => overwrite::Policy::Fail,
+                policy => policy,
+            },
+            ..Self::default()
+        };
+        let mut files = walk::files(input_dir, converter.recursive, converter.walk, &mut plan.skipped)?;
+        files.sort();
+        let mut selected = Vec::new();
+        for path in files {
+            if !ImageConverter::is_supported(&path) {
+                plan.skipped.push((path, "unsupported format"));
+            } else if let Some(reason) = converter.in_place_skip(&path, target_format) {
+                plan.skipped.push((path, reason));
+            } else if !converter.is_selected(path.strip_prefix(input_dir).unwrap_or(&path)) {
+                plan.skipped.push((path, "excluded by --include/--exclude"));
+            } else if let Some(reason) = converter.input_filter.reason(&path) {
+                plan.skipped.push((path, reason));
+            } else {
+                selected.push(path);
+            }
+        }
+        let outputs = planned_paths(converter, &selected, |path, number| {
+            converter.mirrored_output_path(input_dir, output_dir, path, number, target_format)
+        });
+        let completed = resumed(converter, output_dir);
+        for path in selected {
+            if completed.contains(&checkpoint::key(&path)) {
+                plan.skipped.push((path, checkpoint::DONE));
+                continue;
+            }
+            let output_path = &outputs[&path];
+            plan.add(converter, path.clone(), output_path, target_format);
+        }
+        Ok(plan)
+    }
+
+    /// Plans a `--batch` or `--files-from` run over the `listed` paths and URLs.
+    pub fn list(
+        converter: &ImageConverter,
+        listed: Vec<PathBuf>,
+        output_dir: &Path,
+        target_format: SupportedFormat,
+    ) -> Self {
+        let mut plan = Self {
+            unchecked_duplicates: converter.skip_duplicates.is_some(),
+            overwrite: converter.overwrite,
+            ..Self::default()
+        };
+        let mut kept = Vec::new();
+        for input in listed {
+            match converter.input_filter.reason(&input) {
+                Some(reason) => plan.skipped.push((input, reason)),
+                None => kept.push(input),
+            }
+        }
+        let listed = kept;
+        let outputs = planned_paths(converter, &listed, |input, number| {
+            converter.listed_output_path(input, output_dir, number, target_format)
+        });
+        let completed = resumed(converter, output_dir);
+        for input in listed {
+            if completed.contains(&checkpoint::key(&input)) {
+                plan.skipped.push((input, checkpoint::DONE));
+                continue;
+            }
+            let output_path = &outputs[&input];
+            plan.add(converter, input.clone(), output_path, target_format);
+        }
+        plan
+    }
+
+    /// Plans converting `input` to `output_path`, unless `--skip-existing`
+    /// or `--newer-only` would leave it alone.
+    fn add(&mut self, converter: &ImageConverter, input: PathBuf, output_path: &Path, target_format: SupportedFormat) {
+        if converter.is_up_to_date(&input, output_path, target_format) {
+            self.skipped.push((input, "up to date"));
+        } else {
+            let outputs = converter.planned_outputs(output_path, target_format);
+            let original = converter.in_place.as_ref().and_then(|in_place| {
+                if outputs.contains(&input) {
+                    return None;
+                }
+                Some(in_place.backup_dir.clone())
+            });
+            self.planned.push(Planned { input, outputs, original });
+        }
+    }
+
+    /// Prints each planned conversion with the outputs it would write,
+    /// flagging outputs several inputs would write (only the last one
+    /// finished would survive) and existing files that would be replaced,
+    /// then the skipped files and a closing count.
+    pub fn print(&self, json: bool) {
+        let mut claims: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        for planned in &self.planned {
+            for output in &planned.outputs {
+                claims.entry(output.as_path()).or_default().push(&planned.input);
+            }
+        }
+
+        let collisions = claims.values().filter(|inputs| inputs.len() > 1).count();
+        let overwrites = claims.keys().filter(|output| !is_stdio(output) && output.exists()).count();
+
+        for planned in &self.planned {
+            let mut outputs = Vec::new();
+            for output in &planned.outputs {
+                let others: Vec<&Path> = claims[output.as_path()]
+                    .iter()
+                    .copied()
+                    .filter(|&input| input != planned.input)
+                    .collect();
+                let exists = !is_stdio(output) && output.exists();
+                outputs.push((output, others, exists));
+            }
+
+            if json {
+                report::emit(&json!({
+                    "event": "planned",
+                    "input": planned.input.display().to_string(),
+                    "outputs": outputs
+                        .iter()
+                        .map(|(output, others, exists)| json!({
+                            "path": output.display().to_string(),
+                            "exists": exists,
+                            "collides_with": others.iter().map(|input| input.display().to_string()).collect::<Vec<_>>(),
+                        }))
+                        .collect::<Vec<_>>(),
+                    "original": match &planned.original {
+                        Some(Some(dir)) => json!({ "moved_to": dir.display().to_string() }),
+                        Some(None) => json!("removed"),
+                        None => json!(null),
+                    },
+                }));
+                continue;
+            }
+            match &planned.original {
+                Some(Some(dir)) => println!("{} (original moved to {})", planned.input.display(), dir.display()),
+                Some(None) => println!("{} (original removed)", planned.input.display()),
+                None => println!("{}", planned.input.display()),
+            }
+            for (output, others, exists) in &outputs {
+                let mut notes = Vec::new();
+                if *exists {
+                    notes.push(self.overwrite.describe_existing().to_string());
+                }
+                if !others.is_empty() {
+                    let others: Vec<String> = others.iter().map(|input| input.display().to_string()).collect();
+                    notes.push(format!("also written for {}", others.join(", ")));
+                }
+                if notes.is_empty() {
+                    println!("  -> {}", output.display());
+                } else {
+                    println!("  -> {} ({})", output.display(), notes.join("; "));
+                }
+            }
+        }
+
+        for (path, reason) in &self.skipped {
+            if json {
+                report::emit(&report::skipped(path, reason));
+            } else {
+                println!("Skipping {}: {}", path.display(), reason);
+            }
+        }
+
+        if json {
+            report::emit(&json!({
+                "event": "dry_run",
+                "planned": self.planned.len(),
+                "skipped": self.skipped.len(),
+                "collisions": collisions,
+                "overwrites": overwrites,
+                "duplicates_checked": !self.unchecked_duplicates,
+            }));
+            return;
+        }
+        if self.unchecked_duplicates {
+            println!("Note: --skip-duplicates needs to decode the inputs, so near-duplicates are still listed above");
+        }
+        println!(
+            "\nDry run: {} to convert, {} skipped, {} outputs written more than once, {} existing outputs. Nothing was written.",
+            self.planned.len(),
+            self.skipped.len(),
+            collisions,
+            overwrites
+        );
+    }
+}
+
+/// The inputs `--resume` would skip, from the checkpoint of an earlier run.
+fn resumed(converter: &ImageConverter, output_dir: &Path) -> HashSet<PathBuf> {
+    if !converter.resume {
+        return HashSet::new();
+    }
+    checkpoint::completed(&converter.checkpoint_path(output_dir)).unwrap_or_default()
+}
+
+/// The output path of each of `inputs`, numbered from 1 as a batch run
+/// does. Collisions `--on-collision` would rename are renamed; ones it
+/// would stop the run for are left for [`Plan::print`] to point out.
+fn planned_paths<F>(converter: &ImageConverter, inputs: &[PathBuf], output_path: F) -> HashMap<PathBuf, PathBuf>
+where
+    F: Fn(&Path, usize) -> PathBuf,
+{
+    let numbers: HashMap<&Path, usize> = inputs.iter().map(PathBuf::as_path).zip(1..).collect();
+    let output_path = |input: &Path| output_path(input, numbers[input]);
+    converter
+        .output_paths(inputs, output_path)
+        .unwrap_or_else(|_| inputs.iter().map(|input| (input.clone(), output_path(input))).collect())
+}
@@ -0,0 +1,167 @@
+use image::DynamicImage;
+use rayon::prelude::*;
+
+use crate::adjust::{from_oklab, to_oklab};
+use crate::hdr;
+use crate::transform::convert_to;
+
+const BINS: usize = 256;
+
+/// Histogram equalization of lightness (OKLab L for color images, so hues
+/// survive), for low-contrast scans, X-rays and micrographs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Equalize {
+    /// One mapping for the whole image.
+    Global,
+    /// Contrast-limited adaptive equalization: a mapping per tile, with
+    /// each histogram bin capped at `clip` times the average so noise in
+    /// flat areas isn't amplified, blended bilinearly between tiles.
+    Clahe { tiles: (u32, u32), clip: f32 },
+}
+
+impl Equalize {
+    pub const DEFAULT_CLIP: f32 = 2.0;
+
+    /// Parses `--clahe` settings: `TILES[,CLIP]`, where tiles is `N` or
+    /// `NxM`, e.g. `8,2` or `16x8,3.5`.
+    pub fn parse_clahe(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid CLAHE setting: {} (expected tiles[,clip], e.g. 8,2 or 16x8,3)", spec);
+        let (tiles, clip) = match spec.split_once(',') {
+            Some((tiles, clip)) => (tiles, Some(clip)),
+            None => (spec, None),
+        };
+        let count = |value: &str| value.trim().parse().ok().filter(|count| (1..=64).contains(count)).ok_or_else(invalid);
+        let tiles = match tiles.split_once(['x', 'X']) {
+            Some((across, down)) => (count(across)?, count(down)?),
+            None => {
+                let tiles = count(tiles)?;
+                (tiles, tiles)
+            }
+        };
+        let clip = match clip {
+            Some(clip) => clip.trim().parse().ok().filter(|&clip: &f32| clip >= 1.0).ok_or_else(invalid)?,
+            None => Self::DEFAULT_CLIP,
+        };
+        Ok(Equalize::Clahe { tiles, clip })
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        let color = image.color();
+        let mut pixels = image.into_rgba32f();
+        let (width, height) = (pixels.width() as usize, pixels.height() as usize);
+        if width == 0 || height == 0 {
+            return convert_to(DynamicImage::ImageRgba32F(pixels), color);
+        }
+
+        let lab: Vec<[f32; 3]> = pixels
+            .par_chunks(4)
+            .map(|pixel| {
+                if color.has_color() {
+                    to_oklab([pixel[0], pixel[1], pixel[2]].map(|value| hdr::decode_srgb(value.clamp(0.0, 1.0))))
+                } else {
+                    [pixel[0].clamp(0.0, 1.0), 0.0, 0.0]
+                }
+            })
+            .collect();
+        let lightness: Vec<f32> = lab.iter().map(|&[l, _, _]| l.clamp(0.0, 1.0)).collect();
+
+        let mapped = match self {
+            Equalize::Global => {
+                let table = mapping(&histogram(&lightness, width, 0..width, 0..height), None);
+                lightness.par_iter().map(|&l| lookup(&table, l)).collect()
+            }
+            Equalize::Clahe { tiles, clip } => clahe(&lightness, width, height, tiles, clip),
+        };
+
+        pixels.par_chunks_mut(4).enumerate().for_each(|(index, pixel)| {
+            let rgb = if color.has_color() {
+                let [_, a, b] = lab[index];
+                from_oklab([mapped[index], a, b]).map(hdr::encode_srgb)
+            } else {
+                [mapped[index]; 3]
+            };
+            for c in 0..3 {
+                pixel[c] = rgb[c].clamp(0.0, 1.0);
+            }
+        });
+        convert_to(DynamicImage::ImageRgba32F(pixels), color)
+    }
+}
+
+fn histogram(lightness: &[f32], width: usize, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) -> Vec<f32> {
+    let mut counts = vec![0.0; BINS];
+    for y in ys {
+        for &l in &lightness[y * width + xs.start..y * width + xs.end] {
+            counts[(l * (BINS - 1) as f32).round() as usize] += 1.0;
+        }
+    }
+    counts
+}
+
+/// The cumulative distribution of `counts` as a 0-1 mapping per bin, with
+/// bins above `limit` clipped and the excess spread over all bins.
+fn mapping(counts: &[f32], limit: Option<f32>) -> Vec<f32> {
+    let mut counts = counts.to_vec();
+    if let Some(limit) = limit {
+        let excess: f32 = counts.iter().map(|&count| (count - limit).max(0.0)).sum();
+        for count in &mut counts {
+            *count = count.min(limit) + excess / BINS as f32;
+        }
+    }
+    let total: f32 = counts.iter().sum();
+    let mut cumulative = 0.0;
+    counts
+        .iter()
+        .map(|&count| {
+            // Midpoint of the bin's span, so a flat image maps to mid-gray
+            // rather than white.
+            let value = (cumulative + count / 2.0) / total.max(1.0);
+            cumulative += count;
+            value
+        })
+        .collect()
+}
+
+fn lookup(table: &[f32], lightness: f32) -> f32 {
+    let position = lightness * (BINS - 1) as f32;
+    let index = (position as usize).min(BINS - 2);
+    let fraction = position - index as f32;
+    table[index] * (1.0 - fraction) + table[index + 1] * fraction
+}
+
+fn clahe(lightness: &[f32], width: usize, height: usize, (across, down): (u32, u32), clip: f32) -> Vec<f32> {
+    let (across, down) = ((across as usize).min(width), (down as usize).min(height));
+    let bounds = |tile: usize, tiles: usize, size: usize| tile * size / tiles..(tile + 1) * size / tiles;
+
+    let tables: Vec<Vec<f32>> = (0..across * down)
+        .into_par_iter()
+        .map(|tile| {
+            let (xs, ys) = (bounds(tile % across, across, width), bounds(tile / across, down, height));
+            let area = (xs.len() * ys.len()) as f32;
+            let limit = (clip * area / BINS as f32).max(1.0);
+            mapping(&histogram(lightness, width, xs, ys), Some(limit))
+        })
+        .collect();
+
+    // Each pixel blends the mappings of the four tiles whose centres
+    // surround it.
+    let neighbours = |position: usize, size: usize, tiles: usize| {
+        let centre = (position as f32 + 0.5) * tiles as f32 / size as f32 - 0.5;
+        let low = centre.floor().clamp(0.0, (tiles - 1) as f32) as usize;
+        let high = (low + 1).min(tiles - 1);
+        (low, high, (centre - low as f32).clamp(0.0, 1.0))
+    };
+    let mut mapped = vec![0.0; lightness.len()];
+    mapped.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let (top, bottom, fy) = neighbours(y, height, down);
+        for (x, value) in row.iter_mut().enumerate() {
+            let (left, right, fx) = neighbours(x, width, across);
+            let l = lightness[y * width + x];
+            let at = |tx: usize, ty: usize| lookup(&tables[ty * across + tx], l);
+            let upper = at(left, top) * (1.0 - fx) + at(right, top) * fx;
+            let lower = at(left, bottom) * (1.0 - fx) + at(right, bottom) * fx;
+            *value = upper * (1.0 - fy) + lower * fy;
+        }
+    });
+    mapped
+}
@@ -0,0 +1,69 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::imageops::{self, FilterType};
+use image::{ColorType, ImageFormat, RgbaImage};
+
+use crate::{atomic, ImageConverter};
+
+/// Sizes bundled into `favicon.ico`.
+const ICO_SIZES: [u32; 4] = [16, 32, 48, 64];
+
+/// Standalone PNG icons: Apple touch icon and the Android/PWA manifest sizes.
+const PNG_ICONS: [(&str, u32); 3] = [
+    ("apple-touch-icon.png", 180),
+    ("android-chrome-192x192.png", 192),
+    ("android-chrome-512x512.png", 512),
+];
+
+/// Writes a multi-resolution `favicon.ico` and the standard PNG icons for
+/// `input` into `output_dir`, returning the files created. Non-square
+/// sources are centered on a transparent square canvas.
+pub fn generate(
+    converter: &ImageConverter,
+    input: &Path,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(output_dir)?;
+
+    let data = converter.read_input(input)?;
+    let source = square(converter.process(converter.decode_image(input, &data)?).to_rgba8());
+
+    let icons: Vec<RgbaImage> = ICO_SIZES
+        .iter()
+        .map(|&size| imageops::resize(&source, size, size, FilterType::Lanczos3))
+        .collect();
+    let frames = icons
+        .iter()
+        .map(|icon| IcoFrame::as_png(icon.as_raw(), icon.width(), icon.height(), ColorType::Rgba8))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ico_path = output_dir.join("favicon.ico");
+    let mut ico = Vec::new();
+    IcoEncoder::new(&mut ico).encode_images(&frames)?;
+    atomic::write(&ico_path, &ico)?;
+
+    let mut written = vec![ico_path];
+    for (name, size) in PNG_ICONS {
+        let path = output_dir.join(name);
+        let mut png = Cursor::new(Vec::new());
+        imageops::resize(&source, size, size, FilterType::Lanczos3).write_to(&mut png, ImageFormat::Png)?;
+        atomic::write(&path, png.get_ref())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Pads `image` with transparency to a centered square.
+fn square(image: RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width == height {
+        return image;
+    }
+
+    let side = width.max(height);
+    let mut canvas = RgbaImage::new(side, side);
+    imageops::overlay(&mut canvas, &image, i64::from((side - width) / 2), i64::from((side - height) / 2));
+    canvas
+}
@@ -0,0 +1,192 @@
+use image::DynamicImage;
+use rayon::prelude::*;
+
+use crate::transform::convert_to;
+
+/// Long edge, in pixels, up to which `--sharpen` radii are taken
+/// literally; larger images, whose detail spans more pixels, scale them up
+/// proportionally.
+const REFERENCE_SIZE: f32 = 1000.0;
+/// Below this sigma the blur kernel is a single tap and sharpening does
+/// nothing.
+const MIN_SIGMA: f32 = 0.3;
+
+/// Unsharp mask: adds back the difference between the image and a blurred
+/// copy of it, restoring the crispness lost when downscaling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sharpen {
+    /// Strength; 1.0 adds the full difference.
+    pub amount: f32,
+    /// Blur sigma in pixels up to a 1000-pixel long edge, scaled up beyond
+    /// that, so the same setting suits thumbnails and full-size output
+    /// alike.
+    pub radius: f32,
+    /// Differences at or below this (0-255) are left alone, which keeps
+    /// smooth areas and noise from being sharpened.
+    pub threshold: u8,
+}
+
+impl Sharpen {
+    pub const DEFAULT_RADIUS: f32 = 1.0;
+
+    /// Parses `amount[,radius[,threshold]]`, e.g. `0.8,1.2,3`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid sharpen setting: {} (expected amount[,radius[,threshold]], e.g. 0.8,1,2)", spec);
+        let mut parts = spec.split(',').map(str::trim);
+        let amount = parts
+            .next()
+            .and_then(|amount| amount.parse().ok())
+            .filter(|amount: &f32| (0.0..=10.0).contains(amount))
+            .ok_or_else(invalid)?;
+        let radius = match parts.next() {
+            Some(radius) => radius.parse().ok().filter(|&radius: &f32| radius > 0.0 && radius <= 100.0).ok_or_else(invalid)?,
+            None => Self::DEFAULT_RADIUS,
+        };
+        let threshold = match parts.next() {
+            Some(threshold) => threshold.parse().map_err(|_| invalid())?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self { amount, radius, threshold })
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if self.amount == 0.0 {
+            return image;
+        }
+        let long_edge = image.width().max(image.height()) as f32;
+        let sigma = (self.radius * (long_edge / REFERENCE_SIZE).max(1.0)).max(MIN_SIGMA);
+
+        let color = image.color();
+        let blurred = image.blur(sigma).into_rgba32f();
+        let mut pixels = image.into_rgba32f();
+        let threshold = f32::from(self.threshold) / 255.0;
+        pixels.par_chunks_mut(4).zip(blurred.par_chunks(4)).for_each(|(pixel, blurred)| {
+            for channel in 0..3 {
+                let difference = pixel[channel] - blurred[channel];
+                if difference.abs() > threshold {
+                    pixel[channel] = (pixel[channel] + self.amount * difference).clamp(0.0, 1.0);
+                }
+            }
+        });
+        convert_to(DynamicImage::ImageRgba32F(pixels), color)
+    }
+}
+
+/// Gaussian blur, for soft background and hero variants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blur {
+    /// Standard deviation in output pixels.
+    pub sigma: f32,
+}
+
+impl Blur {
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if !image.color().has_alpha() {
+            return image.blur(self.sigma);
+        }
+
+        // Blurring straight alpha would bleed the color of invisible pixels
+        // into the edges, so blur premultiplied values instead.
+        let color = image.color();
+        let mut pixels = image.into_rgba32f();
+        pixels.par_chunks_mut(4).for_each(|pixel| {
+            for channel in 0..3 {
+                pixel[channel] *= pixel[3];
+            }
+        });
+        let mut blurred = image::imageops::blur(&pixels, self.sigma);
+        blurred.par_chunks_mut(4).for_each(|pixel| {
+            if pixel[3] > 0.0 {
+                for channel in 0..3 {
+                    pixel[channel] = (pixel[channel] / pixel[3]).clamp(0.0, 1.0);
+                }
+            }
+        });
+        convert_to(DynamicImage::ImageRgba32F(blurred), color)
+    }
+}
+
+/// BT.601 luma weights; chroma is kept as plain blue and red differences.
+const KR: f32 = 0.299;
+const KB: f32 = 0.114;
+
+/// Edge-preserving noise reduction: a bilateral filter on luma and a wider,
+/// stronger one on chroma, where high-ISO noise is most visible and costs
+/// encoders the most.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoise {
+    /// 0-100.
+    pub strength: f32,
+}
+
+impl Denoise {
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        if self.strength <= 0.0 {
+            return image;
+        }
+        let strength = self.strength / 100.0;
+        let color = image.color();
+        let mut pixels = image.into_rgba32f();
+        let (width, height) = (pixels.width() as usize, pixels.height() as usize);
+
+        let luma: Vec<f32> = pixels.chunks(4).map(|p| KR * p[0] + (1.0 - KR - KB) * p[1] + KB * p[2]).collect();
+        let luma = bilateral(&luma, width, height, 2, 1.0, 0.01 + 0.06 * strength);
+
+        if color.has_color() {
+            let blue: Vec<f32> = pixels.chunks(4).zip(&luma).map(|(p, y)| p[2] - y).collect();
+            let red: Vec<f32> = pixels.chunks(4).zip(&luma).map(|(p, y)| p[0] - y).collect();
+            let blue = bilateral(&blue, width, height, 3, 2.0, 0.02 + 0.15 * strength);
+            let red = bilateral(&red, width, height, 3, 2.0, 0.02 + 0.15 * strength);
+            pixels.par_chunks_mut(4).enumerate().for_each(|(index, pixel)| {
+                let (y, b, r) = (luma[index], blue[index] + luma[index], red[index] + luma[index]);
+                let g = (y - KR * r - KB * b) / (1.0 - KR - KB);
+                pixel[0] = r.clamp(0.0, 1.0);
+                pixel[1] = g.clamp(0.0, 1.0);
+                pixel[2] = b.clamp(0.0, 1.0);
+            });
+        } else {
+            pixels.par_chunks_mut(4).zip(luma).for_each(|(pixel, y)| {
+                let y = y.clamp(0.0, 1.0);
+                pixel[0] = y;
+                pixel[1] = y;
+                pixel[2] = y;
+            });
+        }
+        convert_to(DynamicImage::ImageRgba32F(pixels), color)
+    }
+}
+
+/// Averages each sample with its neighbours within `radius`, weighted by
+/// both distance and similarity of value so edges stay sharp.
+fn bilateral(plane: &[f32], width: usize, height: usize, radius: usize, spatial_sigma: f32, range_sigma: f32) -> Vec<f32> {
+    let size = 2 * radius + 1;
+    let spatial: Vec<f32> = (0..size * size)
+        .map(|index| {
+            let (dx, dy) = ((index % size) as f32 - radius as f32, (index / size) as f32 - radius as f32);
+            (-(dx * dx + dy * dy) / (2.0 * spatial_sigma * spatial_sigma)).exp()
+        })
+        .collect();
+    let range = -1.0 / (2.0 * range_sigma * range_sigma);
+
+    let mut output = vec![0.0; plane.len()];
+    output.par_chunks_mut(width.max(1)).enumerate().for_each(|(y, row)| {
+        for (x, value) in row.iter_mut().enumerate() {
+            let centre = plane[y * width + x];
+            let (mut sum, mut total) = (0.0, 0.0);
+            for ny in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                for nx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                    let sample = plane[ny * width + nx];
+                    let difference = sample - centre;
+                    let weight = spatial[(ny + radius - y) * size + nx + radius - x] * (difference * difference * range).exp();
+                    sum += sample * weight;
+                    total += weight;
+                }
+            }
+            *value = sum / total;
+        }
+    });
+    output
+}
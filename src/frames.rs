@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use image::{Delay, DynamicImage, Frame};
+
+use crate::animation::{self, Animation};
+use crate::{numbered_path, ImageConverter, SupportedFormat};
+
+/// Which frames `extract-frames` writes: every `every`th frame of the
+/// 1-based, inclusive `first..=last` range, counting from `first`.
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    pub every: usize,
+    pub first: usize,
+    pub last: usize,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self {
+            every: 1,
+            first: 1,
+            last: usize::MAX,
+        }
+    }
+}
+
+impl Selection {
+    /// Parses `A-B`, `A-` or `-B` (1-based, inclusive) into `first`/`last`.
+    pub fn parse_range(&mut self, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid frame range: {} (expected A-B, A- or -B)", value);
+        let (first, last) = value.split_once('-').ok_or_else(invalid)?;
+        let bound = |text: &str, default: usize| match text.trim() {
+            "" => Ok(default),
+            text => text.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(invalid),
+        };
+        self.first = bound(first, 1)?;
+        self.last = bound(last, usize::MAX)?;
+        if self.first > self.last {
+            return Err(invalid());
+        }
+        Ok(())
+    }
+
+    fn includes(&self, number: usize) -> bool {
+        (self.first..=self.last).contains(&number) && (number - self.first).is_multiple_of(self.every)
+    }
+}
+
+/// Splits an animated GIF, WebP or APNG into numbered stills in
+/// `output_dir` (`clip-01.png`, `clip-02.png`, ...), each run through the
+/// converter's pipeline. Files are numbered by their frame position in the
+/// source, so gaps show which frames were skipped.
+pub fn extract(
+    converter: &ImageConverter,
+    input: &Path,
+    output_dir: &Path,
+    target_format: SupportedFormat,
+    selection: Selection,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let data = converter.read_input(input)?;
+    if !animation::is_animated(&data) {
+        return Err(format!("{} is not an animated GIF, WebP or APNG", input.display()).into());
+    }
+    converter.check_animation_pixels(&data)?;
+    let frames = animation::decode(&data)?.frames;
+
+    std::fs::create_dir_all(output_dir)?;
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let template = output_dir.join(format!("{}.{}", stem, target_format.extension()));
+
+    let count = frames.len();
+    let mut written = Vec::new();
+    for (index, frame) in frames.into_iter().enumerate() {
+        let number = index + 1;
+        if !selection.includes(number) {
+            continue;
+        }
+        let image = converter.process(DynamicImage::ImageRgba8(frame.into_buffer()));
+        let output_path = numbered_path(&template, number, count);
+        converter.save_image(&image, &data, &output_path, target_format)?;
+        written.push(output_path);
+    }
+
+    if written.is_empty() {
+        return Err(format!("no frames of {} fall in the selected range", input.display()).into());
+    }
+    Ok(written)
+}
+
+/// Frame timing for `animate`.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    pub delay_ms: f32,
+    /// Total number of plays; 0 means forever.
+    pub plays: u16,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            delay_ms: 100.0,
+            plays: 0,
+        }
+    }
+}
+
+/// Builds an animated GIF, WebP or APNG from the stills matching `pattern`,
+/// either a glob (`frames/*.png`) or a printf-style sequence
+/// (`frame_%04d.png`). Frames are ordered by the numbers in their names and
+/// must all end up the same size after the converter's pipeline.
+pub fn assemble(
+    converter: &ImageConverter,
+    pattern: &str,
+    output_path: &Path,
+    target_format: SupportedFormat,
+    timing: Timing,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if !animation::supports(target_format) {
+        return Err(format!("{} cannot hold an animation (use gif, webp or png)", target_format.extension()).into());
+    }
+
+    let mut inputs: Vec<PathBuf> = glob::glob(&printf_to_glob(pattern))?
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect();
+    if inputs.is_empty() {
+        return Err(format!("no frames match {}", pattern).into());
+    }
+    inputs.sort_by_cached_key(|path| natural_key(&path.to_string_lossy()));
+
+    let delay = Delay::from_saturating_duration(Duration::from_secs_f32(timing.delay_ms / 1000.0));
+    let mut frames: Vec<Frame> = Vec::with_capacity(inputs.len());
+    for input in &inputs {
+        let data = converter.read_input(input)?;
+        let image = converter.process(converter.decode_image(input, &data)?).to_rgba8();
+        if let Some(first) = frames.first() {
+            if first.buffer().dimensions() != image.dimensions() {
+                return Err(format!(
+                    "{} is {}x{} but the first frame is {}x{}",
+                    input.display(),
+                    image.width(),
+                    image.height(),
+                    first.buffer().width(),
+                    first.buffer().height(),
+                )
+                .into());
+            }
+        }
+        frames.push(Frame::from_parts(image, 0, 0, delay));
+    }
+
+    let count = frames.len();
+    let animation = Animation {
+        frames,
+        plays: timing.plays,
+    };
+    ImageConverter::write_output(&animation::encode(converter, &animation, target_format)?, output_path)?;
+    Ok(count)
+}
+
+/// Turns printf-style `%d` / `%04d` placeholders into glob wildcards.
+fn printf_to_glob(pattern: &str) -> String {
+    let mut glob = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(at) = rest.find('%') {
+        glob.push_str(&rest[..at]);
+        let spec = &rest[at + 1..];
+        let digits = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+        if spec[digits..].starts_with('d') {
+            match spec[..digits].trim_start_matches('0').parse::<usize>() {
+                Ok(width) if spec.starts_with('0') => glob.push_str(&"[0-9]".repeat(width)),
+                _ => glob.push('*'),
+            }
+            rest = &spec[digits + 1..];
+        } else if let Some(after) = spec.strip_prefix('%') {
+            glob.push('%');
+            rest = after;
+        } else {
+            glob.push('%');
+            rest = spec;
+        }
+    }
+    glob.push_str(rest);
+    glob
+}
+
+/// Sort key comparing digit runs by value, so `frame2` sorts before `frame10`.
+fn natural_key(name: &str) -> Vec<(u64, String)> {
+    let mut key = Vec::new();
+    let mut chars = name.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let digit = c.is_ascii_digit();
+        let mut run = String::new();
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() == digit) {
+            run.push(c);
+            chars.next();
+        }
+        key.push(if digit { (run.parse().unwrap_or(u64::MAX), String::new()) } else { (0, run) });
+    }
+    key
+}
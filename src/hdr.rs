@@ -0,0 +1,119 @@
+use image::codecs::hdr::HdrDecoder;
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ColorType, DynamicImage, ImageBuffer, ImageError, ImageFormat, Rgba};
+
+/// Curve compressing scene-linear HDR values into the displayable 0-1 range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// Narkowicz's fit of the ACES filmic curve: contrasty, with soft
+    /// highlight roll-off.
+    Aces,
+    /// Reinhard on luminance: flatter, keeps hues in the highlights.
+    Reinhard,
+    /// Clips everything above 1.0.
+    Clamp,
+}
+
+impl Operator {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "aces" => Ok(Operator::Aces),
+            "reinhard" => Ok(Operator::Reinhard),
+            "clamp" | "none" => Ok(Operator::Clamp),
+            _ => Err(format!("Invalid tone mapping operator: {} (expected aces, reinhard or clamp)", value)),
+        }
+    }
+}
+
+/// How float (OpenEXR, Radiance HDR) input is brought into display range.
+#[derive(Debug, Clone, Copy)]
+pub struct ToneMap {
+    pub operator: Operator,
+    /// Exposure adjustment in stops, applied before the curve.
+    pub exposure: f32,
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        Self {
+            operator: Operator::Aces,
+            exposure: 0.0,
+        }
+    }
+}
+
+/// Decodes a Radiance HDR file to float pixels. The `image` crate's generic
+/// loader hands these out already squashed to 8 bits.
+pub fn decode_radiance(data: &[u8]) -> Result<DynamicImage, ImageError> {
+    let decoder = HdrDecoder::new(data)?;
+    let metadata = decoder.metadata();
+    let samples: Vec<f32> = decoder.read_image_hdr()?.into_iter().flat_map(|pixel| pixel.0).collect();
+    ImageBuffer::from_raw(metadata.width, metadata.height, samples)
+        .map(DynamicImage::ImageRgb32F)
+        .ok_or_else(|| {
+            ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::Hdr), "truncated pixel data"))
+        })
+}
+
+/// Whether `image` holds floating-point, potentially out-of-range samples.
+pub fn is_hdr(image: &DynamicImage) -> bool {
+    matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+/// Tone maps scene-linear float pixels to sRGB-encoded 16-bit ones, which
+/// every encoder accepts and 8-bit formats narrow on write.
+pub fn apply(image: DynamicImage, tone_map: ToneMap) -> DynamicImage {
+    let has_alpha = image.color().has_alpha();
+    let scale = tone_map.exposure.exp2();
+    let pixels = image.into_rgba32f();
+
+    let to_sample = |value: f32| (value.clamp(0.0, 1.0) * 65535.0).round() as u16;
+    let mapped = ImageBuffer::from_fn(pixels.width(), pixels.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *pixels.get_pixel(x, y);
+        let [r, g, b] = map(tone_map.operator, [r * scale, g * scale, b * scale]).map(encode_srgb);
+        Rgba([to_sample(r), to_sample(g), to_sample(b), to_sample(a)])
+    });
+
+    let image = DynamicImage::ImageRgba16(mapped);
+    if has_alpha {
+        image
+    } else {
+        DynamicImage::ImageRgb16(image.into_rgb16())
+    }
+}
+
+fn map(operator: Operator, rgb: [f32; 3]) -> [f32; 3] {
+    // NaNs and negatives carry no light.
+    let rgb = rgb.map(|value| if value.is_nan() { 0.0 } else { value.max(0.0) });
+    match operator {
+        Operator::Aces => rgb.map(|x| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)),
+        Operator::Reinhard => {
+            let luminance = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            if luminance <= 0.0 {
+                return [0.0; 3];
+            }
+            let ratio = (luminance / (1.0 + luminance)) / luminance;
+            rgb.map(|value| value * ratio)
+        }
+        Operator::Clamp => rgb,
+    }
+}
+
+/// The sRGB transfer function, from linear light to encoded values.
+pub fn encode_srgb(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse sRGB transfer function, from encoded values to linear light.
+pub fn decode_srgb(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
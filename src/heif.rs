@@ -0,0 +1,98 @@
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError};
+
+/// `ftyp` brands that mark a HEVC-coded HEIF image.
+const HEIC_BRANDS: [&[u8; 4]; 6] = [b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis"];
+
+fn hint() -> ImageFormatHint {
+    ImageFormatHint::Name("HEIC".to_string())
+}
+
+/// Whether `data` is a HEIC file, judged by the major or compatible brands
+/// of its leading `ftyp` box.
+pub fn is_heif(data: &[u8]) -> bool {
+    if data.len() < 16 || &data[4..8] != b"ftyp" {
+        return false;
+    }
+    let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let Some(ftyp) = data.get(8..size.max(16)) else {
+        return false;
+    };
+
+    // Major brand, minor version, then compatible brands.
+    std::iter::once(&ftyp[..4])
+        .chain(ftyp[8..].chunks_exact(4))
+        .any(|brand| HEIC_BRANDS.iter().any(|heic| brand == *heic))
+}
+
+/// Decodes the primary image of a HEIC file with libheif, which also applies
+/// the container's rotation, mirroring and crop. Deeper than 8-bit sources
+/// decode to a 16-bit image.
+#[cfg(feature = "heic")]
+pub fn decode(data: &[u8]) -> Result<DynamicImage, ImageError> {
+    use image::ImageBuffer;
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let error = |e: &dyn std::fmt::Display| ImageError::Decoding(DecodingError::new(hint(), e.to_string()));
+
+    let context = HeifContext::read_from_bytes(data).map_err(|e| error(&e))?;
+    let handle = context.primary_image_handle().map_err(|e| error(&e))?;
+    let alpha = handle.has_alpha_channel();
+    let bits = handle.luma_bits_per_pixel();
+    let chroma = match (bits > 8, alpha) {
+        (false, false) => RgbChroma::Rgb,
+        (false, true) => RgbChroma::Rgba,
+        (true, false) => RgbChroma::HdrRgbLe,
+        (true, true) => RgbChroma::HdrRgbaLe,
+    };
+
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(|e| error(&e))?;
+    let planes = decoded.planes();
+    let plane = planes.interleaved.ok_or_else(|| error(&"no interleaved plane"))?;
+    let (width, height) = (plane.width, plane.height);
+    let channels = if alpha { 4 } else { 3 };
+
+    let image = if bits > 8 {
+        // Little-endian samples in 0..2^bits, rescaled to the full 16-bit range.
+        let max = (1u32 << bits) - 1;
+        let row_len = width as usize * channels * 2;
+        let samples: Vec<u16> = plane
+            .data
+            .chunks(plane.stride)
+            .take(height as usize)
+            .flat_map(|row| row[..row_len].chunks_exact(2))
+            .map(|sample| (u32::from(u16::from_le_bytes([sample[0], sample[1]])) * 65535 / max) as u16)
+            .collect();
+        if alpha {
+            ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba16)
+        } else {
+            ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb16)
+        }
+    } else {
+        let row_len = width as usize * channels;
+        let samples: Vec<u8> = plane
+            .data
+            .chunks(plane.stride)
+            .take(height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+        if alpha {
+            ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba8)
+        } else {
+            ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb8)
+        }
+    };
+
+    image.ok_or_else(|| error(&"decoded plane is smaller than the image"))
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn decode(_data: &[u8]) -> Result<DynamicImage, ImageError> {
+    Err(ImageError::Decoding(DecodingError::new(
+        hint(),
+        "HEIC input requires building with `--features heic` (libheif)",
+    )))
+}
@@ -0,0 +1,129 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use serde_json::{json, Map, Value};
+
+/// Chart geometry: two pixels per level, one panel per channel.
+const BAR_WIDTH: u32 = 2;
+const PANEL_HEIGHT: u32 = 128;
+const GAP: u32 = 8;
+
+/// Per-channel counts of each 8-bit level.
+pub struct Histogram {
+    /// Channel names with their 256 counts: luma alone for grayscale
+    /// images, red, green, blue and luma otherwise.
+    pub channels: Vec<(&'static str, [u64; 256])>,
+    pub pixels: u64,
+}
+
+/// Counts the levels of every pixel in `image`, alpha ignored.
+pub fn compute(image: &DynamicImage) -> Histogram {
+    let pixels = image.to_rgb8();
+    let mut red = [0u64; 256];
+    let mut green = [0u64; 256];
+    let mut blue = [0u64; 256];
+    let mut luma = [0u64; 256];
+    for Rgb([r, g, b]) in pixels.pixels() {
+        red[usize::from(*r)] += 1;
+        green[usize::from(*g)] += 1;
+        blue[usize::from(*b)] += 1;
+        // BT.601, as the luma conversions elsewhere.
+        let y = 0.299 * f32::from(*r) + 0.587 * f32::from(*g) + 0.114 * f32::from(*b);
+        luma[y.round() as usize] += 1;
+    }
+
+    let channels = if image.color().has_color() {
+        vec![("red", red), ("green", green), ("blue", blue), ("luma", luma)]
+    } else {
+        vec![("luma", luma)]
+    };
+    Histogram {
+        channels,
+        pixels: u64::from(pixels.width()) * u64::from(pixels.height()),
+    }
+}
+
+impl Histogram {
+    /// Fractions of pixels at level 0 and at level 255 in `counts`, where
+    /// shadows or highlights have been clipped.
+    pub fn clipped(&self, counts: &[u64; 256]) -> (f64, f64) {
+        let total = self.pixels.max(1) as f64;
+        (counts[0] as f64 / total, counts[255] as f64 / total)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.channels
+            .iter()
+            .map(|(name, counts)| {
+                let (black, white) = self.clipped(counts);
+                format!("{}: {:.3}% clipped black, {:.3}% clipped white", name, black * 100.0, white * 100.0)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut channels = Map::new();
+        for (name, counts) in &self.channels {
+            let (black, white) = self.clipped(counts);
+            channels.insert(
+                name.to_string(),
+                json!({
+                    "counts": counts.to_vec(),
+                    "clipped_black_percent": black * 100.0,
+                    "clipped_white_percent": white * 100.0,
+                }),
+            );
+        }
+        json!({ "pixels": self.pixels, "channels": channels })
+    }
+
+    /// One row per level with a column per channel.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("level");
+        for (name, _) in &self.channels {
+            csv.push(',');
+            csv.push_str(name);
+        }
+        csv.push('\n');
+        for level in 0..256 {
+            csv.push_str(&level.to_string());
+            for (_, counts) in &self.channels {
+                csv.push_str(&format!(",{}", counts[level]));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Draws the channels as stacked bar charts on white, each scaled to
+    /// its own tallest bar. Clipped levels at either end are drawn
+    /// darker so they stand out.
+    pub fn chart(&self) -> RgbImage {
+        let count = self.channels.len() as u32;
+        let height = count * PANEL_HEIGHT + (count + 1) * GAP;
+        let mut chart = RgbImage::from_pixel(256 * BAR_WIDTH + 2 * GAP, height, Rgb([255, 255, 255]));
+
+        for (panel, (name, counts)) in self.channels.iter().enumerate() {
+            let color = match *name {
+                "red" => [220, 50, 47],
+                "green" => [60, 160, 60],
+                "blue" => [38, 110, 210],
+                _ => [110, 110, 110],
+            };
+            let top = GAP + panel as u32 * (PANEL_HEIGHT + GAP);
+            let tallest = counts.iter().copied().max().unwrap_or(0).max(1);
+            for (level, &value) in counts.iter().enumerate() {
+                let bar = ((value as f64 / tallest as f64) * f64::from(PANEL_HEIGHT)).round() as u32;
+                let shade = if level == 0 || level == 255 { color.map(|c| c / 2) } else { color };
+                for dx in 0..BAR_WIDTH {
+                    let x = GAP + level as u32 * BAR_WIDTH + dx;
+                    for y in top + PANEL_HEIGHT - bar..top + PANEL_HEIGHT {
+                        chart.put_pixel(x, y, Rgb(shade));
+                    }
+                    // Baseline.
+                    chart.put_pixel(x, top + PANEL_HEIGHT - 1, Rgb([0, 0, 0]));
+                }
+            }
+        }
+        chart
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+/// `--in-place`: outputs are written beside their inputs and replace them,
+/// with the originals optionally kept in a backup directory.
+#[derive(Debug, Clone, Default)]
+pub struct InPlace {
+    /// Where originals go, at the same path relative to the input root;
+    /// they're deleted when unset.
+    pub backup_dir: Option<PathBuf>,
+}
+
+impl InPlace {
+    /// Backs up `original`, found under `root`, before it's overwritten by
+    /// an output of the same name. Nothing happens without a backup
+    /// directory, or when the output goes elsewhere and [`retire`](Self::retire)
+    /// moves the original afterwards.
+    pub fn prepare(&self, root: &Path, original: &Path, outputs: &[PathBuf]) -> std::io::Result<()> {
+        let Some(backup) = self.backup_path(root, original) else {
+            return Ok(());
+        };
+        if outputs.iter().any(|output| output == original) {
+            create_parent(&backup)?;
+            std::fs::copy(original, &backup)?;
+        }
+        Ok(())
+    }
+
+    /// Moves `original` to the backup directory, or deletes it, once its
+    /// outputs are written; an original that was overwritten by one of
+    /// them is left alone. Returns whether it was deleted.
+    pub fn retire(&self, root: &Path, original: &Path, outputs: &[PathBuf]) -> std::io::Result<bool> {
+        if outputs.iter().any(|output| output == original) {
+            return Ok(false);
+        }
+        match self.backup_path(root, original) {
+            Some(backup) => {
+                create_parent(&backup)?;
+                // Renaming fails across file systems; copy instead.
+                if std::fs::rename(original, &backup).is_err() {
+                    std::fs::copy(original, &backup)?;
+                    std::fs::remove_file(original)?;
+                }
+                Ok(false)
+            }
+            None => std::fs::remove_file(original).map(|()| true),
+        }
+    }
+
+    fn backup_path(&self, root: &Path, original: &Path) -> Option<PathBuf> {
+        let relative = original.strip_prefix(root).unwrap_or(original);
+        let relative = if relative.is_absolute() { Path::new(relative.file_name()?) } else { relative };
+        Some(self.backup_dir.as_ref()?.join(relative))
+    }
+}
+
+fn create_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::remote;
+
+/// When an input whose outputs are already on disk is left alone, so a
+/// re-run only converts what changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Skip inputs whose outputs all exist, however old.
+    SkipExisting,
+    /// Skip inputs whose outputs all exist and were modified no earlier
+    /// than the input.
+    NewerOnly,
+}
+
+impl Mode {
+    /// Whether every one of `outputs` is there and, for
+    /// [`NewerOnly`](Mode::NewerOnly), at least as recent as `input`. URLs
+    /// have no modification time to compare, so they only count as up to
+    /// date under [`SkipExisting`](Mode::SkipExisting).
+    pub fn is_up_to_date(self, input: &Path, outputs: &[PathBuf]) -> bool {
+        if outputs.is_empty() || !outputs.iter().all(|output| output.is_file()) {
+            return false;
+        }
+        match self {
+            Mode::SkipExisting => true,
+            Mode::NewerOnly if remote::is_url(&input.to_string_lossy()) => false,
+            Mode::NewerOnly => {
+                let Some(source) = modified(input) else {
+                    return false;
+                };
+                outputs.iter().all(|output| modified(output).is_some_and(|time| time >= source))
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,209 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::codecs::bmp::BmpDecoder;
+use image::codecs::gif::GifDecoder;
+use image::codecs::hdr::HdrDecoder;
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::openexr::OpenExrDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::tiff::TiffDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{ColorType, ImageDecoder, ImageError, ImageFormat};
+use serde_json::{json, Map, Value};
+
+use crate::{animation, heif, jxl, pdf, raw, svg, tiff_page, ImageConverter};
+
+/// EXIF tags worth showing, with the names they're reported under.
+const EXIF_FIELDS: [(exif::Tag, &str); 11] = [
+    (exif::Tag::Make, "Make"),
+    (exif::Tag::Model, "Model"),
+    (exif::Tag::LensModel, "Lens"),
+    (exif::Tag::DateTimeOriginal, "Taken"),
+    (exif::Tag::Orientation, "Orientation"),
+    (exif::Tag::ExposureTime, "Exposure"),
+    (exif::Tag::FNumber, "Aperture"),
+    (exif::Tag::PhotographicSensitivity, "ISO"),
+    (exif::Tag::FocalLength, "Focal length"),
+    (exif::Tag::GPSLatitude, "GPS latitude"),
+    (exif::Tag::GPSLongitude, "GPS longitude"),
+];
+
+/// What `info` reports about one file.
+pub struct Info {
+    pub path: PathBuf,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub color: ColorType,
+    /// Frames of an animation, or pages of a TIFF or PDF.
+    pub frames: usize,
+    pub file_size: u64,
+    pub exif: Vec<(&'static str, String)>,
+}
+
+/// Reads the header of `path` (decoding only formats the `image` crate has
+/// no cheap header access for) and its EXIF.
+pub fn inspect(converter: &ImageConverter, path: &Path) -> Result<Info, Box<dyn std::error::Error>> {
+    let data = converter.read_input(path)?;
+
+    let (format, frames, (width, height, color)) = if jxl::is_jxl(&data) {
+        ("JPEG XL".to_string(), 1, decoded(converter, path, &data)?)
+    } else if heif::is_heif(&data) {
+        ("HEIC".to_string(), 1, decoded(converter, path, &data)?)
+    } else if pdf::is_pdf(&data) {
+        let pages = pdf::Document::parse(&data)?.page_count();
+        ("PDF".to_string(), pages, decoded(converter, path, &data)?)
+    } else if raw::is_raw(path) {
+        ("RAW".to_string(), 1, decoded(converter, path, &data)?)
+    } else if svg::is_svg(path, &data) {
+        ("SVG".to_string(), 1, decoded(converter, path, &data)?)
+    } else {
+        let format = image::guess_format(&data).or_else(|_| ImageFormat::from_path(path))?;
+        let frames = match format {
+            ImageFormat::Tiff => tiff_page::page_count(&data),
+            _ => animation::frame_count(&data),
+        };
+        (format_name(format), frames.unwrap_or(1), header(format, &data)?)
+    };
+
+    Ok(Info {
+        path: path.to_path_buf(),
+        format,
+        width,
+        height,
+        color,
+        frames,
+        file_size: data.len() as u64,
+        exif: read_exif(&data),
+    })
+}
+
+fn decoded(converter: &ImageConverter, path: &Path, data: &[u8]) -> Result<(u32, u32, ColorType), ImageError> {
+    let image = converter.decode_image(path, data)?;
+    Ok((image.width(), image.height(), image.color()))
+}
+
+/// Dimensions and pixel layout from the file header, without decoding
+/// pixels, for the formats that allow it.
+fn header(format: ImageFormat, data: &[u8]) -> Result<(u32, u32, ColorType), ImageError> {
+    fn probe<'a>(decoder: impl ImageDecoder<'a>) -> (u32, u32, ColorType) {
+        let (width, height) = decoder.dimensions();
+        (width, height, decoder.color_type())
+    }
+    let reader = || Cursor::new(data);
+    Ok(match format {
+        ImageFormat::Png => probe(PngDecoder::new(reader())?),
+        ImageFormat::Jpeg => probe(JpegDecoder::new(reader())?),
+        ImageFormat::Gif => probe(GifDecoder::new(reader())?),
+        ImageFormat::WebP => probe(WebPDecoder::new(reader())?),
+        ImageFormat::Tiff => probe(TiffDecoder::new(reader())?),
+        ImageFormat::Bmp => probe(BmpDecoder::new(reader())?),
+        ImageFormat::OpenExr => probe(OpenExrDecoder::new(reader())?),
+        ImageFormat::Hdr => {
+            let metadata = HdrDecoder::new(reader())?.metadata();
+            (metadata.width, metadata.height, ColorType::Rgb32F)
+        }
+        _ => {
+            let image = image::load_from_memory_with_format(data, format)?;
+            (image.width(), image.height(), image.color())
+        }
+    })
+}
+
+fn format_name(format: ImageFormat) -> String {
+    match format {
+        ImageFormat::Jpeg => "JPEG".to_string(),
+        ImageFormat::WebP => "WebP".to_string(),
+        ImageFormat::OpenExr => "OpenEXR".to_string(),
+        ImageFormat::Hdr => "Radiance HDR".to_string(),
+        format => format!("{:?}", format).to_uppercase(),
+    }
+}
+
+fn read_exif(data: &[u8]) -> Vec<(&'static str, String)> {
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(data)) else {
+        return Vec::new();
+    };
+    EXIF_FIELDS
+        .iter()
+        .filter_map(|&(tag, name)| {
+            let field = exif.get_field(tag, exif::In::PRIMARY)?;
+            let value = field.display_value().with_unit(&exif).to_string();
+            Some((name, value.trim_matches('"').trim().to_string()))
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+fn channels(color: ColorType) -> &'static str {
+    match (color.has_color(), color.has_alpha()) {
+        (false, false) => "Gray",
+        (false, true) => "Gray+Alpha",
+        (true, false) => "RGB",
+        (true, true) => "RGBA",
+    }
+}
+
+fn bit_depth(color: ColorType) -> u16 {
+    color.bits_per_pixel() / u16::from(color.channel_count())
+}
+
+/// Formats a byte count with a binary unit, e.g. `2.4 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+impl Info {
+    /// One summary line, then the EXIF fields indented beneath it.
+    pub fn to_text(&self) -> String {
+        let frames = match self.frames {
+            1 => String::new(),
+            count => format!(", {} frames", count),
+        };
+        let mut text = format!(
+            "{}: {} {}x{} {} {}-bit{}, {}",
+            self.path.display(),
+            self.format,
+            self.width,
+            self.height,
+            channels(self.color),
+            bit_depth(self.color),
+            frames,
+            format_size(self.file_size)
+        );
+        for (name, value) in &self.exif {
+            text.push_str(&format!("\n  {}: {}", name, value));
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> Value {
+        let exif: Map<String, Value> = self
+            .exif
+            .iter()
+            .map(|(name, value)| (name.to_string(), Value::from(value.as_str())))
+            .collect();
+        json!({
+            "path": self.path.display().to_string(),
+            "format": self.format,
+            "width": self.width,
+            "height": self.height,
+            "channels": channels(self.color),
+            "bit_depth": bit_depth(self.color),
+            "frames": self.frames,
+            "file_size": self.file_size,
+            "exif": exif,
+        })
+    }
+}
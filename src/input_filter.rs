@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use crate::remote;
+
+/// `--min-width`, `--min-height`, `--min-filesize` and `--max-filesize`:
+/// batch inputs outside these bounds are skipped. Sizes come from the file
+/// header alone, so an input is never decoded just to be left out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InputFilter {
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+    pub min_filesize: Option<u64>,
+    pub max_filesize: Option<u64>,
+}
+
+impl InputFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_width.is_none() && self.min_height.is_none() && self.min_filesize.is_none() && self.max_filesize.is_none()
+    }
+
+    /// Why `input` is skipped, or `None` to convert it. URLs aren't fetched
+    /// to be checked, and formats whose header the image crate can't read
+    /// (SVG, PDF, HEIC, JPEG XL, AVIF, camera RAW) pass the dimension bounds.
+    pub fn reason(&self, input: &Path) -> Option<&'static str> {
+        if self.is_empty() || remote::is_url(&input.to_string_lossy()) {
+            return None;
+        }
+        if self.min_filesize.is_some() || self.max_filesize.is_some() {
+            let size = std::fs::metadata(input).ok()?.len();
+            if self.min_filesize.is_some_and(|min| size < min) {
+                return Some("smaller than --min-filesize");
+            }
+            if self.max_filesize.is_some_and(|max| size > max) {
+                return Some("larger than --max-filesize");
+            }
+        }
+        if self.min_width.is_some() || self.min_height.is_some() {
+            let (width, height) = header_dimensions(input)?;
+            if self.min_width.is_some_and(|min| width < min) {
+                return Some("narrower than --min-width");
+            }
+            if self.min_height.is_some_and(|min| height < min) {
+                return Some("shorter than --min-height");
+            }
+        }
+        None
+    }
+}
+
+/// Width and height read from the file header without decoding pixels.
+pub fn header_dimensions(path: &Path) -> Option<(u32, u32)> {
+    image::io::Reader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
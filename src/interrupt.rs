@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::atomic;
+
+/// Exit status of a batch run stopped by Ctrl+C, as shells report a process
+/// killed by SIGINT.
+pub const EXIT_CODE: i32 = 130;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Takes over Ctrl+C for a batch run. The first press lets the files in
+/// progress finish and starts no more, so the run ends with its summary;
+/// a second one exits at once, removing the temporary files of outputs
+/// still being written.
+pub fn install() {
+    let installed = ctrlc::set_handler(|| {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            atomic::remove_temporaries();
+            eprintln!("\nAborted");
+            std::process::exit(EXIT_CODE);
+        }
+        eprintln!("\nInterrupted: finishing the files in progress (press Ctrl+C again to abort them)");
+    });
+    if let Err(e) = installed {
+        eprintln!("Warning: Ctrl+C will stop the run without a summary: {}", e);
+    }
+}
+
+/// Whether Ctrl+C has been pressed since [`install`].
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,114 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::error::{EncodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat};
+
+use crate::color::Subsampling;
+
+/// Library that writes JPEG output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoder {
+    /// The pure-Rust encoders, always available.
+    #[default]
+    Builtin,
+    /// mozjpeg: trellis quantization and optimized Huffman tables, typically
+    /// 10-20% smaller at the same visual quality. Needs `--features mozjpeg`.
+    Mozjpeg,
+}
+
+impl Encoder {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "builtin" | "default" => Ok(Encoder::Builtin),
+            "mozjpeg" => Ok(Encoder::Mozjpeg),
+            _ => Err(format!("Invalid JPEG encoder: {} (expected builtin or mozjpeg)", value)),
+        }
+    }
+}
+
+/// JPEG encoder settings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JpegOptions {
+    /// Writes a progressive JPEG, which browsers render coarse-to-fine as it
+    /// downloads.
+    pub progressive: bool,
+    pub encoder: Encoder,
+    /// Chroma subsampling; unset leaves the encoder's default, 4:4:4 for
+    /// baseline output and 4:2:0 for progressive and mozjpeg output.
+    pub subsampling: Option<Subsampling>,
+}
+
+fn error(message: impl std::fmt::Display) -> ImageError {
+    ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::Jpeg), message.to_string()))
+}
+
+/// Encodes `image` as JPEG. Baseline 4:4:4 output uses the `image` encoder;
+/// progressive or subsampled output, which it can't write, goes through
+/// `jpeg-encoder`.
+pub fn encode(image: &DynamicImage, quality: u8, options: JpegOptions) -> Result<Vec<u8>, ImageError> {
+    if options.encoder == Encoder::Mozjpeg {
+        return encode_mozjpeg(image, quality, options.subsampling);
+    }
+
+    let mut output = Vec::new();
+    if !options.progressive && matches!(options.subsampling, None | Some(Subsampling::Yuv444)) {
+        JpegEncoder::new_with_quality(&mut output, quality).encode_image(image)?;
+        return Ok(output);
+    }
+
+    let (Ok(width), Ok(height)) = (u16::try_from(image.width()), u16::try_from(image.height())) else {
+        return Err(error(format!("{}x{} exceeds the JPEG limit of 65535 pixels per side", image.width(), image.height())));
+    };
+
+    let mut encoder = jpeg_encoder::Encoder::new(&mut output, quality.max(1));
+    encoder.set_progressive(options.progressive);
+    if let Some(subsampling) = options.subsampling {
+        encoder.set_sampling_factor(match subsampling {
+            Subsampling::Yuv420 => jpeg_encoder::SamplingFactor::R_4_2_0,
+            Subsampling::Yuv422 => jpeg_encoder::SamplingFactor::R_4_2_2,
+            Subsampling::Yuv444 => jpeg_encoder::SamplingFactor::R_4_4_4,
+        });
+    }
+    let result = if image.color().has_color() {
+        encoder.encode(image.to_rgb8().as_raw(), width, height, jpeg_encoder::ColorType::Rgb)
+    } else {
+        encoder.encode(image.to_luma8().as_raw(), width, height, jpeg_encoder::ColorType::Luma)
+    };
+    result.map_err(error)?;
+    Ok(output)
+}
+
+/// mozjpeg's default profile always writes progressive scans, tuned per
+/// image, so `--progressive` changes nothing here.
+#[cfg(feature = "mozjpeg")]
+fn encode_mozjpeg(image: &DynamicImage, quality: u8, subsampling: Option<Subsampling>) -> Result<Vec<u8>, ImageError> {
+    use mozjpeg::{ColorSpace, Compress};
+
+    let (color_space, pixels) = if image.color().has_color() {
+        (ColorSpace::JCS_RGB, image.to_rgb8().into_raw())
+    } else {
+        (ColorSpace::JCS_GRAYSCALE, image.to_luma8().into_raw())
+    };
+    let (width, height) = (image.width() as usize, image.height() as usize);
+
+    // libjpeg reports errors by unwinding out of the C code.
+    std::panic::catch_unwind(|| {
+        let mut compress = Compress::new(color_space);
+        compress.set_size(width, height);
+        compress.set_quality(f32::from(quality.max(1)));
+        if let (Some(subsampling), ColorSpace::JCS_RGB) = (subsampling, color_space) {
+            let (horizontal, vertical) = subsampling.factors();
+            let size = (horizontal as u8, vertical as u8);
+            compress.set_chroma_sampling_pixel_sizes(size, size);
+        }
+        let mut started = compress.start_compress(Vec::new())?;
+        started.write_scanlines(&pixels)?;
+        started.finish()
+    })
+    .map_err(|_| error("mozjpeg failed to encode the image"))?
+    .map_err(error)
+}
+
+#[cfg(not(feature = "mozjpeg"))]
+fn encode_mozjpeg(_image: &DynamicImage, _quality: u8, _subsampling: Option<Subsampling>) -> Result<Vec<u8>, ImageError> {
+    Err(error("--encoder mozjpeg requires building with `--features mozjpeg`"))
+}
@@ -0,0 +1,125 @@
+use std::io::Cursor;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageBuffer, ImageError};
+use jxl_oxide::{EnumColourEncoding, JxlImage, RenderingIntent};
+
+/// Bare codestream signature.
+const CODESTREAM_SIGNATURE: &[u8] = &[0xFF, 0x0A];
+/// ISOBMFF container signature box.
+const CONTAINER_SIGNATURE: &[u8] = b"\0\0\0\x0CJXL \r\n\x87\n";
+
+fn hint() -> ImageFormatHint {
+    ImageFormatHint::Name("JPEG XL".to_string())
+}
+
+/// Whether `data` starts with a JPEG XL codestream or container signature.
+pub fn is_jxl(data: &[u8]) -> bool {
+    data.starts_with(CODESTREAM_SIGNATURE) || data.starts_with(CONTAINER_SIGNATURE)
+}
+
+/// Width and height from the image header, without rendering any pixels.
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let image = JxlImage::builder().read(Cursor::new(data)).ok()?;
+    Some((image.width(), image.height()))
+}
+
+/// Decodes the first frame of a JPEG XL image. Samples deeper than 8 bits
+/// decode to a 16-bit image; CMYK sources are rendered to sRGB.
+pub fn decode(data: &[u8]) -> Result<DynamicImage, ImageError> {
+    let error = |e: &dyn std::fmt::Display| ImageError::Decoding(DecodingError::new(hint(), e.to_string()));
+
+    let mut image = JxlImage::builder().read(Cursor::new(data)).map_err(|e| error(&e))?;
+    if image.pixel_format().has_black() {
+        image.request_color_encoding(EnumColourEncoding::srgb(RenderingIntent::Relative));
+    }
+    let wide = image.image_header().metadata.bit_depth.bits_per_sample() > 8;
+
+    let render = image.render_frame(0).map_err(|e| error(&e))?;
+    let mut stream = render.stream();
+    let (width, height, channels) = (stream.width(), stream.height(), stream.channels());
+    let len = width as usize * height as usize * channels as usize;
+
+    let decoded = if wide {
+        let mut samples = vec![0u16; len];
+        stream.write_to_buffer(&mut samples);
+        match channels {
+            1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma16),
+            2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA16),
+            3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb16),
+            4 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba16),
+            _ => None,
+        }
+    } else {
+        let mut samples = vec![0u8; len];
+        stream.write_to_buffer(&mut samples);
+        match channels {
+            1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma8),
+            2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA8),
+            3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+            4 => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba8),
+            _ => None,
+        }
+    };
+
+    decoded.ok_or_else(|| error(&format!("unsupported channel count {}", channels)))
+}
+
+/// Encodes `image` with libjxl. `quality` follows the JPEG 0-100 scale;
+/// `lossless` ignores it and stores the exact pixels.
+#[cfg(feature = "jxl")]
+pub fn encode(image: &DynamicImage, quality: u8, lossless: bool) -> Result<Vec<u8>, ImageError> {
+    let has_alpha = image.color().has_alpha();
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .has_alpha(has_alpha)
+        .lossless(lossless)
+        .uses_original_profile(lossless)
+        .jpeg_quality(f32::from(quality))
+        .build()
+        .map_err(encoding_error)?;
+
+    let (width, height) = (image.width(), image.height());
+    let wide = image.color().bytes_per_pixel() / image.color().channel_count() > 1;
+    let encoded = match (wide, has_alpha) {
+        (false, false) => encoder.encode::<u8>(image.to_rgb8().as_raw(), width, height),
+        (false, true) => encoder.encode::<u8>(image.to_rgba8().as_raw(), width, height),
+        (true, false) => encoder.encode::<u16>(image.to_rgb16().as_raw(), width, height),
+        (true, true) => encoder.encode::<u16>(image.to_rgba16().as_raw(), width, height),
+    };
+    encoded.map_err(encoding_error)
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn encode(_image: &DynamicImage, _quality: u8, _lossless: bool) -> Result<Vec<u8>, ImageError> {
+    Err(not_built())
+}
+
+/// Repacks a JPEG file into JPEG XL without decoding it, keeping the data
+/// needed to reconstruct the original JPEG bit for bit.
+#[cfg(feature = "jxl")]
+pub fn recompress_jpeg(jpeg: &[u8]) -> Result<Vec<u8>, ImageError> {
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .use_container(true)
+        .uses_original_profile(true)
+        .build()
+        .map_err(encoding_error)?;
+    encoder.encode_jpeg(jpeg).map_err(encoding_error)
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn recompress_jpeg(_jpeg: &[u8]) -> Result<Vec<u8>, ImageError> {
+    Err(not_built())
+}
+
+#[cfg(feature = "jxl")]
+fn encoding_error(e: jpegxl_rs::EncodeError) -> ImageError {
+    ImageError::Encoding(image::error::EncodingError::new(hint(), e))
+}
+
+#[cfg(not(feature = "jxl"))]
+fn not_built() -> ImageError {
+    ImageError::Encoding(image::error::EncodingError::new(
+        hint(),
+        "JPEG XL output requires building with `--features jxl` (libjxl)",
+    ))
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use image::DynamicImage;
+
+use crate::adjust::{map_rgb, promote_gray};
+
+/// A 3D color lookup table from an Adobe/Resolve `.cube` file.
+#[derive(Debug, Clone)]
+pub struct Lut {
+    /// Samples per axis.
+    size: usize,
+    /// Output colors with red varying fastest, then green, then blue.
+    table: Vec<[f32; 3]>,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+}
+
+impl Lut {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read LUT {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid LUT {}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let triple = |fields: &[&str]| -> Result<[f32; 3], String> {
+            match fields {
+                [r, g, b] => {
+                    let parse = |value: &str| value.parse::<f32>().map_err(|_| format!("bad number {}", value));
+                    Ok([parse(r)?, parse(g)?, parse(b)?])
+                }
+                _ => Err(format!("expected three values, got {}", fields.join(" "))),
+            }
+        };
+
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let at_line = |e: String| format!("line {}: {}", number + 1, e);
+            match fields[0] {
+                "TITLE" | "LUT_3D_INPUT_RANGE" => {}
+                "LUT_1D_SIZE" => return Err("1D LUTs are not supported".to_string()),
+                "LUT_3D_SIZE" => {
+                    let value = fields.get(1).and_then(|value| value.parse().ok()).filter(|&size: &usize| (2..=256).contains(&size));
+                    size = Some(value.ok_or_else(|| at_line("LUT_3D_SIZE must be 2-256".to_string()))?);
+                }
+                "DOMAIN_MIN" => domain_min = triple(&fields[1..]).map_err(at_line)?,
+                "DOMAIN_MAX" => domain_max = triple(&fields[1..]).map_err(at_line)?,
+                _ => table.push(triple(&fields).map_err(at_line)?),
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE")?;
+        if table.len() != size * size * size {
+            return Err(format!("expected {} entries for size {}, found {}", size * size * size, size, table.len()));
+        }
+        if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+            return Err("DOMAIN_MAX must exceed DOMAIN_MIN".to_string());
+        }
+        Ok(Self {
+            size,
+            table,
+            domain_min,
+            domain_max,
+        })
+    }
+
+    /// Looks up `rgb` with trilinear interpolation between the eight
+    /// surrounding table entries.
+    fn lookup(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        let position: [f32; 3] = std::array::from_fn(|c| {
+            let normalized = (rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]);
+            normalized.clamp(0.0, 1.0) * last
+        });
+        let low = position.map(|value| (value.floor() as usize).min(self.size - 2));
+        let fraction: [f32; 3] = std::array::from_fn(|c| position[c] - low[c] as f32);
+        let entry = |r: usize, g: usize, b: usize| self.table[(b * self.size + g) * self.size + r];
+
+        let mut output = [0.0; 3];
+        for corner in 0..8 {
+            let (dr, dg, db) = (corner & 1, corner >> 1 & 1, corner >> 2 & 1);
+            let weight = [dr, dg, db]
+                .iter()
+                .zip(fraction)
+                .map(|(&high, fraction)| if high == 1 { fraction } else { 1.0 - fraction })
+                .product::<f32>();
+            let value = entry(low[0] + dr, low[1] + dg, low[2] + db);
+            for c in 0..3 {
+                output[c] += weight * value[c];
+            }
+        }
+        output
+    }
+
+    /// Applies the table to the encoded RGB values of `image`; gray images
+    /// come out in color if the LUT tints.
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        map_rgb(promote_gray(image), |rgb| self.lookup(rgb))
+    }
+}
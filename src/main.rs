@@ -1,15 +1,122 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::Path;
-use std::fs::File;
-use std::io::BufReader;
-use image::{ImageFormat, DynamicImage, ImageError};
+use std::path::{Path, PathBuf};
+use std::io::{Cursor, Read, Seek, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use image::{ColorType, Frame, ImageFormat, DynamicImage, ImageError};
+use image::codecs::tga::TgaEncoder;
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use glob::Pattern;
+use rayon::prelude::*;
 
-#[derive(Debug, Clone, Copy)]
+mod adjust;
+mod animation;
+mod atomic;
+mod avif;
+mod blurhash;
+mod budget;
+mod checkpoint;
+mod collision;
+mod color;
+mod compare;
+mod config;
+mod contact_sheet;
+mod dated;
+mod dedupe;
+mod deskew;
+mod dry_run;
+mod dominant;
+mod equalize;
+mod favicon;
+mod filter;
+mod frames;
+mod hdr;
+mod heif;
+mod histogram;
+mod in_place;
+mod incremental;
+mod info;
+mod input_filter;
+mod interrupt;
+mod jpeg;
+mod jxl;
+mod lut;
+mod memory;
+mod metadata;
+mod overwrite;
+mod pdf;
+mod pipeline;
+mod placeholder;
+mod png_opt;
+mod preserve;
+mod preset;
+mod progress;
+mod quantize;
+mod recipe;
+mod report;
+mod raster;
+mod raw;
+mod remote;
+mod responsive;
+mod sidecar;
+mod spritesheet;
+mod stitch;
+mod svg;
+mod template;
+mod text;
+mod thumbnail;
+mod tiff_page;
+mod tiles;
+mod transform;
+mod verify;
+mod walk;
+mod watch;
+
+use adjust::{Curves, Effect, Hue, Illuminant, Levels, Tone, WhiteBalance};
+use equalize::Equalize;
+use filter::{Blur, Denoise, Sharpen};
+use metadata::Metadata;
+use progress::BatchProgress;
+use report::Conversion;
+use raster::Raster;
+use text::Caption;
+use transform::{
+    parse_color, Anchor, AspectRatio, Crop, Flip, Pad, Resize, ResizeMode, ResizeSpec, Rotation,
+    SmartCrop, Transform, Trim, Watermark,
+};
+
+/// Largest image, in pixels, decoded unless `--max-pixels` says otherwise:
+/// 100 megapixels, about 400 MB as 8-bit RGBA.
+const DEFAULT_MAX_PIXELS: u64 = 100_000_000;
+
+/// Encoder quality unless `--quality` says otherwise.
+const DEFAULT_QUALITY: u8 = 85;
+
+/// JPEG quality without `--quality`: the image crate's default, which JPEG
+/// output has always been written at.
+const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum SupportedFormat {
     Jpeg,
     Png,
     WebP,
     Avif,
+    Tiff,
+    Bmp,
+    Gif,
+    Ico,
+    Tga,
+    Qoi,
+    Jxl,
+    Heic,
+    Raw,
+    Svg,
+    Pdf,
+    Exr,
+    Hdr,
 }
 
 impl SupportedFormat {
@@ -19,6 +126,19 @@ impl SupportedFormat {
             "png" => Ok(SupportedFormat::Png),
             "webp" => Ok(SupportedFormat::WebP),
             "avif" => Ok(SupportedFormat::Avif),
+            "tif" | "tiff" => Ok(SupportedFormat::Tiff),
+            "bmp" => Ok(SupportedFormat::Bmp),
+            "gif" => Ok(SupportedFormat::Gif),
+            "ico" => Ok(SupportedFormat::Ico),
+            "tga" => Ok(SupportedFormat::Tga),
+            "qoi" => Ok(SupportedFormat::Qoi),
+            "jxl" => Ok(SupportedFormat::Jxl),
+            "heic" | "heif" => Ok(SupportedFormat::Heic),
+            "svg" | "svgz" => Ok(SupportedFormat::Svg),
+            "pdf" => Ok(SupportedFormat::Pdf),
+            "exr" => Ok(SupportedFormat::Exr),
+            "hdr" => Ok(SupportedFormat::Hdr),
+            ext if raw::EXTENSIONS.contains(&ext) => Ok(SupportedFormat::Raw),
             _ => Err(format!("Unsupported format: {}", ext)),
         }
     } 
@@ -29,200 +149,4036 @@ impl SupportedFormat {
             SupportedFormat::Png => "png",
             SupportedFormat::WebP => "webp",
             SupportedFormat::Avif => "avif",
+            SupportedFormat::Tiff => "tiff",
+            SupportedFormat::Bmp => "bmp",
+            SupportedFormat::Gif => "gif",
+            SupportedFormat::Ico => "ico",
+            SupportedFormat::Tga => "tga",
+            SupportedFormat::Qoi => "qoi",
+            SupportedFormat::Jxl => "jxl",
+            SupportedFormat::Heic => "heic",
+            SupportedFormat::Raw => "raw",
+            SupportedFormat::Svg => "svg",
+            SupportedFormat::Pdf => "pdf",
+            SupportedFormat::Exr => "exr",
+            SupportedFormat::Hdr => "hdr",
         }
     }
+
+    /// Whether the format can store transparency (GIF only fully on or off).
+    fn has_alpha(self) -> bool {
+        self != SupportedFormat::Jpeg
+    }
 }
 
 struct ImageConverter {
     quality: u8,
+    format_quality: Vec<(SupportedFormat, u8)>,
+    jobs: usize,
+    max_memory: Option<u64>,
+    recursive: bool,
+    walk: walk::Options,
+    input_filter: input_filter::InputFilter,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    pipeline: pipeline::Pipeline,
+    auto_orient: bool,
+    keep_metadata: bool,
+    strip_metadata: bool,
+    to_srgb: bool,
+    page: usize,
+    animated: bool,
+    tga_rle: bool,
+    jxl_lossless: bool,
+    webp_lossless: bool,
+    webp_near_lossless: Option<u8>,
+    jpeg_recompress: bool,
+    raster: Raster,
+    tone_map: hdr::ToneMap,
+    all_pages: bool,
+    max_size: Option<u64>,
+    max_pixels: Option<u64>,
+    allow_downscale: bool,
+    bit_depth: Option<u8>,
+    background: Option<image::Rgba<u8>>,
+    quantize: Option<quantize::Quantize>,
+    variants: Option<responsive::Variants>,
+    suffix: String,
+    name_template: Option<template::Template>,
+    rename_by_date: Option<dated::DateNaming>,
+    on_collision: collision::Strategy,
+    json: bool,
+    report: Option<PathBuf>,
+    incremental: Option<incremental::Mode>,
+    overwrite: overwrite::Policy,
+    verify: Option<verify::Verify>,
+    in_place: Option<in_place::InPlace>,
+    delete_original: bool,
+    preserve: preserve::Preserve,
+    checkpoint: Option<PathBuf>,
+    resume: bool,
+    /// Outputs taken by earlier inputs of this run, so two inputs can't
+    /// both settle on the same free name.
+    claimed: Mutex<HashSet<PathBuf>>,
+    skip_duplicates: Option<dedupe::Settings>,
+    analysis: sidecar::Analysis,
+    placeholder: Option<placeholder::Placeholder>,
+    jpeg: jpeg::JpegOptions,
+    png: png_opt::PngOptions,
+    avif: avif::AvifOptions,
 }
 
 impl ImageConverter {
     fn new(quality: u8) -> Self {
         Self {
             quality: quality.min(100),
+            format_quality: Vec::new(),
+            jobs: 0,
+            max_memory: None,
+            recursive: false,
+            walk: walk::Options::default(),
+            input_filter: input_filter::InputFilter::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            pipeline: pipeline::Pipeline::default(),
+            auto_orient: true,
+            keep_metadata: false,
+            strip_metadata: false,
+            to_srgb: false,
+            page: 1,
+            animated: true,
+            tga_rle: true,
+            jxl_lossless: false,
+            webp_lossless: false,
+            webp_near_lossless: None,
+            jpeg_recompress: false,
+            raster: Raster::default(),
+            tone_map: hdr::ToneMap::default(),
+            all_pages: false,
+            max_size: None,
+            max_pixels: Some(DEFAULT_MAX_PIXELS),
+            allow_downscale: false,
+            bit_depth: None,
+            background: None,
+            quantize: None,
+            variants: None,
+            suffix: String::new(),
+            name_template: None,
+            rename_by_date: None,
+            on_collision: collision::Strategy::Error,
+            json: false,
+            report: None,
+            incremental: None,
+            overwrite: overwrite::Policy::default(),
+            verify: None,
+            in_place: None,
+            delete_original: false,
+            preserve: preserve::Preserve::default(),
+            checkpoint: None,
+            resume: false,
+            claimed: Mutex::new(HashSet::new()),
+            skip_duplicates: None,
+            analysis: sidecar::Analysis::default(),
+            placeholder: None,
+            jpeg: jpeg::JpegOptions::default(),
+            png: png_opt::PngOptions::default(),
+            avif: avif::AvifOptions::default(),
+        }
+    }
+
+    /// Overrides the encoder quality given to [`new`](Self::new).
+    fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality.min(100);
+        self
+    }
+
+    /// Sets the quality for particular formats; later entries win, and
+    /// formats not listed use the overall quality.
+    fn with_format_quality(mut self, format_quality: Vec<(SupportedFormat, u8)>) -> Self {
+        self.format_quality = format_quality;
+        self
+    }
+
+    /// Appends `suffix` to output file stems in batch and watch mode.
+    fn with_suffix(mut self, suffix: String) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// Names batch and watch outputs after `template` instead of the input
+    /// stem and `--suffix`.
+    fn with_name_template(mut self, template: Option<template::Template>) -> Self {
+        self.name_template = template;
+        self
+    }
+
+    /// Names batch and watch outputs after when each input was taken,
+    /// gathering them under `output_dir` rather than mirroring the input tree.
+    fn with_rename_by_date(mut self, naming: Option<dated::DateNaming>) -> Self {
+        self.rename_by_date = naming;
+        self
+    }
+
+    /// Settles batch inputs that would share an output name.
+    fn with_on_collision(mut self, strategy: collision::Strategy) -> Self {
+        self.on_collision = strategy;
+        self
+    }
+
+    /// Reports batch results as JSON events instead of text.
+    fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Writes a CSV or JSON report of every batch input to `report`.
+    fn with_report(mut self, report: Option<PathBuf>) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Leaves inputs alone whose outputs are already up to date.
+    fn with_incremental(mut self, incremental: Option<incremental::Mode>) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Sets what happens to output files that already exist.
+    fn with_overwrite(mut self, overwrite: overwrite::Policy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Reads back and decodes every output written, failing the input if
+    /// it doesn't match.
+    fn with_verify(mut self, verify: Option<verify::Verify>) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Replaces batch inputs with their outputs instead of writing to an
+    /// output directory.
+    fn with_in_place(mut self, in_place: Option<in_place::InPlace>) -> Self {
+        self.in_place = in_place;
+        self
+    }
+
+    /// Deletes each input once its outputs have been written.
+    fn with_delete_original(mut self, delete_original: bool) -> Self {
+        self.delete_original = delete_original;
+        self
+    }
+
+    /// Copies each input's file times or permissions to its outputs.
+    fn with_preserve(mut self, preserve: preserve::Preserve) -> Self {
+        self.preserve = preserve;
+        self
+    }
+
+    /// Keeps batch checkpoints at `checkpoint` instead of in the output
+    /// directory, and with `resume` skips the inputs one already lists.
+    fn with_checkpoint(mut self, checkpoint: Option<PathBuf>, resume: bool) -> Self {
+        self.checkpoint = checkpoint;
+        self.resume = resume;
+        self
+    }
+
+    /// Caps the number of files converted concurrently in batch mode.
+    /// `0` lets rayon pick one worker per logical CPU.
+    fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Holds back batch conversions while those running are expected to
+    /// need more than `max_memory` bytes between them.
+    fn with_max_memory(mut self, max_memory: Option<u64>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// Makes batch mode descend into subdirectories, mirroring their layout
+    /// under the output directory.
+    fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Which entries of input directories batch runs look at: symbolic
+    /// links, hidden files, and how deep recursive mode goes.
+    fn with_walk(mut self, walk: walk::Options) -> Self {
+        self.walk = walk;
+        self
+    }
+
+    /// Skips batch inputs outside the given pixel and file size bounds.
+    fn with_input_filter(mut self, input_filter: input_filter::InputFilter) -> Self {
+        self.input_filter = input_filter;
+        self
+    }
+
+    /// Restricts batch mode to files matching at least one `include` pattern
+    /// (if any are given) and none of the `exclude` patterns.
+    fn with_filters(mut self, include: Vec<Pattern>, exclude: Vec<Pattern>) -> Self {
+        self.include = include;
+        self.exclude = exclude;
+        self
+    }
+
+    /// Sets the operations applied, in order, between decoding and encoding.
+    fn with_pipeline(mut self, pipeline: pipeline::Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Controls whether the EXIF Orientation tag is applied on load.
+    fn with_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// Carries EXIF and XMP from the source over to the output.
+    fn with_keep_metadata(mut self, keep_metadata: bool) -> Self {
+        self.keep_metadata = keep_metadata;
+        self
+    }
+
+    /// Guarantees outputs carry no EXIF, XMP, ICC or text metadata.
+    fn with_strip_metadata(mut self, strip_metadata: bool) -> Self {
+        self.strip_metadata = strip_metadata;
+        self
+    }
+
+    /// Converts pixels from the embedded ICC profile to sRGB on load instead
+    /// of embedding the profile in the output.
+    fn with_to_srgb(mut self, to_srgb: bool) -> Self {
+        self.to_srgb = to_srgb;
+        self
+    }
+
+    /// Selects which page (1-based) of a multi-page TIFF to convert.
+    fn with_page(mut self, page: usize) -> Self {
+        self.page = page;
+        self
+    }
+
+    /// Keeps every frame of animated inputs when the output format can store
+    /// an animation, instead of converting only the first.
+    fn with_animated(mut self, animated: bool) -> Self {
+        self.animated = animated;
+        self
+    }
+
+    /// Chooses between RLE-compressed (default) and uncompressed TGA output.
+    fn with_tga_rle(mut self, tga_rle: bool) -> Self {
+        self.tga_rle = tga_rle;
+        self
+    }
+
+    /// Encodes JPEG XL output losslessly instead of at `quality`.
+    fn with_jxl_lossless(mut self, jxl_lossless: bool) -> Self {
+        self.jxl_lossless = jxl_lossless;
+        self
+    }
+
+    /// Encodes WebP output losslessly instead of at `quality`.
+    fn with_webp_lossless(mut self, webp_lossless: bool) -> Self {
+        self.webp_lossless = webp_lossless;
+        self
+    }
+
+    /// Encodes WebP output near-losslessly: lossless coding of pixels
+    /// preprocessed at `level` (0-100, where 100 changes nothing).
+    fn with_webp_near_lossless(mut self, webp_near_lossless: Option<u8>) -> Self {
+        self.webp_near_lossless = webp_near_lossless;
+        self
+    }
+
+    /// Repacks JPEG inputs into JPEG XL losslessly (reconstructible bit for
+    /// bit) instead of re-encoding their pixels.
+    fn with_jpeg_recompress(mut self, jpeg_recompress: bool) -> Self {
+        self.jpeg_recompress = jpeg_recompress;
+        self
+    }
+
+    /// Sets the resolution or target size vector inputs are rasterized at.
+    fn with_raster(mut self, raster: Raster) -> Self {
+        self.raster = raster;
+        self
+    }
+
+    /// Sets the tone mapping curve and exposure for OpenEXR and HDR input.
+    fn with_tone_map(mut self, tone_map: hdr::ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Caps every output at `max_size` bytes by lowering the quality.
+    fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Refuses inputs larger than `max_pixels`, when set.
+    fn with_max_pixels(mut self, max_pixels: Option<u64>) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Lets `max_size` shrink images that don't fit even at the lowest
+    /// quality, instead of failing.
+    fn with_allow_downscale(mut self, allow_downscale: bool) -> Self {
+        self.allow_downscale = allow_downscale;
+        self
+    }
+
+    /// Forces 8 or 16 bits per sample in the output instead of keeping the
+    /// source depth where the format can store it.
+    fn with_bit_depth(mut self, bit_depth: Option<u8>) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    /// Sets the color transparent images are composited over when the
+    /// output format has no alpha channel.
+    fn with_background(mut self, background: Option<image::Rgba<u8>>) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Reduces PNG and GIF output to an indexed palette.
+    fn with_quantize(mut self, quantize: Option<quantize::Quantize>) -> Self {
+        self.quantize = quantize;
+        self
+    }
+
+    /// Writes one output per width (and format) instead of a single file.
+    fn with_variants(mut self, variants: Option<responsive::Variants>) -> Self {
+        self.variants = variants;
+        self
+    }
+
+    /// Leaves near-duplicate inputs out of batch runs, keeping the first of
+    /// each group in path order.
+    fn with_skip_duplicates(mut self, skip_duplicates: Option<dedupe::Settings>) -> Self {
+        self.skip_duplicates = skip_duplicates;
+        self
+    }
+
+    /// Writes a JSON sidecar with placeholder hashes and colors beside each
+    /// output.
+    fn with_analysis(mut self, analysis: sidecar::Analysis) -> Self {
+        self.analysis = analysis;
+        self
+    }
+
+    /// Also writes a tiny low-quality preview beside each output.
+    fn with_placeholder(mut self, placeholder: Option<placeholder::Placeholder>) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    /// Sets JPEG encoder options such as progressive output.
+    fn with_jpeg_options(mut self, jpeg: jpeg::JpegOptions) -> Self {
+        self.jpeg = jpeg;
+        self
+    }
+
+    /// Sets the PNG compression level, row filter, interlacing and
+    /// optimization pass.
+    fn with_png_options(mut self, png: png_opt::PngOptions) -> Self {
+        self.png = png;
+        self
+    }
+
+    /// Sets the AVIF encoder speed, alpha quality, bit depth and chroma
+    /// subsampling.
+    fn with_avif_options(mut self, avif: avif::AvifOptions) -> Self {
+        self.avif = avif;
+        self
+    }
+
+    /// Converts every page of a PDF input to numbered outputs instead of
+    /// only the `--page` one.
+    fn with_all_pages(mut self, all_pages: bool) -> Self {
+        self.all_pages = all_pages;
+        self
+    }
+
+    fn process(&self, image: DynamicImage) -> DynamicImage {
+        self.pipeline.run(image)
+    }
+
+    fn decode_image(&self, input_path: &Path, data: &[u8]) -> Result<DynamicImage, ImageError> {
+        // JPEG XL, HEIC, PDF, camera RAW and SVG aren't decoded by the
+        // `image` crate, and have no EXIF orientation left to apply afterwards.
+        let (image, oriented) = if jxl::is_jxl(data) {
+            if let Some(dimensions) = jxl::dimensions(data) {
+                self.check_pixels(dimensions)?;
+            }
+            (jxl::decode(data)?, true)
+        } else if heif::is_heif(data) {
+            (heif::decode(data)?, true)
+        } else if pdf::is_pdf(data) {
+            (pdf::Document::parse(data)?.render(self.page - 1, self.raster)?, true)
+        } else if raw::is_raw(input_path) {
+            (raw::decode(data)?, true)
+        } else if svg::is_svg(input_path, data) {
+            (svg::decode(data, self.raster)?, true)
+        } else {
+            let format = if is_stdio(input_path) || remote::is_url(&input_path.to_string_lossy()) {
+                image::guess_format(data)?
+            } else {
+                ImageFormat::from_path(input_path)?
+            };
+            if let Ok(dimensions) = image::io::Reader::with_format(Cursor::new(data), format).into_dimensions() {
+                self.check_pixels(dimensions)?;
+            }
+            let image = if format == ImageFormat::Tiff && self.page > 1 {
+                tiff_page::decode(data, self.page - 1)?
+            } else if format == ImageFormat::Hdr {
+                hdr::decode_radiance(data)?
+            } else {
+                image::load_from_memory_with_format(data, format)?
+            };
+            (image, false)
+        };
+        // Formats without a cheap header probe are checked once decoded,
+        // before any processing multiplies the memory.
+        self.check_pixels((image.width(), image.height()))?;
+        self.finish_decode(input_path, data, image, oriented)
+    }
+
+    /// Fails an input of `width` by `height` pixels if that's more than
+    /// `--max-pixels`, ideally from its header before the pixels are
+    /// decoded, so a decompression bomb fails its own input rather than
+    /// running the process out of memory.
+    fn check_pixels(&self, (width, height): (u32, u32)) -> Result<(), ImageError> {
+        let pixels = u64::from(width) * u64::from(height);
+        match self.max_pixels {
+            Some(max_pixels) if pixels > max_pixels => Err(ImageError::IoError(std::io::Error::other(format!(
+                "image is {}x{} ({} pixels), over the --max-pixels limit of {}",
+                width, height, pixels, max_pixels
+            )))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`check_pixels`](Self::check_pixels) for an animation, whose
+    /// frames are all decoded at once: the canvas times the frame count, both
+    /// read from the container, must be within `--max-pixels`.
+    fn check_animation_pixels(&self, data: &[u8]) -> Result<(), ImageError> {
+        let Ok((width, height)) = image::io::Reader::new(Cursor::new(data)).with_guessed_format()?.into_dimensions() else {
+            return Ok(());
+        };
+        self.check_pixels((width, height))?;
+        let frames = animation::frame_count(data).unwrap_or(1) as u64;
+        let pixels = u64::from(width) * u64::from(height) * frames;
+        match self.max_pixels {
+            Some(max_pixels) if pixels > max_pixels => Err(ImageError::IoError(std::io::Error::other(format!(
+                "animation is {}x{} with {} frames ({} pixels), over the --max-pixels limit of {}",
+                width, height, frames, pixels, max_pixels
+            )))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The steps every decoded image goes through: tone mapping, EXIF
+    /// orientation (unless the decoder already applied it) and, with
+    /// `--to-srgb`, ICC conversion.
+    fn finish_decode(
+        &self,
+        input_path: &Path,
+        data: &[u8],
+        image: DynamicImage,
+        oriented: bool,
+    ) -> Result<DynamicImage, ImageError> {
+        // Float input is brought into display range before anything else, so
+        // the pipeline and encoders only see ordinary pixels.
+        let image = if hdr::is_hdr(&image) { hdr::apply(image, self.tone_map) } else { image };
+
+        // The pixels are rotated upright here; any EXIF copied to the output
+        // gets its orientation reset to match.
+        let image = match metadata::read_orientation(data) {
+            Some(orientation) if self.auto_orient && !oriented => metadata::apply_orientation(image, orientation),
+            _ => image,
+        };
+
+        if self.to_srgb {
+            if let Some(icc) = Metadata::extract(data).icc {
+                return color::to_srgb(image.clone(), &icc).or_else(|e| {
+                    eprintln!("Warning: {}: {}; colors left unconverted", input_path.display(), e);
+                    Ok(image)
+                });
+            }
         }
+        Ok(image)
     }
 
-    fn load_image(&self, input_path: &Path) -> Result<DynamicImage, ImageError> {
-        let file = File::open(input_path)?;
-        let reader = BufReader::new(file);
-        image::load(reader, ImageFormat::from_path(input_path)?)
+    /// Reads the raw encoded bytes from a file, stdin or URL.
+    fn read_input(&self, input_path: &Path) -> Result<Vec<u8>, ImageError> {
+        if is_stdio(input_path) {
+            let mut data = Vec::new();
+            std::io::stdin().lock().read_to_end(&mut data)?;
+            return Ok(data);
+        }
+
+        if let Some(url) = input_path.to_str().filter(|path| remote::is_url(path)) {
+            return remote::download(url)
+                .map_err(|e| ImageError::IoError(std::io::Error::other(e)));
+        }
+
+        Ok(std::fs::read(input_path)?)
     }
 
+    /// Encodes `image` and writes it to `output_path`. `source` holds the
+    /// original encoded bytes, used when metadata is carried over.
     fn save_image(
         &self,
         image: &DynamicImage,
+        source: &[u8],
         output_path: &Path,
         format: SupportedFormat,
     ) -> Result<(), ImageError> {
-        match format {
-            SupportedFormat::Jpeg => {
-                let mut output = File::create(output_path)?;
-                image.write_to(&mut output, ImageFormat::Jpeg)?;
-            }
-            SupportedFormat::Png => {
-                image.save_with_format(output_path, ImageFormat::Png)?;
-            }
-            SupportedFormat::WebP => {
-                image.save_with_format(output_path, ImageFormat::WebP)?;
+        let image = self.flatten(image, format, output_path);
+        let encoded = if format == SupportedFormat::Jxl && self.jpeg_recompress && image::guess_format(source).ok() == Some(ImageFormat::Jpeg) {
+            // The JPEG's own metadata travels inside the recompressed file.
+            jxl::recompress_jpeg(source)?
+        } else {
+            match self.max_size {
+                Some(max_size) => budget::encode(self, &image, source, format, max_size, self.allow_downscale)?,
+                None => self.encode(&image, source, format, self.quality_for(format))?,
             }
-            SupportedFormat::Avif => {
-                image.save_with_format(output_path, ImageFormat::Avif)?;
+        };
+        Self::write_output(&encoded, output_path)?;
+
+        if let Some(verify) = &self.verify {
+            if let Err(e) = verify.check(self, &image, &encoded, output_path, format) {
+                // A bad output shouldn't be mistaken for a good one later.
+                if !is_stdio(output_path) {
+                    let _ = std::fs::remove_file(output_path);
+                }
+                return Err(ImageError::IoError(std::io::Error::other(format!("verification failed: {}", e))));
             }
         }
         Ok(())
     }
 
-    fn convert(
-        &self,
-        input_path: &Path,
-        output_path: &Path,
-        target_format: SupportedFormat,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Loading image: {}", input_path.display());
-        let image = self.load_image(input_path)?;
-        
-        println!("Image dimensions: {}x{}", image.width(), image.height());
-        
-        println!("Converting to {} format...", target_format.extension());
-        self.save_image(&image, output_path, target_format)?;
-        
-        println!("Conversion completed: {}", output_path.display());
-        Ok(())
+    /// Encoder quality for `format`.
+    fn quality_for(&self, format: SupportedFormat) -> u8 {
+        self.format_quality
+            .iter()
+            .rev()
+            .find(|&&(entry, _)| entry == format)
+            .map_or(self.quality, |&(_, quality)| quality)
     }
 
-    fn batch_convert(
-        &self,
-        input_dir: &Path,
-        output_dir: &Path,
-        target_format: SupportedFormat,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        if !output_dir.exists() {
-            std::fs::create_dir_all(output_dir)?;
+    /// Composites translucent images over `background` when `format` can't
+    /// store alpha. Without a background the encoder simply drops the
+    /// channel, which is warned about since hidden pixels then show through.
+    fn flatten<'a>(&self, image: &'a DynamicImage, format: SupportedFormat, output_path: &Path) -> Cow<'a, DynamicImage> {
+        if format.has_alpha() || !image.color().has_alpha() {
+            return Cow::Borrowed(image);
         }
-
-        let entries = std::fs::read_dir(input_dir)?;
-        let mut converted_count = 0;
-
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if path.is_file() {
-                if let Some(extension) = path.extension() {
-                    if let Ok(_) = SupportedFormat::from_extension(&extension.to_string_lossy()) {
-                        let file_stem = path.file_stem().unwrap().to_string_lossy();
-                        let output_filename = format!("{}.{}", file_stem, target_format.extension());
-                        let output_path = output_dir.join(output_filename);
-
-                        match self.convert(&path, &output_path, target_format) {
-                            Ok(_) => {
-                                converted_count += 1;
-                                println!("✓ Converted: {}", path.file_name().unwrap().to_string_lossy());
-                            }
-                            Err(e) => {
-                                eprintln!("✗ Failed to convert {}: {}", path.display(), e);
-                            }
-                        }
-                    }
+        match self.background {
+            Some(background) => Cow::Owned(transform::flatten(image, background)),
+            None => {
+                if image.to_rgba8().pixels().any(|pixel| pixel[3] < 255) {
+                    eprintln!(
+                        "Warning: {}: {} has no alpha channel; transparency is lost (set --background to choose a fill)",
+                        output_path.display(),
+                        format.extension().to_uppercase(),
+                    );
                 }
+                Cow::Borrowed(image)
             }
         }
-
-        println!("\nBatch conversion completed! {} files converted.", converted_count);
-        Ok(())
     }
-}
 
-fn print_usage() {
-    println!("Image Format Converter");
-    println!("Supports: JPG/JPEG, PNG, WebP, AVIF");
-    println!();
-    println!("Usage:");
-    println!("  Single file: {} <input_file> <output_file>", env::args().next().unwrap());
-    println!("  Batch mode:  {} --batch <input_dir> <output_dir> <format>", env::args().next().unwrap());
-    println!();
-    println!("Examples:");
-    println!("  {} image.png image.webp", env::args().next().unwrap());
-    println!("  {} input.jpg output.avif", env::args().next().unwrap());
-    println!("  {} --batch ./input ./output webp", env::args().next().unwrap());
-    println!();
-    println!("Supported formats: jpg, jpeg, png, webp, avif");
-}
+    /// Encodes `image` at `quality`, then strips or carries over metadata
+    /// from `source`.
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        source: &[u8],
+        format: SupportedFormat,
+        quality: u8,
+    ) -> Result<Vec<u8>, ImageError> {
+        let image = match self.bit_depth {
+            Some(8) => to_8bit(image),
+            Some(16) => to_16bit(image),
+            _ => Cow::Borrowed(image),
+        };
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    
-    if args.len() < 3 {
-        print_usage();
-        std::process::exit(1);
+        // Encoders need `Seek`, which a pipe can't offer, so encode in memory.
+        let mut buffer = Cursor::new(Vec::new());
+        self.write_image(&image, &mut buffer, format, quality)?;
+        let encoded = buffer.into_inner();
+
+        Ok(if self.strip_metadata {
+            metadata::strip(encoded)
+        } else {
+            self.copy_metadata(source, encoded, format, image.color().has_color())
+        })
     }
 
-    let converter = ImageConverter::new(85); // Default quality
+    /// Writes encoded bytes to `output_path`, or stdout for `-`. Files are
+    /// replaced atomically, so an interrupted run never leaves a truncated
+    /// output behind.
+    fn write_output(encoded: &[u8], output_path: &Path) -> Result<(), ImageError> {
+        if is_stdio(output_path) {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(encoded)?;
+            stdout.flush()?;
+        } else {
+            atomic::write(output_path, encoded)?;
+        }
+        Ok(())
+    }
 
-    if args[1] == "--batch" {
-        // Batch mode
-        if args.len() != 5 {
-            eprintln!("Error: Batch mode requires 4 arguments");
-            print_usage();
-            std::process::exit(1);
+    /// Handles animated GIF, WebP and APNG inputs. When the target can hold an
+    /// animation every frame is run through the pipeline and written with the
+    /// source timing and loop count, returning the frame count; otherwise
+    /// warns that only the first frame will be converted and returns `None`.
+    fn convert_animation(
+        &self,
+        input_path: &Path,
+        data: &[u8],
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Option<usize>, ImageError> {
+        if !animation::is_animated(data) {
+            return Ok(None);
+        }
+        if !self.animated {
+            return Ok(None);
+        }
+        if !animation::supports(target_format) {
+            eprintln!(
+                "Warning: {} is animated; converting the first frame only (animation is kept for GIF, WebP and PNG output)",
+                input_path.display(),
+            );
+            return Ok(None);
         }
 
-        let input_dir = Path::new(&args[2]);
-        let output_dir = Path::new(&args[3]);
-        let format_str = &args[4];
+        self.check_animation_pixels(data)?;
+        let mut animation = animation::decode(data)?;
+        animation.frames = animation
+            .frames
+            .into_iter()
+            .map(|frame| {
+                let delay = frame.delay();
+                let image = self.process(DynamicImage::ImageRgba8(frame.into_buffer()));
+                Frame::from_parts(image.to_rgba8(), 0, 0, delay)
+            })
+            .collect();
+        let encoded = animation::encode(self, &animation, target_format)?;
+        Self::write_output(&encoded, output_path)?;
 
-        let target_format = match SupportedFormat::from_extension(format_str) {
-            Ok(format) => format,
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        if let Some(verify) = &self.verify {
+            let canvas = animation.frames[0].buffer().dimensions();
+            if let Err(e) = verify.check_animation(&encoded, output_path, animation.frames.len(), canvas) {
+                if !is_stdio(output_path) {
+                    let _ = std::fs::remove_file(output_path);
+                }
+                return Err(ImageError::IoError(std::io::Error::other(format!("verification failed: {}", e))));
             }
-        };
-
-        if !input_dir.exists() || !input_dir.is_dir() {
-            eprintln!("Error: Input directory does not exist or is not a directory");
-            std::process::exit(1);
         }
+        Ok(Some(animation.frames.len()))
+    }
 
-        if let Err(e) = converter.batch_convert(input_dir, output_dir, target_format) {
-            eprintln!("Error during batch conversion: {}", e);
-            std::process::exit(1);
+    /// With `--pages all`, renders every page of a PDF input through the
+    /// pipeline to numbered outputs (`doc-1.png`, `doc-2.png`, ...) and
+    /// returns the paths written; `None` for other inputs.
+    fn convert_pages(
+        &self,
+        data: &[u8],
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Option<Vec<PathBuf>>, Box<dyn std::error::Error>> {
+        if !self.all_pages || !pdf::is_pdf(data) {
+            return Ok(None);
         }
-    } else {
-        // Single file mode
-        if args.len() != 3 {
-            eprintln!("Error: Single file mode requires 2 arguments");
-            print_usage();
-            std::process::exit(1);
+        if is_stdio(output_path) {
+            return Err("--pages all writes one file per page and can't write to stdout".into());
         }
 
-        let input_path = Path::new(&args[1]);
-        let output_path = Path::new(&args[2]);
+        let document = pdf::Document::parse(data)?;
+        let count = document.page_count();
+        let mut written = Vec::with_capacity(count);
+        for index in 0..count {
+            let image = self.process(document.render(index, self.raster)?);
+            let page_path = numbered_path(output_path, index + 1, count);
+            self.save_image(&image, data, &page_path, target_format)?;
+            written.push(page_path);
+        }
+        Ok(Some(written))
+    }
 
-        if !input_path.exists() {
-            eprintln!("Error: Input file does not exist: {}", input_path.display());
-            std::process::exit(1);
+    /// Transfers the source ICC profile (unless pixels were converted to
+    /// sRGB) and, with `--keep-metadata`, EXIF and XMP into `encoded` when the
+    /// target container can hold them.
+    fn copy_metadata(
+        &self,
+        source: &[u8],
+        encoded: Vec<u8>,
+        format: SupportedFormat,
+        has_color: bool,
+    ) -> Vec<u8> {
+        let mut metadata = Metadata::extract(source);
+        if !self.keep_metadata {
+            metadata.exif = None;
+            metadata.xmp = None;
+        }
+        // An RGB profile is invalid on a grayscale output.
+        if self.to_srgb || !has_color {
+            metadata.icc = None;
+        }
+        if metadata.is_empty() {
+            return encoded;
+        }
+        if self.auto_orient {
+            metadata.reset_orientation();
         }
 
-        let target_format = match output_path.extension() {
-            Some(ext) => match SupportedFormat::from_extension(&ext.to_string_lossy()) {
-                Ok(format) => format,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            },
+        match metadata.embed(&encoded) {
+            Some(embedded) => embedded,
             None => {
-                eprintln!("Error: Output file must have a valid extension");
-                std::process::exit(1);
+                eprintln!(
+                    "Warning: {} output can't carry {}; not preserved{}",
+                    format.extension(),
+                    metadata.kinds().join("/"),
+                    if metadata.icc.is_some() { " (use --to-srgb to keep colors accurate)" } else { "" },
+                );
+                encoded
             }
-        };
-
-        if let Err(e) = converter.convert(input_path, output_path, target_format) {
-            eprintln!("Error during conversion: {}", e);
-            std::process::exit(1);
         }
     }
+
+    fn write_image<W: Write + Seek>(
+        &self,
+        image: &DynamicImage,
+        output: &mut W,
+        format: SupportedFormat,
+        quality: u8,
+    ) -> Result<(), ImageError> {
+        match format {
+            SupportedFormat::Jpeg => {
+                output.write_all(&jpeg::encode(image, quality, self.jpeg)?)?;
+            }
+            SupportedFormat::Png => match self.quantize {
+                Some(settings) => {
+                    let indexed = quantize::quantize(image, settings, false);
+                    output.write_all(&png_opt::optimize(quantize::encode_png(&indexed)?, self.png)?)?;
+                }
+                None => output.write_all(&png_opt::encode(image, self.png)?)?,
+            },
+            SupportedFormat::WebP => {
+                output.write_all(&self.encode_webp(image, quality)?)?;
+            }
+            SupportedFormat::Avif => {
+                output.write_all(&avif::encode(image, quality, self.avif)?)?;
+            }
+            SupportedFormat::Tiff => {
+                // The TIFF encoder has no gray+alpha layout.
+                match image.color() {
+                    ColorType::La8 => DynamicImage::ImageRgba8(image.to_rgba8()).write_to(output, ImageFormat::Tiff)?,
+                    ColorType::La16 => DynamicImage::ImageRgba16(image.to_rgba16()).write_to(output, ImageFormat::Tiff)?,
+                    _ => image.write_to(output, ImageFormat::Tiff)?,
+                }
+            }
+            SupportedFormat::Bmp => {
+                to_8bit(image).write_to(output, ImageFormat::Bmp)?;
+            }
+            SupportedFormat::Gif => match self.quantize {
+                Some(settings) => output.write_all(&quantize::encode_gif(&quantize::quantize(image, settings, true))?)?,
+                // The GIF encoder quantizes from RGBA only.
+                None => DynamicImage::ImageRgba8(image.to_rgba8()).write_to(output, ImageFormat::Gif)?,
+            },
+            SupportedFormat::Ico => {
+                // Single-size icon, at most 256x256; see `favicon` for sets.
+                image.write_to(output, ImageFormat::Ico)?;
+            }
+            SupportedFormat::Tga => {
+                let image = to_8bit(image);
+                let encoder = TgaEncoder::new(output);
+                let encoder = if self.tga_rle { encoder } else { encoder.disable_rle() };
+                encoder.encode(image.as_bytes(), image.width(), image.height(), image.color())?;
+            }
+            SupportedFormat::Qoi => {
+                // QOI stores 8-bit RGB or RGBA only.
+                let image = if image.color().has_alpha() {
+                    DynamicImage::ImageRgba8(image.to_rgba8())
+                } else {
+                    DynamicImage::ImageRgb8(image.to_rgb8())
+                };
+                image.write_to(output, ImageFormat::Qoi)?;
+            }
+            SupportedFormat::Jxl => {
+                output.write_all(&jxl::encode(image, quality, self.jxl_lossless)?)?;
+            }
+            // Input-only formats.
+            SupportedFormat::Heic
+            | SupportedFormat::Raw
+            | SupportedFormat::Svg
+            | SupportedFormat::Pdf
+            | SupportedFormat::Exr
+            | SupportedFormat::Hdr => {
+                let hint = ImageFormatHint::Name(format.extension().to_uppercase());
+                return Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                    hint.clone(),
+                    UnsupportedErrorKind::Format(hint),
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes WebP with libwebp: lossy at `quality` by default, or
+    /// (near-)losslessly when asked.
+    fn encode_webp(&self, image: &DynamicImage, quality: u8) -> Result<Vec<u8>, ImageError> {
+        let error = |message: String| {
+            ImageError::Encoding(image::error::EncodingError::new(ImageFormatHint::Exact(ImageFormat::WebP), message))
+        };
+
+        let mut config = webp::WebPConfig::new().map_err(|_| error("libwebp version mismatch".to_string()))?;
+        if self.webp_lossless || self.webp_near_lossless.is_some() {
+            config.lossless = 1;
+            config.near_lossless = i32::from(self.webp_near_lossless.unwrap_or(100));
+        } else {
+            config.quality = f32::from(quality);
+        }
+
+        let (width, height) = (image.width(), image.height());
+        let encoded = if image.color().has_alpha() {
+            let pixels = image.to_rgba8();
+            webp::Encoder::from_rgba(pixels.as_raw(), width, height).encode_advanced(&config)
+        } else {
+            let pixels = image.to_rgb8();
+            webp::Encoder::from_rgb(pixels.as_raw(), width, height).encode_advanced(&config)
+        };
+        encoded
+            .map(|memory| memory.to_vec())
+            .map_err(|e| error(format!("{:?}", e)))
+    }
+
+    fn convert(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        // stdout may be carrying the encoded image, so keep it clean.
+        let log = |message: String| {
+            if is_stdio(output_path) {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
+        };
+
+        let output_path = &self.claim_output(output_path, target_format)?;
+        self.check_replaces_original(input_path, output_path)?;
+        log(format!("Loading image: {}", input_path.display()));
+        let data = self.read_input(input_path)?;
+
+        if let Some(frames) = self.convert_animation(input_path, &data, output_path, target_format)? {
+            log(format!("Converted {} frames to animated {}", frames, target_format.extension()));
+            log(format!("Conversion completed: {}", output_path.display()));
+            return Ok(vec![output_path.to_path_buf()]);
+        }
+        if let Some(pages) = self.convert_pages(&data, output_path, target_format)? {
+            for page in &pages {
+                log(format!("Converted page: {}", page.display()));
+            }
+            log(format!("Conversion completed: {} pages", pages.len()));
+            return Ok(pages);
+        }
+        let image = self.decode_image(input_path, &data)?;
+        
+        log(format!("Image dimensions: {}x{}", image.width(), image.height()));
+
+        let (width, height) = (image.width(), image.height());
+        let image = self.process(image);
+        if (image.width(), image.height()) != (width, height) {
+            log(format!("Output dimensions: {}x{}", image.width(), image.height()));
+        }
+        for path in self.write_extras(&image, output_path, target_format)? {
+            log(format!("Created: {}", path.display()));
+        }
+        
+        if let Some(variants) = &self.variants {
+            let written = variants.write(self, &image, &data, output_path, target_format)?;
+            for path in &written {
+                log(format!("Created: {}", path.display()));
+            }
+            return Ok(written);
+        }
+
+        log(format!("Converting to {} format...", target_format.extension()));
+        self.save_image(&image, &data, output_path, target_format)?;
+
+        if self.strip_metadata {
+            let removed = metadata::describe(&data);
+            if removed.is_empty() {
+                log("Stripped metadata: none found".to_string());
+            } else {
+                log(format!("Stripped metadata: {}", removed.join(", ")));
+            }
+        }
+        
+        log(format!("Conversion completed: {}", output_path.display()));
+        Ok(vec![output_path.to_path_buf()])
+    }
+
+    /// Same as [`convert`](Self::convert) without the step-by-step logging,
+    /// for batch runs where progress is reported per file instead.
+    fn convert_file(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Conversion, Box<dyn std::error::Error>> {
+        let source = self.preserve.source(input_path);
+        let mut conversion = self.write_outputs(input_path, output_path, target_format)?;
+        self.preserve_metadata(source.as_ref(), &conversion.outputs)?;
+        conversion.deleted = self.delete_original(input_path, &conversion.outputs)?;
+        Ok(conversion)
+    }
+
+    /// Gives `outputs` the times and permissions in `source`, the input's
+    /// metadata from before converting, as `--preserve-times` and
+    /// `--preserve-permissions` ask.
+    fn preserve_metadata(&self, source: Option<&std::fs::Metadata>, outputs: &[PathBuf]) -> Result<(), String> {
+        match source {
+            Some(source) => self
+                .preserve
+                .apply(source, outputs)
+                .map_err(|e| format!("converted, but the input's times or permissions couldn't be copied: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// With `--delete-original`, refuses to write `output_path` over the
+    /// input it would then delete.
+    fn check_replaces_original(&self, input_path: &Path, output_path: &Path) -> Result<(), String> {
+        if self.delete_original && same_file(input_path, output_path) {
+            return Err(format!(
+                "{} would overwrite its input, which --delete-original would then delete",
+                output_path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// With `--delete-original`, deletes the local file `input_path` once
+    /// `outputs` are written (and verified, with `--verify`), unless one of
+    /// them replaced it. Returns whether it was deleted.
+    fn delete_original(&self, input_path: &Path, outputs: &[PathBuf]) -> Result<bool, String> {
+        if !self.delete_original
+            || is_stdio(input_path)
+            || remote::is_url(&input_path.to_string_lossy())
+            || outputs.iter().any(|output| output == input_path || same_file(output, input_path))
+        {
+            return Ok(false);
+        }
+        std::fs::remove_file(input_path).map_err(|e| format!("converted, but the original couldn't be deleted: {}", e))?;
+        Ok(true)
+    }
+
+    fn write_outputs(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Conversion, Box<dyn std::error::Error>> {
+        let data = self.read_input(input_path)?;
+        let conversion = Conversion::new(input_path, data.len() as u64);
+        // A name with `{width}` or `{height}` is only settled (and claimed)
+        // once the image has been processed.
+        let sized_name = self.name_template.as_ref().is_some_and(template::Template::uses_dimensions);
+        if sized_name
+            && ((self.animated && animation::is_animated(&data) && animation::supports(target_format))
+                || (self.all_pages && pdf::is_pdf(&data)))
+        {
+            return Err("{width} and {height} in --name-template aren't known for animations and --pages all".into());
+        }
+        let claimed = if sized_name { None } else { Some(self.claim_output(output_path, target_format)?) };
+        if let Some(output_path) = &claimed {
+            self.check_replaces_original(input_path, output_path)?;
+            if self.convert_animation(input_path, &data, output_path, target_format)?.is_some() {
+                return Ok(conversion.with_outputs(vec![output_path.to_path_buf()]));
+            }
+            if let Some(pages) = self.convert_pages(&data, output_path, target_format)? {
+                return Ok(conversion.with_outputs(pages));
+            }
+        }
+        let image = self.decode_image(input_path, &data)?;
+        let dimensions = Some((image.width(), image.height()));
+        let image = self.process(image);
+        let output_path = &match claimed {
+            Some(output_path) => output_path,
+            None => {
+                let output_path = template::fill_dimensions(output_path, (image.width(), image.height()));
+                if let Some(parent) = output_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let output_path = self.claim_output(&output_path, target_format)?;
+                self.check_replaces_original(input_path, &output_path)?;
+                output_path
+            }
+        };
+        self.write_extras(&image, output_path, target_format)?;
+        let outputs = match &self.variants {
+            Some(variants) => variants.write(self, &image, &data, output_path, target_format)?,
+            None => {
+                self.save_image(&image, &data, output_path, target_format)?;
+                vec![output_path.to_path_buf()]
+            }
+        };
+        Ok(Conversion {
+            dimensions,
+            output_dimensions: Some((image.width(), image.height())),
+            ..conversion.with_outputs(outputs)
+        })
+    }
+
+    /// Settles where `output_path` is written under the overwrite policy,
+    /// reserving the result (and its variants and extras) for this input.
+    fn claim_output(&self, output_path: &Path, target_format: SupportedFormat) -> Result<PathBuf, String> {
+        if self.overwrite == overwrite::Policy::Overwrite || is_stdio(output_path) {
+            return Ok(output_path.to_path_buf());
+        }
+        let mut claimed = self.claimed.lock().unwrap();
+        let taken = |candidate: &Path| {
+            self.planned_outputs(candidate, target_format)
+                .iter()
+                .any(|path| path.exists() || claimed.contains(path))
+        };
+        let output_path = self.overwrite.resolve(output_path, taken)?;
+        claimed.extend(self.planned_outputs(&output_path, target_format));
+        Ok(output_path)
+    }
+
+    /// Writes the files that accompany an output: the JSON sidecar
+    /// (`--blurhash`, `--thumbhash`, `--dominant-colors`) and the
+    /// `--placeholder` preview, as asked for.
+    fn write_extras(
+        &self,
+        image: &DynamicImage,
+        output_path: &Path,
+        format: SupportedFormat,
+    ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let mut written = Vec::new();
+        if self.analysis.is_empty() && self.placeholder.is_none() {
+            return Ok(written);
+        }
+        if is_stdio(output_path) {
+            eprintln!("Warning: no sidecar or placeholder is written when the output is stdout");
+            return Ok(written);
+        }
+        if !self.analysis.is_empty() {
+            written.push(self.analysis.write(image, output_path)?);
+        }
+        if let Some(placeholder) = &self.placeholder {
+            written.push(placeholder.write(self, image, output_path, format)?);
+        }
+        Ok(written)
+    }
+
+    fn batch_convert(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !output_dir.exists() {
+            std::fs::create_dir_all(output_dir)?;
+        }
+
+        let mut found = self.selected_inputs(input_dir)?;
+        found.sort();
+        let found_count = found.len();
+        found.retain(|path| match self.in_place_skip(path, target_format) {
+            Some(reason) => {
+                if self.json {
+                    report::emit(&report::skipped(path, reason));
+                }
+                false
+            }
+            None => true,
+        });
+        let found = self.drop_filtered(found);
+        // `{counter}` numbers inputs in file name order.
+        let numbers: HashMap<PathBuf, usize> = found.iter().cloned().zip(1..).collect();
+        let outputs = self.output_paths(&found, |path| {
+            self.mirrored_output_path(input_dir, output_dir, path, numbers[path], target_format)
+        })?;
+        let checkpoint = checkpoint::Checkpoint::open(&self.checkpoint_path(output_dir), self.resume)?;
+        let found = checkpoint.drop_completed(found, self.json);
+        let files = self.drop_up_to_date(found, |path| outputs[path].clone(), target_format);
+        let files = self.drop_duplicates(files);
+
+        let summary = self.run_batch(&files, found_count - files.len(), |path| {
+            let conversion = match &self.in_place {
+                Some(in_place) => self.convert_in_place(in_place, input_dir, path, &outputs[path], target_format)?,
+                None => self.convert_into(path, &outputs[path], target_format)?,
+            };
+            checkpoint.record(path);
+            Ok(conversion)
+        })?;
+        checkpoint.close(&summary, self.json);
+        Ok(())
+    }
+
+    /// Why an `--in-place` batch leaves `path` alone: neither backups nor
+    /// files converted by an earlier run are converted again.
+    fn in_place_skip(&self, path: &Path, target_format: SupportedFormat) -> Option<&'static str> {
+        let in_place = self.in_place.as_ref()?;
+        if in_place.backup_dir.as_ref().is_some_and(|dir| path.starts_with(dir)) {
+            return Some("backup");
+        }
+        path.extension()
+            .is_some_and(|ext| SupportedFormat::from_extension(&ext.to_string_lossy()) == Ok(target_format))
+            .then_some("already converted")
+    }
+
+    /// Batch-converts `listed`, the entries of a `--batch` list file or
+    /// `--files-from`, into `output_dir`. Entries may be local paths or
+    /// HTTP(S) URLs.
+    fn batch_convert_list(
+        &self,
+        listed: Vec<PathBuf>,
+        output_dir: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(output_dir).map_err(|e| format!("can't create {}: {}", output_dir.display(), e))?;
+
+        let listed_count = listed.len();
+        let listed = self.drop_filtered(listed);
+        let numbers: HashMap<PathBuf, usize> = listed.iter().cloned().zip(1..).collect();
+        let outputs = self.output_paths(&listed, |input| {
+            self.listed_output_path(input, output_dir, numbers[input], target_format)
+        })?;
+        let checkpoint = checkpoint::Checkpoint::open(&self.checkpoint_path(output_dir), self.resume)?;
+        let listed = checkpoint.drop_completed(listed, self.json);
+        let inputs = self.drop_up_to_date(listed, |input| outputs[input].clone(), target_format);
+        let inputs = self.drop_duplicates(inputs);
+
+        let summary = self.run_batch(&inputs, listed_count - inputs.len(), |input| {
+            let conversion = self.convert_into(input, &outputs[input], target_format)?;
+            checkpoint.record(input);
+            Ok(conversion)
+        })?;
+        checkpoint.close(&summary, self.json);
+        Ok(())
+    }
+
+    /// Where a batch writing to `output_dir` keeps its checkpoint.
+    fn checkpoint_path(&self, output_dir: &Path) -> PathBuf {
+        self.checkpoint.clone().unwrap_or_else(|| output_dir.join(checkpoint::FILE_NAME))
+    }
+
+    /// The output path of each of `inputs`, with any that several would
+    /// share settled under `--on-collision`. Names depending on the output
+    /// size can't be compared up front and are left as they are.
+    fn output_paths<F>(&self, inputs: &[PathBuf], output_path: F) -> Result<HashMap<PathBuf, PathBuf>, String>
+    where
+        F: Fn(&Path) -> PathBuf,
+    {
+        let mut outputs = inputs.iter().map(|input| (input.clone(), output_path(input))).collect();
+        if !self.name_template.as_ref().is_some_and(template::Template::uses_dimensions) {
+            self.on_collision.resolve(inputs, &mut outputs)?;
+        }
+        Ok(outputs)
+    }
+
+    /// Where a `--batch` list entry (path or URL), the `number`th, is written
+    /// in `output_dir`.
+    fn listed_output_path(&self, input: &Path, output_dir: &Path, number: usize, target_format: SupportedFormat) -> PathBuf {
+        let input_str = input.to_string_lossy();
+        // URLs aren't fetched just to be named, so they keep theirs.
+        let stem = if remote::is_url(&input_str) {
+            remote::url_stem(&input_str).unwrap_or_else(|| "image".to_string())
+        } else if let Some(name) = self.rename_by_date.as_ref().and_then(|naming| naming.name(input)) {
+            name
+        } else {
+            input.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+        };
+        output_dir.join(self.output_name(input, &stem, number, target_format))
+    }
+
+    /// Where `path`, the `number`th input found under `input_dir`, is
+    /// written under `output_dir`.
+    fn mirrored_output_path(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        path: &Path,
+        number: usize,
+        target_format: SupportedFormat,
+    ) -> PathBuf {
+        if let Some(name) = self.rename_by_date.as_ref().and_then(|naming| naming.name(path)) {
+            return output_dir.join(self.output_name(path, &name, number, target_format));
+        }
+        let relative = path.strip_prefix(input_dir).unwrap_or(path);
+        let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+        output_dir
+            .join(relative)
+            .with_file_name(self.output_name(path, &stem, number, target_format))
+    }
+
+    /// The file name `input` (with file stem `stem`) is written under: the
+    /// stem with `--suffix` and the format's extension, or whatever
+    /// `--name-template` makes of them, `{width}` and `{height}` aside.
+    fn output_name(&self, input: &Path, stem: &str, number: usize, target_format: SupportedFormat) -> String {
+        match &self.name_template {
+            Some(template) => template.render(&template::Fields {
+                input,
+                stem,
+                format: target_format,
+                quality: self.quality_for(target_format),
+                number,
+            }),
+            None => format!("{}{}.{}", stem, self.suffix, target_format.extension()),
+        }
+    }
+
+    /// Every file converting to `output_path` writes: the output itself, or
+    /// one per width and format, plus any sidecar and placeholder.
+    fn planned_outputs(&self, output_path: &Path, target_format: SupportedFormat) -> Vec<PathBuf> {
+        let mut outputs = match &self.variants {
+            Some(variants) => variants.paths(output_path, target_format),
+            None => vec![output_path.to_path_buf()],
+        };
+        if !self.analysis.is_empty() {
+            outputs.push(sidecar::Analysis::path(output_path));
+        }
+        if let Some(placeholder) = &self.placeholder {
+            outputs.push(placeholder.path(output_path));
+        }
+        outputs
+    }
+
+    /// Whether `input`'s outputs are current enough to skip it under
+    /// `--skip-existing` or `--newer-only`.
+    fn is_up_to_date(&self, input: &Path, output_path: &Path, target_format: SupportedFormat) -> bool {
+        self.incremental
+            .is_some_and(|mode| mode.is_up_to_date(input, &self.planned_outputs(output_path, target_format)))
+    }
+
+    /// Removes inputs that are already up to date, given where each one's
+    /// output goes. Only JSON mode lists them; the summary counts them.
+    fn drop_up_to_date<F>(&self, inputs: Vec<PathBuf>, output_path: F, target_format: SupportedFormat) -> Vec<PathBuf>
+    where
+        F: Fn(&Path) -> PathBuf,
+    {
+        if self.incremental.is_none() {
+            return inputs;
+        }
+        inputs
+            .into_iter()
+            .filter(|input| {
+                let up_to_date = self.is_up_to_date(input, &output_path(input), target_format);
+                if up_to_date && self.json {
+                    report::emit(&report::skipped(input, "up to date"));
+                }
+                !up_to_date
+            })
+            .collect()
+    }
+
+    /// Removes inputs outside the `--min-width`, `--min-height`,
+    /// `--min-filesize` and `--max-filesize` bounds. Only JSON mode lists
+    /// them; the summary counts them.
+    fn drop_filtered(&self, inputs: Vec<PathBuf>) -> Vec<PathBuf> {
+        if self.input_filter.is_empty() {
+            return inputs;
+        }
+        inputs
+            .into_par_iter()
+            .filter(|input| match self.input_filter.reason(input) {
+                Some(reason) => {
+                    if self.json {
+                        report::emit(&report::skipped(input, reason));
+                    }
+                    false
+                }
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Removes near-duplicates from `inputs` when `--skip-duplicates` is set.
+    fn drop_duplicates(&self, inputs: Vec<PathBuf>) -> Vec<PathBuf> {
+        let Some(settings) = self.skip_duplicates else {
+            return inputs;
+        };
+        let groups = dedupe::find_groups(self, &inputs, settings);
+        let duplicates = dedupe::duplicates(&groups);
+        for path in &duplicates {
+            if self.json {
+                report::emit(&report::skipped(path, "near-duplicate"));
+            } else {
+                println!("Skipping near-duplicate: {}", path.display());
+            }
+        }
+        inputs.iter().filter(|path| !duplicates.contains(path.as_path())).cloned().collect()
+    }
+
+    /// Runs `task` over `inputs` on the batch thread pool with progress
+    /// reporting, writes the `--report` if asked for and prints a summary
+    /// that also counts the `skipped` inputs left out beforehand.
+    fn run_batch<F>(&self, inputs: &[PathBuf], skipped: usize, task: F) -> Result<report::Summary, Box<dyn std::error::Error>>
+    where
+        F: Fn(&Path) -> Result<Conversion, Box<dyn std::error::Error>> + Sync,
+    {
+        let progress = BatchProgress::new(inputs.len(), self.json);
+        let started = Instant::now();
+        let entries = Mutex::new(Vec::with_capacity(inputs.len()));
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()?;
+        let budget = self.max_memory.map(memory::Budget::new);
+
+        pool.install(|| {
+            inputs.par_iter().enumerate().for_each(|(index, path)| {
+                let _reservation = budget.as_ref().map(|budget| budget.reserve(memory::estimate(path)));
+                // After Ctrl+C, inputs not yet started are left alone.
+                if interrupt::requested() {
+                    return;
+                }
+                progress.start(path);
+                let started = Instant::now();
+                let outcome = match task(path) {
+                    Ok(conversion) => {
+                        progress.success(path, &conversion);
+                        Ok(conversion)
+                    }
+                    Err(e) => {
+                        progress.failure(path, &e);
+                        Err(e.to_string())
+                    }
+                };
+                let entry = report::Entry {
+                    input: path.clone(),
+                    outcome,
+                    duration: started.elapsed(),
+                };
+                entries.lock().unwrap().push((index, entry));
+            });
+        });
+        progress.finish();
+
+        let mut entries = entries.into_inner().unwrap();
+        entries.sort_by_key(|&(index, _)| index);
+        let entries: Vec<report::Entry> = entries.into_iter().map(|(_, entry)| entry).collect();
+        let summary = report::Summary::new(&entries, skipped, started.elapsed());
+        if self.json {
+            report::emit(&summary.to_json());
+        } else {
+            println!("\n{}", summary.to_text());
+        }
+        if interrupt::requested() {
+            let not_started = inputs.len() - entries.len();
+            if self.json {
+                report::emit(&report::interrupted(not_started));
+            } else {
+                println!("Interrupted: {} inputs not started", not_started);
+            }
+        }
+
+        if let Some(report_path) = &self.report {
+            report::write(report_path, &entries)
+                .map_err(|e| format!("Failed to write report {}: {}", report_path.display(), e))?;
+            if !self.json {
+                println!("Report written: {}", report_path.display());
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Converts `path`, found under `root`, to `output_path` beside it, then
+    /// backs up or deletes the original as `in_place` says.
+    fn convert_in_place(
+        &self,
+        in_place: &in_place::InPlace,
+        root: &Path,
+        path: &Path,
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Conversion, Box<dyn std::error::Error>> {
+        let planned = self.planned_outputs(output_path, target_format);
+        // Only the input itself may be replaced, never an unrelated file
+        // that happens to have the output's name.
+        if self.overwrite == overwrite::Policy::Overwrite {
+            if let Some(existing) = planned.iter().find(|output| output.exists() && !same_file(output, path)) {
+                return Err(format!(
+                    "{} already exists and isn't the input, so --in-place won't replace it (use --rename-on-conflict to keep both)",
+                    existing.display()
+                )
+                .into());
+            }
+        }
+        in_place.prepare(root, path, &planned)?;
+        let mut conversion = self.convert_file(path, output_path, target_format)?;
+        conversion.deleted = in_place
+            .retire(root, path, &conversion.outputs)
+            .map_err(|e| format!("converted, but the original couldn't be removed: {}", e))?;
+        Ok(conversion)
+    }
+
+    /// Converts `path` to `output_path`, creating intermediate directories
+    /// as needed.
+    fn convert_into(
+        &self,
+        path: &Path,
+        output_path: &Path,
+        target_format: SupportedFormat,
+    ) -> Result<Conversion, Box<dyn std::error::Error>> {
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        self.convert_file(path, output_path, target_format)
+    }
+
+    /// Combines the inputs in `input` (a directory, taken in file name order,
+    /// or a list file as for `--batch`) into a single PDF, one image per page.
+    fn images_to_pdf(
+        &self,
+        input: &Path,
+        output_path: &Path,
+        layout: pdf::Layout,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let inputs = if input.is_dir() {
+            let mut files = self.selected_inputs(input)?;
+            files.sort();
+            files
+        } else {
+            read_input_list(input)?
+        };
+        if inputs.is_empty() {
+            return Err(format!("no images found in {}", input.display()).into());
+        }
+
+        let document = pdf::write_document(self, &inputs, layout)?;
+        Self::write_output(&document, output_path)?;
+        Ok(inputs.len())
+    }
+
+    /// Convertible files under `input_dir` that pass the include/exclude
+    /// globs, descending into subdirectories when recursive mode is enabled.
+    fn selected_inputs(&self, input_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let mut skipped = Vec::new();
+        let mut files = walk::files(input_dir, self.recursive, self.walk, &mut skipped)?;
+        for (path, reason) in skipped {
+            if self.json {
+                report::emit(&report::skipped(&path, reason));
+            } else if reason == walk::LOOP {
+                eprintln!("Warning: skipping {}: {}", path.display(), reason);
+            }
+        }
+        files.retain(|path| Self::is_supported(path) && self.is_selected(path.strip_prefix(input_dir).unwrap_or(path)));
+        Ok(files)
+    }
+
+    /// Applies the include/exclude globs. A pattern matches if it matches
+    /// either the path relative to the input directory or the bare file name,
+    /// so `IMG_*.jpg` works at any depth while `*/thumbs/*` can target folders.
+    fn is_selected(&self, relative: &Path) -> bool {
+        let file_name = Path::new(relative.file_name().unwrap_or_default());
+        let matches = |pattern: &Pattern| {
+            pattern.matches_path(relative) || pattern.matches_path(file_name)
+        };
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    fn is_supported(path: &Path) -> bool {
+        path.extension()
+            .map(|ext| SupportedFormat::from_extension(&ext.to_string_lossy()).is_ok())
+            .unwrap_or(false)
+    }
+}
+
+/// Reads a list file for `--batch`: one path or URL per line, skipping blank
+/// lines and `#` comments.
+fn read_input_list(list_path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    Ok(parse_input_list(&std::fs::read_to_string(list_path)?))
+}
+
+fn parse_input_list(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Reads the inputs for `--files-from` from `source`, or stdin for `-`:
+/// one per line as in a `--batch` list, or with `null_separated` split on
+/// NUL bytes as `find -print0` writes them, taken exactly as given.
+fn read_files_from(source: &Path, null_separated: bool) -> std::io::Result<Vec<PathBuf>> {
+    let data = if is_stdio(source) {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        data
+    } else {
+        std::fs::read(source)?
+    };
+    if !null_separated {
+        return Ok(parse_input_list(&String::from_utf8_lossy(&data)));
+    }
+    Ok(data
+        .split(|&byte| byte == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+/// A path from raw bytes; outside Unix, names must be UTF-8.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Narrows 16-bit and float images to 8 bits per channel, keeping alpha,
+/// for encoders (BMP, TGA) that only store 8-bit samples.
+fn to_8bit(image: &DynamicImage) -> Cow<'_, DynamicImage> {
+    match image.color() {
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => Cow::Borrowed(image),
+        color if color.has_alpha() => Cow::Owned(DynamicImage::ImageRgba8(image.to_rgba8())),
+        _ => Cow::Owned(DynamicImage::ImageRgb8(image.to_rgb8())),
+    }
+}
+
+/// Widens 8-bit and float images to 16 bits per channel, keeping alpha. PNG,
+/// TIFF and JPEG XL store the extra depth; other encoders narrow it again.
+fn to_16bit(image: &DynamicImage) -> Cow<'_, DynamicImage> {
+    match image.color() {
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => Cow::Borrowed(image),
+        ColorType::L8 => Cow::Owned(DynamicImage::ImageLuma16(image.to_luma16())),
+        ColorType::La8 => Cow::Owned(DynamicImage::ImageLumaA16(image.to_luma_alpha16())),
+        color if color.has_alpha() => Cow::Owned(DynamicImage::ImageRgba16(image.to_rgba16())),
+        _ => Cow::Owned(DynamicImage::ImageRgb16(image.to_rgb16())),
+    }
+}
+
+/// `out.png` becomes `out-3.png` for page 3, zero-padded to the width of
+/// `count` so the files sort in page order.
+fn numbered_path(path: &Path, number: usize, count: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let width = count.to_string().len();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{:0width$}.{}", stem, number, ext.to_string_lossy(), width = width),
+        None => format!("{}-{:0width$}", stem, number, width = width),
+    };
+    path.with_file_name(name)
+}
+
+/// `-` stands for stdin when used as the input and stdout as the output.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Whether `a` and `b` are the same existing file, however each is spelled
+/// (`a.png` and `./a.png`, or through a symlink or hard link).
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Options that may appear anywhere on the command line.
+struct CliOptions {
+    jobs: usize,
+    max_memory: Option<u64>,
+    recursive: bool,
+    walk: walk::Options,
+    files_from: Option<PathBuf>,
+    null_separated: bool,
+    out_dir: Option<PathBuf>,
+    input_filter: input_filter::InputFilter,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    to: Option<SupportedFormat>,
+    /// Output format when nothing on the command line names one.
+    default_format: Option<SupportedFormat>,
+    preset: Option<preset::Preset>,
+    rotate: Option<Rotation>,
+    flips: Vec<Flip>,
+    deskew: bool,
+    deskew_color: image::Rgba<u8>,
+    trim: Option<Trim>,
+    crop: Option<Crop>,
+    smart_crop: Option<AspectRatio>,
+    resize: Option<ResizeSpec>,
+    resize_mode: ResizeMode,
+    shrink_only: bool,
+    white_balance: WhiteBalance,
+    levels: Option<Levels>,
+    level_clip: f32,
+    equalize: Option<Equalize>,
+    tone: Tone,
+    curves: Curves,
+    hue: Hue,
+    lut: Option<Arc<lut::Lut>>,
+    effects: Vec<Effect>,
+    denoise: Option<Denoise>,
+    blur: Option<Blur>,
+    sharpen: Option<Sharpen>,
+    pad: Option<AspectRatio>,
+    pad_color: image::Rgba<u8>,
+    grayscale: bool,
+    watermark: Option<Arc<image::RgbaImage>>,
+    position: Anchor,
+    opacity: f32,
+    margin: u32,
+    watermark_scale: f32,
+    text: Option<String>,
+    font: Option<ab_glyph::FontArc>,
+    text_size: f32,
+    text_position: Anchor,
+    text_color: image::Rgba<u8>,
+    text_outline: image::Rgba<u8>,
+    text_outline_width: u32,
+    ops: Vec<Transform>,
+    no_auto_orient: bool,
+    keep_metadata: bool,
+    strip: bool,
+    /// `--to-srgb` or `--no-to-srgb`; unset lets a preset decide.
+    to_srgb: Option<bool>,
+    page: usize,
+    first_frame: bool,
+    no_rle: bool,
+    jxl_lossless: bool,
+    webp_lossless: bool,
+    webp_near_lossless: Option<u8>,
+    jpeg_recompress: bool,
+    raster: Raster,
+    /// `--dpi`, kept apart from `raster` so a preset can tell it was given.
+    dpi: Option<f32>,
+    tone_map: hdr::ToneMap,
+    all_pages: bool,
+    page_size: pdf::PageSize,
+    page_margin: f32,
+    frames: frames::Selection,
+    timing: frames::Timing,
+    thumb_size: u32,
+    json: bool,
+    min_psnr: Option<f64>,
+    min_ssim: Option<f64>,
+    perceptual: bool,
+    diff_output: Option<PathBuf>,
+    diff_threshold: u8,
+    dedupe: dedupe::Settings,
+    skip_duplicates: bool,
+    blurhash: bool,
+    blurhash_components: (u32, u32),
+    thumbhash: bool,
+    dominant_colors: Option<usize>,
+    placeholder: bool,
+    placeholder_options: placeholder::Placeholder,
+    sheet: contact_sheet::Layout,
+    sprites: spritesheet::Options,
+    tiles: tiles::Options,
+    widths: Vec<u32>,
+    width_suffix: String,
+    formats: Vec<SupportedFormat>,
+    quality: Option<u8>,
+    format_quality: Vec<(SupportedFormat, u8)>,
+    suffix: String,
+    name_template: Option<template::Template>,
+    rename_by_date: Option<dated::DateNaming>,
+    on_collision: collision::Strategy,
+    report: Option<PathBuf>,
+    dry_run: bool,
+    deterministic: bool,
+    in_place: bool,
+    backup_dir: Option<PathBuf>,
+    delete_original: bool,
+    preserve: preserve::Preserve,
+    checkpoint: Option<PathBuf>,
+    resume: bool,
+    incremental: Option<incremental::Mode>,
+    overwrite: overwrite::Policy,
+    verify: Option<verify::Verify>,
+    max_size: Option<u64>,
+    max_pixels: Option<u64>,
+    allow_downscale: bool,
+    bit_depth: Option<u8>,
+    background: Option<image::Rgba<u8>>,
+    colors: Option<u16>,
+    quantizer: quantize::Method,
+    dither: bool,
+    jpeg: jpeg::JpegOptions,
+    png: png_opt::PngOptions,
+    avif: avif::AvifOptions,
+}
+
+impl Default for CliOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 0,
+            max_memory: None,
+            recursive: false,
+            walk: walk::Options::default(),
+            files_from: None,
+            null_separated: false,
+            out_dir: None,
+            input_filter: input_filter::InputFilter::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            to: None,
+            default_format: None,
+            preset: None,
+            rotate: None,
+            flips: Vec::new(),
+            deskew: false,
+            deskew_color: image::Rgba([255, 255, 255, 255]),
+            trim: None,
+            crop: None,
+            smart_crop: None,
+            resize: None,
+            resize_mode: ResizeMode::default(),
+            shrink_only: false,
+            white_balance: WhiteBalance::default(),
+            levels: None,
+            level_clip: Levels::DEFAULT_CLIP,
+            equalize: None,
+            tone: Tone::default(),
+            curves: Curves::default(),
+            hue: Hue::default(),
+            lut: None,
+            effects: Vec::new(),
+            denoise: None,
+            blur: None,
+            sharpen: None,
+            pad: None,
+            pad_color: image::Rgba([0, 0, 0, 255]),
+            grayscale: false,
+            watermark: None,
+            position: Anchor::default(),
+            opacity: 1.0,
+            margin: 20,
+            watermark_scale: 0.2,
+            text: None,
+            font: None,
+            text_size: 24.0,
+            text_position: Anchor::BottomLeft,
+            text_color: image::Rgba([255, 255, 255, 255]),
+            text_outline: image::Rgba([0, 0, 0, 255]),
+            text_outline_width: 0,
+            ops: Vec::new(),
+            no_auto_orient: false,
+            keep_metadata: false,
+            strip: false,
+            to_srgb: None,
+            page: 1,
+            first_frame: false,
+            no_rle: false,
+            jxl_lossless: false,
+            webp_lossless: false,
+            webp_near_lossless: None,
+            jpeg_recompress: false,
+            raster: Raster::default(),
+            dpi: None,
+            tone_map: hdr::ToneMap::default(),
+            all_pages: false,
+            page_size: pdf::PageSize::parse("a4").unwrap(),
+            page_margin: 0.0,
+            frames: frames::Selection::default(),
+            timing: frames::Timing::default(),
+            thumb_size: thumbnail::DEFAULT_SIZE,
+            json: false,
+            min_psnr: None,
+            min_ssim: None,
+            perceptual: false,
+            diff_output: None,
+            diff_threshold: 0,
+            dedupe: dedupe::Settings::default(),
+            skip_duplicates: false,
+            blurhash: false,
+            blurhash_components: (4, 3),
+            thumbhash: false,
+            dominant_colors: None,
+            placeholder: false,
+            placeholder_options: placeholder::Placeholder::default(),
+            sheet: contact_sheet::Layout::default(),
+            sprites: spritesheet::Options::default(),
+            tiles: tiles::Options::default(),
+            widths: Vec::new(),
+            width_suffix: responsive::DEFAULT_SUFFIX.to_string(),
+            formats: Vec::new(),
+            quality: None,
+            format_quality: Vec::new(),
+            suffix: String::new(),
+            name_template: None,
+            rename_by_date: None,
+            on_collision: collision::Strategy::Error,
+            report: None,
+            dry_run: false,
+            deterministic: false,
+            in_place: false,
+            backup_dir: None,
+            delete_original: false,
+            preserve: preserve::Preserve::default(),
+            checkpoint: None,
+            resume: false,
+            incremental: None,
+            overwrite: overwrite::Policy::default(),
+            verify: None,
+            max_size: None,
+            max_pixels: Some(DEFAULT_MAX_PIXELS),
+            allow_downscale: false,
+            bit_depth: None,
+            background: None,
+            colors: None,
+            quantizer: quantize::Method::MedianCut,
+            dither: false,
+            jpeg: jpeg::JpegOptions::default(),
+            png: png_opt::PngOptions::default(),
+            avif: avif::AvifOptions::default(),
+        }
+    }
+}
+
+impl CliOptions {
+    /// Transforms requested on the command line: the `--ops` list in its
+    /// own order, then the individual flags in pipeline order.
+    fn transforms(&self) -> Vec<Transform> {
+        let mut transforms = self.ops.clone();
+        if self.deskew {
+            transforms.push(Transform::Deskew(deskew::Deskew { color: self.deskew_color }));
+        }
+        if let Some(trim) = self.trim {
+            transforms.push(Transform::Trim(trim));
+        }
+        if let Some(rotation) = self.rotate {
+            transforms.push(Transform::Rotate(rotation));
+        }
+        transforms.extend(self.flips.iter().copied().map(Transform::Flip));
+        if let Some(crop) = self.crop {
+            transforms.push(Transform::Crop(crop));
+        }
+        if let Some(ratio) = self.smart_crop {
+            transforms.push(Transform::SmartCrop(SmartCrop { ratio }));
+        }
+        if let Some(denoise) = self.denoise {
+            transforms.push(Transform::Denoise(denoise));
+        }
+        if let Some(spec) = self.resize {
+            transforms.push(Transform::Resize(Resize {
+                spec,
+                mode: self.resize_mode,
+                shrink_only: self.shrink_only,
+            }));
+        }
+        if !self.white_balance.is_identity() {
+            transforms.push(Transform::WhiteBalance(self.white_balance));
+        }
+        if let Some(levels) = self.levels {
+            transforms.push(Transform::Levels(Levels {
+                clip: self.level_clip,
+                ..levels
+            }));
+        }
+        if let Some(equalize) = self.equalize {
+            transforms.push(Transform::Equalize(equalize));
+        }
+        if !self.tone.is_identity() {
+            transforms.push(Transform::Tone(self.tone));
+        }
+        if !self.curves.is_identity() {
+            transforms.push(Transform::Curves(self.curves.clone()));
+        }
+        if !self.hue.is_identity() {
+            transforms.push(Transform::Hue(self.hue));
+        }
+        if let Some(lut) = &self.lut {
+            transforms.push(Transform::Lut(Arc::clone(lut)));
+        }
+        if let Some(blur) = self.blur {
+            transforms.push(Transform::Blur(blur));
+        }
+        if let Some(sharpen) = self.sharpen {
+            transforms.push(Transform::Sharpen(sharpen));
+        }
+        if let Some(ratio) = self.pad {
+            transforms.push(Transform::Pad(Pad {
+                ratio,
+                color: self.pad_color,
+            }));
+        }
+        if self.grayscale {
+            transforms.push(Transform::Grayscale);
+        }
+        // After grayscale, so sepia and duotone still tint.
+        transforms.extend(self.effects.iter().copied().map(Transform::Effect));
+        if let Some(logo) = &self.watermark {
+            transforms.push(Transform::Watermark(Watermark {
+                logo: Arc::clone(logo),
+                anchor: self.position,
+                opacity: self.opacity,
+                margin: self.margin,
+                scale: self.watermark_scale,
+            }));
+        }
+        if let (Some(text), Some(font)) = (&self.text, &self.font) {
+            transforms.push(Transform::Text(Caption {
+                text: text.clone(),
+                font: font.clone(),
+                size: self.text_size,
+                anchor: self.text_position,
+                margin: self.margin,
+                color: self.text_color,
+                outline: self.text_outline,
+                outline_width: self.text_outline_width,
+            }));
+        }
+        transforms
+    }
+}
+
+/// Splits recognised `--flag value` options out of `args`, returning the
+/// remaining arguments (program name, `--batch` and positionals) in order.
+fn parse_options(args: Vec<String>) -> Result<(Vec<String>, CliOptions), String> {
+    let (args, recipe_flags) = expand_recipes(args)?;
+    let mut options = CliOptions::default();
+    let mut rest = Vec::new();
+    let mut iter = args.into_iter();
+    // Parsed after the loop, so the settings it reads (--fill, --shrink-only,
+    // --pad-color) may come later on the command line.
+    let mut ops = None;
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--max-memory" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.max_memory = Some(parse_byte_size(&value).ok_or_else(|| {
+                    format!("Invalid value for {}: {} (expected e.g. 2G or 512MB)", arg, value)
+                })?);
+            }
+            "--jobs" | "-j" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.jobs = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--recursive" | "-r" => options.recursive = true,
+            "--follow-symlinks" => options.walk.symlinks = walk::Symlinks::Follow,
+            "--no-follow-symlinks" => options.walk.symlinks = walk::Symlinks::Skip,
+            "--files-from" => options.files_from = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "-0" | "--null" => options.null_separated = true,
+            "--out-dir" => options.out_dir = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--min-width" | "--min-height" => {
+                let value = next_value(&mut iter, &arg)?;
+                let pixels = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {} (expected pixels, e.g. 64)", arg, value))?;
+                if arg == "--min-width" {
+                    options.input_filter.min_width = Some(pixels);
+                } else {
+                    options.input_filter.min_height = Some(pixels);
+                }
+            }
+            "--min-filesize" | "--max-filesize" => {
+                let value = next_value(&mut iter, &arg)?;
+                let bytes = parse_byte_size(&value)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected e.g. 10KB or 50MB)", arg, value))?;
+                if arg == "--min-filesize" {
+                    options.input_filter.min_filesize = Some(bytes);
+                } else {
+                    options.input_filter.max_filesize = Some(bytes);
+                }
+            }
+            "--include-hidden" => options.walk.include_hidden = true,
+            "--max-depth" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.walk.max_depth = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for {}: {} (expected a number of levels, e.g. 2)", arg, value))?,
+                );
+                options.recursive = true;
+            }
+            "--no-auto-orient" => options.no_auto_orient = true,
+            "--keep-metadata" => options.keep_metadata = true,
+            "--strip" => options.strip = true,
+            "--to-srgb" => options.to_srgb = Some(true),
+            "--no-to-srgb" => options.to_srgb = Some(false),
+            "--first-frame" => options.first_frame = true,
+            // Animation is kept by default; still accepted for old scripts.
+            "--animated" => {}
+            "--no-rle" => options.no_rle = true,
+            "--jxl-lossless" => options.jxl_lossless = true,
+            "--webp-lossless" => options.webp_lossless = true,
+            "--webp-near-lossless" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.webp_near_lossless = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|level| *level <= 100)
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 0-100)", arg, value))?,
+                );
+            }
+            "--jpeg-recompress" => options.jpeg_recompress = true,
+            "--dpi" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.dpi = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|dpi: &f32| *dpi > 0.0)
+                        .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?,
+                );
+            }
+            "--tonemap" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tone_map.operator = hdr::Operator::parse(&value)?;
+            }
+            "--exposure" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tone_map.exposure = value
+                    .parse()
+                    .ok()
+                    .filter(|stops: &f32| stops.is_finite())
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected stops, e.g. -1.5)", arg, value))?;
+            }
+            "--raster-size" => {
+                let (width, height) = Raster::parse_size(&next_value(&mut iter, &arg)?)?;
+                options.raster.width = width;
+                options.raster.height = height;
+            }
+            "--page" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.page = value
+                    .parse()
+                    .ok()
+                    .filter(|page| *page > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (pages start at 1)", arg, value))?;
+            }
+            "--pages" => {
+                let value = next_value(&mut iter, &arg)?;
+                if value != "all" {
+                    return Err(format!("Invalid value for {}: {} (use `all`, or --page N for a single page)", arg, value));
+                }
+                options.all_pages = true;
+            }
+            "--rotate" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.rotate = Some(Rotation::parse(&value)?);
+            }
+            "--flip" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.flips.push(Flip::parse(&value)?);
+            }
+            "--ops" => ops = Some(next_value(&mut iter, &arg)?),
+            "--deskew" => options.deskew = true,
+            "--deskew-color" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.deskew_color = parse_color(&value)?;
+                options.deskew = true;
+            }
+            "--trim" => {
+                options.trim.get_or_insert(Trim { tolerance: 0 });
+            }
+            "--trim-tolerance" => {
+                let value = next_value(&mut iter, &arg)?;
+                let tolerance = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {} (expected 0-255)", arg, value))?;
+                options.trim = Some(Trim { tolerance });
+            }
+            "--crop" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.crop = Some(Crop::parse(&value)?);
+            }
+            "--crop-center" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.crop = Some(Crop::parse_center(&value)?);
+            }
+            "--smart-crop" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.smart_crop = Some(AspectRatio::parse(&value)?);
+            }
+            "--resize" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.resize = Some(ResizeSpec::parse(&value)?);
+            }
+            "--fit" => options.resize_mode = ResizeMode::Fit,
+            "--fill" => options.resize_mode = ResizeMode::Fill,
+            "--exact" => options.resize_mode = ResizeMode::Exact,
+            "--shrink-only" => options.shrink_only = true,
+            "--white-balance" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.white_balance.auto = Some(Illuminant::parse(&value)?);
+            }
+            "--temperature" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.white_balance.temperature = parse_percent_adjustment(&arg, &value)?;
+            }
+            "--tint" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.white_balance.tint = parse_percent_adjustment(&arg, &value)?;
+            }
+            "--auto-level" => {
+                options.levels = Some(Levels {
+                    per_channel: true,
+                    clip: options.level_clip,
+                })
+            }
+            "--auto-contrast" => {
+                options.levels = Some(Levels {
+                    per_channel: false,
+                    clip: options.level_clip,
+                })
+            }
+            "--level-clip" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.level_clip = value
+                    .parse()
+                    .ok()
+                    .filter(|clip| (0.0..50.0).contains(clip))
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected a percentage below 50)", arg, value))?;
+            }
+            "--equalize" => options.equalize = Some(Equalize::Global),
+            "--clahe" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.equalize = Some(Equalize::parse_clahe(&value)?);
+            }
+            "--brightness" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tone.brightness = parse_percent_adjustment(&arg, &value)?;
+            }
+            "--contrast" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tone.contrast = parse_percent_adjustment(&arg, &value)?;
+            }
+            "--gamma" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tone.gamma = value
+                    .parse()
+                    .ok()
+                    .filter(|gamma| (0.1..=10.0).contains(gamma))
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected 0.1-10)", arg, value))?;
+            }
+            "--curve" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.curves.add(&value)?;
+            }
+            "--saturation" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.hue.saturation = parse_percent_adjustment(&arg, &value)?;
+            }
+            "--hue-rotate" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.hue.rotate = value
+                    .parse()
+                    .ok()
+                    .filter(|degrees: &f32| degrees.is_finite())
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected degrees)", arg, value))?;
+            }
+            "--vibrance" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.hue.vibrance = parse_percent_adjustment(&arg, &value)?;
+            }
+            "--lut" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.lut = Some(Arc::new(lut::Lut::load(Path::new(&value))?));
+            }
+            "--filter" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.effects.push(Effect::parse(&value)?);
+            }
+            "--denoise" => {
+                let value = next_value(&mut iter, &arg)?;
+                let strength = value
+                    .parse()
+                    .ok()
+                    .filter(|strength| (0.0..=100.0).contains(strength))
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected 0-100)", arg, value))?;
+                options.denoise = Some(Denoise { strength });
+            }
+            "--blur" => {
+                let value = next_value(&mut iter, &arg)?;
+                let sigma = value
+                    .parse()
+                    .ok()
+                    .filter(|&sigma: &f32| sigma > 0.0 && sigma <= 1000.0)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected a sigma in pixels)", arg, value))?;
+                options.blur = Some(Blur { sigma });
+            }
+            "--sharpen" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.sharpen = Some(Sharpen::parse(&value)?);
+            }
+            "--pad" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.pad = Some(AspectRatio::parse(&value)?);
+            }
+            "--pad-color" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.pad_color = parse_color(&value)?;
+            }
+            "--grayscale" => options.grayscale = true,
+            "--watermark" => {
+                let value = next_value(&mut iter, &arg)?;
+                let logo = image::open(&value)
+                    .map_err(|e| format!("Failed to load watermark {}: {}", value, e))?;
+                options.watermark = Some(Arc::new(logo.into_rgba8()));
+            }
+            "--position" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.position = Anchor::parse(&value)?;
+            }
+            "--opacity" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.opacity = parse_fraction(&arg, &value)?;
+            }
+            "--margin" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.margin = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--text" => options.text = Some(next_value(&mut iter, &arg)?),
+            "--font" => {
+                let value = next_value(&mut iter, &arg)?;
+                let data = std::fs::read(&value)
+                    .map_err(|e| format!("Failed to read font {}: {}", value, e))?;
+                let font = ab_glyph::FontArc::try_from_vec(data)
+                    .map_err(|e| format!("Failed to load font {}: {}", value, e))?;
+                options.font = Some(font);
+            }
+            "--text-size" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.text_size = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|size| *size > 0.0 && size.is_finite())
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--text-pos" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.text_position = Anchor::parse(&value)?;
+            }
+            "--background" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.background = Some(parse_color(&value)?);
+            }
+            "--text-color" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.text_color = parse_color(&value)?;
+            }
+            "--text-outline" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.text_outline = parse_color(&value)?;
+                if options.text_outline_width == 0 {
+                    options.text_outline_width = 2;
+                }
+            }
+            "--text-outline-width" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.text_outline_width = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--watermark-scale" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.watermark_scale = parse_fraction(&arg, &value)?;
+                if options.watermark_scale == 0.0 {
+                    return Err(format!("Invalid value for {}: {}", arg, value));
+                }
+            }
+            "--page-size" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.page_size = pdf::PageSize::parse(&value)?;
+            }
+            "--page-margin" => {
+                let value = next_value(&mut iter, &arg)?;
+                let millimetres = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|margin| *margin >= 0.0 && margin.is_finite())
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+                options.page_margin = millimetres * 72.0 / 25.4;
+            }
+            "--every" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.frames.every = value
+                    .parse()
+                    .ok()
+                    .filter(|&every| every > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--range" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.frames.parse_range(&value)?;
+            }
+            "--fps" | "--delay" => {
+                let value = next_value(&mut iter, &arg)?;
+                let number = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|number| *number > 0.0 && number.is_finite())
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+                options.timing.delay_ms = if arg == "--fps" { 1000.0 / number } else { number };
+            }
+            "--loop" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.timing.plays = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--widths" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.widths = responsive::Variants::parse_widths(&value)?;
+            }
+            "--width-suffix" => {
+                let value = next_value(&mut iter, &arg)?;
+                if !value.contains("{w}") {
+                    return Err(format!("Invalid value for {}: {} (must contain {{w}})", arg, value));
+                }
+                options.width_suffix = value;
+            }
+            "--formats" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.formats = responsive::Variants::parse_formats(&value)?;
+            }
+            "--columns" | "--rows" | "--cell-size" => {
+                let value = next_value(&mut iter, &arg)?;
+                let number = value
+                    .parse()
+                    .ok()
+                    .filter(|&number| number > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+                match arg.as_str() {
+                    "--columns" => options.sheet.columns = number,
+                    "--rows" => options.sheet.rows = number,
+                    _ => options.sheet.cell = number,
+                }
+            }
+            "--packing" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.sprites.packing = spritesheet::Packing::parse(&value)?;
+            }
+            "--padding" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.sprites.padding = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--map" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.sprites.map = spritesheet::MapFormat::parse(&value)?;
+            }
+            "--layout" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tiles.layout = tiles::Layout::parse(&value)?;
+            }
+            "--tile-size" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tiles.tile_size = value
+                    .parse()
+                    .ok()
+                    .filter(|&size| size > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--overlap" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.tiles.overlap = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--json" => options.json = true,
+            "--min-psnr" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.min_psnr = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|psnr: &f64| psnr.is_finite())
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected dB, e.g. 40)", arg, value))?,
+                );
+            }
+            "--min-ssim" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.min_ssim = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|ssim: &f64| (0.0..=1.0).contains(ssim))
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 0.0-1.0)", arg, value))?,
+                );
+            }
+            "--perceptual" => options.perceptual = true,
+            "--hash" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.dedupe.hash = dedupe::HashKind::parse(&value)?;
+            }
+            "--distance" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.dedupe.distance = value
+                    .parse()
+                    .ok()
+                    .filter(|&distance| distance <= 64)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected 0-64)", arg, value))?;
+            }
+            "--skip-duplicates" => options.skip_duplicates = true,
+            "--blurhash" => options.blurhash = true,
+            "--blurhash-components" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.blurhash_components = sidecar::Analysis::parse_components(&value)?;
+                options.blurhash = true;
+            }
+            "--thumbhash" => options.thumbhash = true,
+            "--dominant-colors" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.dominant_colors = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|count| (1..=64).contains(count))
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 1-64)", arg, value))?,
+                );
+            }
+            "--placeholder" => options.placeholder = true,
+            "--placeholder-width" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.placeholder_options.width = value
+                    .parse()
+                    .ok()
+                    .filter(|&width| width > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected a width in pixels)", arg, value))?;
+                options.placeholder = true;
+            }
+            "--placeholder-suffix" => {
+                options.placeholder_options.suffix = next_value(&mut iter, &arg)?;
+                options.placeholder = true;
+            }
+            "--placeholder-blur" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.placeholder_options.blur = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&sigma: &f32| sigma > 0.0)
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected a positive sigma)", arg, value))?,
+                );
+                options.placeholder = true;
+            }
+            "--diff-output" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.diff_output = Some(PathBuf::from(value));
+            }
+            "--diff-threshold" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.diff_threshold = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {} (expected 0-255)", arg, value))?;
+            }
+            "--thumb-size" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.thumb_size = value
+                    .parse()
+                    .ok()
+                    .filter(|&size| size > 0)
+                    .ok_or_else(|| format!("Invalid value for {}: {}", arg, value))?;
+            }
+            "--max-size" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.max_size = Some(parse_byte_size(&value).ok_or_else(|| {
+                    format!("Invalid value for {}: {} (expected e.g. 200KB or 1.5MB)", arg, value)
+                })?);
+            }
+            "--allow-downscale" => options.allow_downscale = true,
+            "--max-pixels" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.max_pixels = parse_pixel_count(&value)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected e.g. 100MP, 250000000 or none)", arg, value))?;
+            }
+            "--colors" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.colors = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|colors| (2..=256).contains(colors))
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 2-256)", arg, value))?,
+                );
+            }
+            "--quantizer" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.quantizer = quantize::Method::parse(&value)?;
+            }
+            "--dither" => options.dither = true,
+            "--bit-depth" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.bit_depth = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|depth| matches!(depth, 8 | 16))
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 8 or 16)", arg, value))?,
+                );
+            }
+            "--png-compression" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.png.compression = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|level| *level <= 9)
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 0-9)", arg, value))?,
+                );
+            }
+            "--png-filter" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.png.filter = Some(png_opt::Filter::parse(&value)?);
+            }
+            "--png-optimize" => options.png.optimize = true,
+            "--interlace" => options.png.interlace = true,
+            "--oxipng" => options.png.oxipng = true,
+            "--progressive" => options.jpeg.progressive = true,
+            "--encoder" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.jpeg.encoder = jpeg::Encoder::parse(&value)?;
+            }
+            "--avif-speed" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.avif.speed = value
+                    .parse()
+                    .ok()
+                    .filter(|speed| *speed <= 10)
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected 0-10)", arg, value))?;
+            }
+            "--avif-alpha-quality" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.avif.alpha_quality = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|quality| (1..=100).contains(quality))
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected 1-100)", arg, value))?,
+                );
+            }
+            "--avif-depth" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.avif.bit_depth = value
+                    .parse()
+                    .ok()
+                    .filter(|depth| matches!(depth, 8 | 10 | 12))
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected 8, 10 or 12)", arg, value))?;
+            }
+            "--avif-chroma" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.avif.subsampling = color::Subsampling::parse(&value)?;
+            }
+            "--subsampling" => {
+                let value = next_value(&mut iter, &arg)?;
+                let subsampling = color::Subsampling::parse(&value)?;
+                options.jpeg.subsampling = Some(subsampling);
+                options.avif.subsampling = subsampling;
+            }
+            "--to" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.to = Some(SupportedFormat::from_extension(&value)?);
+            }
+            "--include" | "--exclude" => {
+                let value = next_value(&mut iter, &arg)?;
+                let pattern = Pattern::new(&value)
+                    .map_err(|e| format!("Invalid pattern for {}: {}: {}", arg, value, e))?;
+                if arg == "--include" {
+                    options.include.push(pattern);
+                } else {
+                    options.exclude.push(pattern);
+                }
+            }
+            "--quality" => {
+                let value = next_value(&mut iter, &arg)?;
+                let (format, quality) = match value.split_once('=') {
+                    Some((format, quality)) => (Some(SupportedFormat::from_extension(format.trim())?), quality),
+                    None => (None, value.as_str()),
+                };
+                let quality = quality
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|quality| (1..=100).contains(quality))
+                    .ok_or_else(|| format!("Invalid value for {}: {} (expected 1-100 or FORMAT=1-100)", arg, value))?;
+                match format {
+                    Some(format) => options.format_quality.push((format, quality)),
+                    None => options.quality = Some(quality),
+                }
+            }
+            "--suffix" => options.suffix = next_value(&mut iter, &arg)?,
+            "--name-template" => options.name_template = Some(template::Template::parse(&next_value(&mut iter, &arg)?)?),
+            "--on-collision" => options.on_collision = collision::Strategy::parse(&next_value(&mut iter, &arg)?)?,
+            "--rename-by-date" => options.rename_by_date = Some(dated::DateNaming::parse(&next_value(&mut iter, &arg)?)?),
+            "--report" => options.report = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--dry-run" => options.dry_run = true,
+            "--deterministic" => options.deterministic = true,
+            "--in-place" => options.in_place = true,
+            "--delete-original" => options.delete_original = true,
+            "--preserve-times" => options.preserve.times = true,
+            "--preserve-permissions" => options.preserve.permissions = true,
+            "--checkpoint" => options.checkpoint = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--resume" => options.resume = true,
+            "--backup-dir" => options.backup_dir = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--skip-existing" => options.incremental = Some(incremental::Mode::SkipExisting),
+            "--newer-only" => options.incremental = Some(incremental::Mode::NewerOnly),
+            "--overwrite" => options.overwrite = overwrite::Policy::Overwrite,
+            "--no-overwrite" => options.overwrite = overwrite::Policy::Fail,
+            "--rename-on-conflict" => options.overwrite = overwrite::Policy::Rename,
+            "--verify" => {
+                options.verify.get_or_insert_with(verify::Verify::default);
+            }
+            "--verify-psnr" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.verify.get_or_insert_with(verify::Verify::default).min_psnr = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|psnr: &f64| psnr.is_finite())
+                        .ok_or_else(|| format!("Invalid value for {}: {} (expected dB, e.g. 30)", arg, value))?,
+                );
+            }
+            "--preset" => {
+                let value = next_value(&mut iter, &arg)?;
+                options.preset = Some(preset::Preset::parse(&value)?);
+            }
+            _ => rest.push(arg),
+        }
+    }
+
+    if let Some(flag) = rest.iter().find(|arg| recipe_flags.contains(arg)) {
+        return Err(format!("Unknown recipe setting: {}", flag.trim_start_matches('-')));
+    }
+
+    if let Some(spec) = ops {
+        options.ops = pipeline::parse_ops(&spec, &mut options)?;
+    }
+
+    if let Some(preset) = options.preset {
+        preset.apply(&mut options);
+    }
+    if let Some(dpi) = options.dpi {
+        options.raster.dpi = dpi;
+    }
+    // Output may only depend on the input and the command line, not on
+    // anything particular to this machine.
+    if options.deterministic {
+        if options.overwrite == overwrite::Policy::Rename {
+            return Err("--rename-on-conflict names outputs in the order they finish, so it can't be used with --deterministic".to_string());
+        }
+        if options.name_template.as_ref().is_some_and(template::Template::uses_date) {
+            return Err("{date} in --name-template changes from day to day, so it can't be used with --deterministic".to_string());
+        }
+        options.raster.system_fonts = false;
+    } else {
+        config::Defaults::load()?.apply(&mut options);
+    }
+
+    if options.text.is_some() && options.font.is_none() {
+        return Err("--text requires --font <file.ttf>".to_string());
+    }
+
+    if options.keep_metadata && options.strip {
+        return Err("--keep-metadata and --strip cannot be used together".to_string());
+    }
+
+    if options.name_template.is_some() && !options.suffix.is_empty() {
+        return Err("--name-template and --suffix cannot be used together (put the suffix in the template)".to_string());
+    }
+
+    if options.rename_by_date.is_some() && options.in_place {
+        return Err("--rename-by-date moves outputs away from their inputs, so it can't be used with --in-place".to_string());
+    }
+
+    if let (Some(min), Some(max)) = (options.input_filter.min_filesize, options.input_filter.max_filesize) {
+        if min > max {
+            return Err("--min-filesize is larger than --max-filesize".to_string());
+        }
+    }
+
+    if options.files_from.is_none() {
+        if options.null_separated {
+            return Err("-0 requires --files-from".to_string());
+        }
+        if options.out_dir.is_some() {
+            return Err("--out-dir requires --files-from".to_string());
+        }
+    }
+
+    if options.backup_dir.is_some() && !options.in_place {
+        return Err("--backup-dir requires --in-place".to_string());
+    }
+    if options.delete_original && options.in_place {
+        return Err("--delete-original is implied by --in-place".to_string());
+    }
+
+    if options.jpeg_recompress && !options.transforms().is_empty() {
+        return Err("--jpeg-recompress keeps the JPEG data as-is and cannot be combined with transforms".to_string());
+    }
+
+    Ok((rest, options))
+}
+
+/// Replaces each `--recipe <file>` with the flags the recipe stands for,
+/// moved to the front so flags given on the command line override them.
+/// Also returns those flags, so ones nothing recognises can be reported.
+fn expand_recipes(args: Vec<String>) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut iter = args.into_iter();
+    let mut command_line: Vec<String> = iter.next().into_iter().collect();
+    let mut from_recipes = Vec::new();
+    while let Some(arg) = iter.next() {
+        if arg == "--recipe" {
+            let value = next_value(&mut iter, &arg)?;
+            from_recipes.extend(recipe::load(Path::new(&value))?);
+        } else {
+            command_line.push(arg);
+        }
+    }
+    let flags = from_recipes.iter().filter(|arg| arg.starts_with("--")).cloned().collect();
+    command_line.splice(1..1, from_recipes);
+    Ok((command_line, flags))
+}
+
+/// Parses `--max-pixels`: a pixel count, optionally in megapixels
+/// (`100MP`, `1.5M`), or `none` for no limit.
+fn parse_pixel_count(value: &str) -> Option<Option<u64>> {
+    let value = value.trim().to_lowercase();
+    if value == "none" {
+        return Some(None);
+    }
+    let (number, multiplier) = match value.strip_suffix("mp").or_else(|| value.strip_suffix('m')) {
+        Some(number) => (number, 1e6),
+        None => (value.as_str(), 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(|number| number * multiplier)
+        .filter(|pixels| *pixels >= 1.0 && pixels.is_finite())
+        .map(|pixels| Some(pixels as u64))
+}
+
+/// Parses a byte count such as `500000`, `200KB`, `1.5MB` or `64KiB`.
+/// KB and MB are decimal; KiB and MiB are binary.
+fn parse_byte_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|number| number * multiplier)
+        .filter(|bytes| *bytes >= 1.0 && bytes.is_finite())
+        .map(|bytes| bytes as u64)
+}
+
+/// Parses a number in the 0.0-1.0 range.
+fn parse_fraction(flag: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|fraction| (0.0..=1.0).contains(fraction))
+        .ok_or_else(|| format!("Invalid value for {}: {} (expected 0.0-1.0)", flag, value))
+}
+
+/// Parses a signed adjustment strength in -100 to 100.
+fn parse_percent_adjustment(flag: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|amount| (-100.0..=100.0).contains(amount))
+        .ok_or_else(|| format!("Invalid value for {}: {} (expected -100 to 100)", flag, value))
+}
+
+fn next_value(iter: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    iter.next().ok_or_else(|| format!("{} requires a value", flag))
+}
+
+/// `--files-from <FILE|-> --out-dir <DIR> --to <FORMAT>`: batch-converts
+/// the inputs read from `source` as a `--batch` list would be.
+fn files_from_batch(converter: &ImageConverter, options: &CliOptions, args: &[String], source: &Path) -> Result<(), String> {
+    if args.len() > 1 {
+        return Err(format!("--files-from takes its inputs from {}, not the command line", source.display()));
+    }
+    if options.in_place {
+        return Err("--in-place only applies to single-file and --batch conversion".to_string());
+    }
+    let output_dir = options.out_dir.as_deref().ok_or("--files-from requires --out-dir <DIR>")?;
+    let target_format = options
+        .to
+        .or(options.default_format)
+        .ok_or("--files-from requires --to <FORMAT>")?;
+    let listed = read_files_from(source, options.null_separated).map_err(|e| format!("{}: {}", source.display(), e))?;
+
+    if options.dry_run {
+        dry_run::Plan::list(converter, listed, output_dir, target_format).print(options.json);
+        return Ok(());
+    }
+    interrupt::install();
+    if let Err(e) = converter.batch_convert_list(listed, output_dir, target_format) {
+        eprintln!("Error during batch conversion: {}", e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn print_usage() {
+    println!("Image Format Converter");
+    println!("Supports: JPG/JPEG, PNG, WebP, AVIF, TIFF, BMP, GIF, ICO, TGA, QOI, JPEG XL, HEIC, camera RAW, SVG, PDF, OpenEXR and HDR (input)");
+    println!();
+    println!("Usage:");
+    println!("  Single file: {} <input_file> <output_file>", env::args().next().unwrap());
+    println!("  Pipeline:    {} - --to <format> > <output_file>", env::args().next().unwrap());
+    println!("  Batch mode:  {} --batch <input_dir> <output_dir> <format>", env::args().next().unwrap());
+    println!("  URL list:    {} --batch <list.txt> <output_dir> <format>", env::args().next().unwrap());
+    println!("  In place:    {} --batch <input_dir> <format> --in-place [--backup-dir <dir>]", env::args().next().unwrap());
+    println!("  File list:   {} --files-from <list.txt|-> [-0] --to <format> --out-dir <output_dir>", env::args().next().unwrap());
+    println!("  Watch mode:  {} watch <input_dir> <output_dir> <format>", env::args().next().unwrap());
+    println!("  Favicons:    {} favicon <input_file> <output_dir>", env::args().next().unwrap());
+    println!("  Frames:      {} extract-frames <input_file> <output_dir> <format>", env::args().next().unwrap());
+    println!("  Animate:     {} animate <frame_pattern> <output.gif|webp|png>", env::args().next().unwrap());
+    println!("  PDF:         {} to-pdf <input_dir|list.txt> <output.pdf>", env::args().next().unwrap());
+    println!("  Contact sheet: {} contact-sheet <input_dir> <output_file>", env::args().next().unwrap());
+    println!("  Sprite sheet: {} spritesheet <input_dir> <atlas.png>", env::args().next().unwrap());
+    println!("  Tiles:       {} tiles <input_file> <output_dir>", env::args().next().unwrap());
+    println!("  Stitch:      {} stitch <tile_dir|index.txt|name.dzi> <output_file>", env::args().next().unwrap());
+    println!("  Info:        {} info <file|dir>...", env::args().next().unwrap());
+    println!("  Compare:     {} compare <reference> <image>", env::args().next().unwrap());
+    println!("  Duplicates:  {} dedupe <input_dir>", env::args().next().unwrap());
+    println!("  Histogram:   {} histogram <input_file> [output.json|csv|png]", env::args().next().unwrap());
+    println!("  Thumbnails:  {} thumbnail <input_file|input_dir> <output_file|output_dir>", env::args().next().unwrap());
+    println!();
+    println!("Examples:");
+    println!("  {} image.png image.webp", env::args().next().unwrap());
+    println!("  {} input.jpg output.avif", env::args().next().unwrap());
+    println!("  {} https://example.com/photo.png photo.avif", env::args().next().unwrap());
+    println!("  {} --batch ./input ./output webp", env::args().next().unwrap());
+    println!("  {} --batch --jobs 4 ./input ./output avif", env::args().next().unwrap());
+    println!("  {} watch ./uploads ./converted webp", env::args().next().unwrap());
+    println!("  {} favicon logo.png ./public", env::args().next().unwrap());
+    println!("  {} extract-frames clip.gif ./frames png --every 2", env::args().next().unwrap());
+    println!("  {} animate 'frame_%04d.png' clip.webp --fps 12", env::args().next().unwrap());
+    println!("  {} to-pdf ./scans scans.pdf --page-size letter --page-margin 10", env::args().next().unwrap());
+    println!("  curl -s https://example.com/a.png | {} - --to webp > a.webp", env::args().next().unwrap());
+    println!();
+    println!("Supported formats: jpg, jpeg, png, webp, avif, tif, tiff, bmp, gif, ico, tga, qoi, jxl, heic/heif and cr2/nef/nrw/arw/srf/dng/raf/orf/rw2/pef, svg/svgz, pdf, exr, hdr (input only)");
+    println!();
+    println!("Options:");
+    println!("  -j, --jobs <N>     Convert up to N files in parallel in batch mode (default: all cores)");
+    println!("  --max-memory <SIZE>  Run fewer batch conversions at once when those running would need");
+    println!("                     more than SIZE of memory, estimated from each image's size, e.g. 2G");
+    println!("  -r, --recursive    Descend into subdirectories in batch mode, mirroring the layout");
+    println!("  --follow-symlinks  Convert linked files and walk linked directories in batch mode, skipping");
+    println!("                     links that loop back to a parent (default)");
+    println!("  --no-follow-symlinks  Leave symbolic links inside the input directory out of batch runs");
+    println!("  --max-depth <N>    Descend at most N levels of subdirectories (implies --recursive;");
+    println!("                     0 converts only the input directory itself)");
+    println!("  --include-hidden   Also convert dotfiles and walk dot-directories in batch mode");
+    println!("  --files-from <FILE>  Convert the paths or URLs listed in FILE, or stdin for -, one per line");
+    println!("  -0, --null         With --files-from, entries are separated by NUL bytes (find -print0)");
+    println!("  --out-dir <DIR>    Where --files-from writes its outputs");
+    println!("  --include <GLOB>   Only convert files matching GLOB in batch mode (repeatable)");
+    println!("  --exclude <GLOB>   Skip files matching GLOB in batch mode (repeatable)");
+    println!("  --min-width <PX>   Skip batch inputs narrower than PX, read from the header (so are");
+    println!("  --min-height <PX>  --min-height); inputs whose header can't be read this way are kept");
+    println!("  --min-filesize <SIZE>  Skip batch inputs smaller than SIZE, e.g. 10KB");
+    println!("  --max-filesize <SIZE>  Skip batch inputs larger than SIZE, e.g. 50MB");
+    println!("  --to <FORMAT>      Output format for single files; required when writing to stdout");
+    println!("                     (also stands in for the format argument of --batch)");
+    println!("  --quality <Q>      Encoder quality 1-100 (default: 85, 75 for JPEG), or FORMAT=Q for one");
+    println!("                     format only, e.g. --quality 80 --quality avif=55 (repeatable)");
+    println!("  --report <FILE>    After a batch, write each input's output, sizes, percent saved,");
+    println!("                     duration and error to FILE (.json for JSON, CSV otherwise)");
+    println!("  --dry-run          List what single-file or batch conversion would write, including");
+    println!("                     colliding outputs, overwritten files and skipped inputs, without");
+    println!("                     decoding or writing anything");
+    println!("  --skip-existing    Leave inputs alone whose outputs already exist");
+    println!("  --newer-only       Leave inputs alone whose outputs exist and are at least as new as");
+    println!("                     the input, so re-runs only convert what changed");
+    println!("  --in-place         Replace each input with its output, deleting the original; batch");
+    println!("                     mode then takes no output directory and skips files already in");
+    println!("                     the target format, single files take no output file (set --to)");
+    println!("  --backup-dir <DIR> With --in-place, move originals into DIR instead of deleting them");
+    println!("  --delete-original  Delete each input once its output is written (and passed --verify);");
+    println!("                     the batch summary counts deletions and the space freed");
+    println!("  --preserve-times   Give outputs the modification and access times of their input");
+    println!("  --preserve-permissions  Give outputs the file permissions of their input");
+    println!("  --resume           Skip the inputs a previous batch run into the same output directory");
+    println!("                     converted before it crashed, failed or was interrupted");
+    println!("  --checkpoint <FILE>  Where batch runs record converted inputs for --resume (default:");
+    println!("                     .image-converter-checkpoint in the output directory, removed once a");
+    println!("                     run converts everything)");
+    println!("  --deterministic    Byte-identical output on every run and machine: ignores config.toml");
+    println!("                     and IMAGE_CONVERTER_* defaults and installed fonts for SVG text, and");
+    println!("                     rejects --rename-on-conflict and {{date}} in --name-template, whose");
+    println!("                     names depend on timing and the day");
+    println!("  --overwrite        Replace output files that already exist (default)");
+    println!("  --no-overwrite     Fail an input whose output file already exists");
+    println!("  --rename-on-conflict  Write to the first free numbered name instead, e.g. photo-1.webp");
+    println!("  --verify           Read each output back and decode it, failing the input (and removing");
+    println!("                     the file) if it doesn't match the encoded data or image size");
+    println!("  --verify-psnr <DB> With --verify, also fail outputs below DB of PSNR against the");
+    println!("                     converted pixels (not checked for AVIF, which isn't decoded)");
+    println!("  --suffix <TEXT>    Append TEXT to output file names in batch and watch mode, e.g. -web");
+    println!("  --name-template <PATTERN>  Name batch and watch outputs after PATTERN, e.g.");
+    println!("                     \"{{stem}}_{{width}}x{{height}}_q{{quality}}.{{ext}}\"; also {{format}}, {{date}},");
+    println!("                     {{counter}} ({{counter:3}} pads to 3 digits) and {{hash}} ({{hash:8}} of the input)");
+    println!("  --rename-by-date <PATTERN>  Name batch and watch outputs after the EXIF capture time (or the");
+    println!("                     file's modification time, in UTC), e.g. \"%Y/%m/%Y%m%d_%H%M%S\"; slashes");
+    println!("                     nest outputs in folders, and {{stem}} in --name-template is the result.");
+    println!("                     Photos taken the same second collide; see --on-collision");
+    println!("  --on-collision <S> When batch inputs map to the same output name (a.png and a.jpg both");
+    println!("                     to a.webp): error (default; nothing is converted), number (a-1.webp)");
+    println!("                     or hash (a-<hash of the input>.webp)");
+    println!("  --preset <NAME>    Bundled settings, overridden by any flag given: web (WebP q80, max");
+    println!("                     1920px, stripped, sRGB), thumbnail (WebP q70, max 320px), archive");
+    println!("                     (PNG, metadata kept) or print (JPEG q95, metadata kept, 300 DPI)");
+    println!("  --recipe <FILE>    Read flags from a TOML or YAML recipe: keys are flag names, plus ops,");
+    println!("                     format, formats and a [quality] table; command-line flags win");
+    println!("  --page <N>         Page of a multi-page TIFF or PDF to convert (default: 1)");
+    println!("  --pages all        Convert every PDF page to numbered outputs (out-1.png, out-2.png, ...)");
+    println!("  --first-frame      Convert only the first frame of animated GIF, WebP and APNG input");
+    println!("                     (by default animations are kept for GIF, WebP and PNG output)");
+    println!("  --no-rle           Write uncompressed TGA instead of RLE-compressed");
+    println!("  --jxl-lossless     Encode JPEG XL output losslessly");
+    println!("  --webp-lossless    Encode WebP output losslessly (default: lossy at the output quality)");
+    println!("  --webp-near-lossless <0-100>  Lossless WebP of slightly smoothed pixels; lower is smaller");
+    println!("  --jpeg-recompress  Repack JPEG inputs into JPEG XL losslessly (~20% smaller, bit-exact)");
+    println!("  --dpi <N>          Rasterize SVG and PDF input at N DPI (default: 96)");
+    println!("  --tonemap <OP>     Tone map OpenEXR and HDR input: aces (default), reinhard or clamp");
+    println!("  --exposure <EV>    Brighten (+) or darken (-) OpenEXR and HDR input by EV stops first");
+    println!("  --raster-size <WxH> Rasterize SVG and PDF input to WxH, Wx or xH pixels (overrides --dpi)");
+    println!("  --every <N>        extract-frames: write every Nth frame (default: 1)");
+    println!("  --range <A-B>      extract-frames: only frames A to B, 1-based (A- and -B leave an end open)");
+    println!("  --fps <N>          animate: frames per second");
+    println!("  --delay <MS>       animate: frame duration in milliseconds (default: 100)");
+    println!("  --loop <N>         animate: times to play the animation, 0 = forever (default: 0)");
+    println!("  --widths <W,W,..>  Write one output per width from a single decode (srcset sets)");
+    println!("  --width-suffix <S> File name suffix per width, {{w}} is the width (default: -{{w}})");
+    println!("  --formats <F,F,..> Write each output (every width with --widths) in each format,");
+    println!("                     e.g. avif,webp,jpg");
+    println!("  --thumb-size <PX>  thumbnail: longest side (default: 256); directories default to --to jpg");
+    println!("  --columns <N>      contact-sheet: thumbnails per row (default: 5)");
+    println!("  --rows <N>         contact-sheet: rows per page; more images go to numbered pages (default: 6)");
+    println!("  --cell-size <PX>   contact-sheet: thumbnail box size (default: 200); labels need --font");
+    println!("  --packing <MODE>   spritesheet: grid or packed (default: packed)");
+    println!("  --padding <PX>     spritesheet: transparent pixels around each sprite (default: 0)");
+    println!("  --map <FORMAT>     spritesheet: coordinate map written beside the atlas, json or css (default: json)");
+    println!("  --layout <L>       tiles: dzi (Deep Zoom, default) or xyz (z/x/y map tiles); format from --to (default: jpg)");
+    println!("  --tile-size <PX>   tiles: tile width and height (default: 256); xyz edge tiles are padded");
+    println!("                     to full size, transparent or in --background");
+    println!("  --overlap <PX>     tiles: pixels shared with neighbouring DZI tiles (default: 1)");
+    println!("                     (stitch reads row and column from the last two numbers in tile names,");
+    println!("                     e.g. r03_c05.png, or from index lines of <row> <col> <path>; given a");
+    println!("                     .dzi or its name_files/<level> folder, or a z/x/y map folder, it reads");
+    println!("                     the largest level in that layout's order, dropping DZI overlap)");
+    println!("  --json             Print JSON instead of text: one event per line (converted, failed,");
+    println!("                     skipped, completed) when converting; reports for info, compare,");
+    println!("                     dedupe and histogram");
+    println!("  --hash <KIND>      dedupe: ahash, dhash or phash (default: phash)");
+    println!("  --distance <N>     dedupe: max differing hash bits of near-duplicates, 0-64 (default: 6)");
+    println!("  --skip-duplicates  Batch mode: convert only the first of each group of near-duplicates");
+    println!("  --blurhash         Write a BlurHash placeholder for each output to a <name>.json sidecar");
+    println!("  --blurhash-components <XxY>  BlurHash detail, 1-9 each way (default: 4x3)");
+    println!("  --thumbhash        Write a ThumbHash placeholder (keeps aspect ratio and alpha) to the sidecar");
+    println!("  --dominant-colors <N>  Add the average and N most common colors (hex) to the sidecar");
+    println!("  --placeholder      Also write a tiny low-quality preview (LQIP) beside each output");
+    println!("  --placeholder-width <PX>  Placeholder width (default: 20)");
+    println!("  --placeholder-suffix <S>  Placeholder file name suffix (default: -placeholder)");
+    println!("  --placeholder-blur <SIGMA>  Blur the placeholder by SIGMA of its own pixels");
+    println!("  --min-psnr <DB>    compare: exit with status 2 when PSNR is below DB");
+    println!("  --min-ssim <0-1>   compare: exit with status 2 when SSIM (or MS-SSIM with --perceptual) is below");
+    println!("  --perceptual       compare: also compute MS-SSIM, closer to perceived quality");
+    println!("  --diff-output <FILE>  compare: write a heatmap of the differing pixels");
+    println!("  --diff-threshold <0-255>  compare: channel difference ignored as unchanged (default: 0)");
+    println!("  --page-size <SIZE> to-pdf page size: a3, a4 (default), a5, letter, legal or fit");
+    println!("  --page-margin <MM> to-pdf margin on every side of the page (default: 0)");
+    println!("                     (to-pdf places images at --dpi, shrinking them to fit the page)");
+    println!("  --avif-speed <0-10>  AVIF encoder speed; higher is faster but larger (default: 4)");
+    println!("  --avif-alpha-quality <1-100>  AVIF quality for the alpha channel (default: same as color)");
+    println!("  --avif-depth <8|10|12>  AVIF bits per sample (default: 8)");
+    println!("  --avif-chroma <420|422|444>  AVIF chroma subsampling (default: 444)");
+    println!("  --subsampling <420|422|444>  JPEG and AVIF chroma subsampling; 444 keeps colored text");
+    println!("                     crisp, 420 is smaller for photos (default: 444, 420 for progressive");
+    println!("                     and mozjpeg JPEG)");
+    println!("  --max-size <SIZE>  Lower JPEG/WebP/AVIF/JPEG XL quality until each output fits, e.g. 200KB");
+    println!("  --allow-downscale  With --max-size, shrink images that don't fit at the lowest quality");
+    println!("  --max-pixels <N>   Fail inputs over N pixels, read from the header before decoding where");
+    println!("                     possible, e.g. 250MP or none (default: 100MP); animations count");
+    println!("                     every frame");
+    println!("  --background <HEX> Fill behind transparent pixels when the output (JPEG) has no alpha");
+    println!("  --colors <N>       Write indexed PNG and GIF output with at most N colors (2-256)");
+    println!("  --quantizer <Q>    Palette algorithm for --colors: median-cut (default) or neuquant");
+    println!("  --dither           Floyd-Steinberg dithering with --colors, trading banding for grain");
+    println!("  --bit-depth <8|16> Force the output sample depth; 16 applies to PNG, TIFF and JPEG XL");
+    println!("                     (default: keep 16-bit sources at 16 bits where the format allows)");
+    println!("  --png-compression <0-9>  PNG zlib level, 0 = store, 9 = smallest");
+    println!("  --png-filter <F>   PNG row filter: none, sub, up, avg, paeth or adaptive (default)");
+    println!("  --png-optimize     Try every PNG filter at maximum compression and keep the smallest");
+    println!("  --oxipng           Run PNG output through oxipng: deflate strategies, color type");
+    println!("                     reductions and stripping chunks that don't affect display");
+    println!("  --interlace        Write Adam7-interlaced PNG, which renders progressively as it loads");
+    println!("  --progressive      Write progressive JPEG, which renders coarse-to-fine as it loads");
+    println!("  --encoder <NAME>   JPEG encoder: builtin (default) or mozjpeg, 10-20% smaller");
+    println!("                     (mozjpeg requires building with --features mozjpeg)");
+    println!("  --no-auto-orient   Ignore the EXIF Orientation tag instead of rotating to match it");
+    println!("  --keep-metadata    Copy EXIF and XMP to JPEG, PNG and WebP outputs");
+    println!("  --strip            Guarantee no EXIF, XMP, ICC or text metadata in the output");
+    println!("  --to-srgb          Convert colors from the embedded ICC profile to sRGB");
+    println!("                     (by default the source profile is embedded in the output)");
+    println!("  --no-to-srgb       Keep the source profile even when --preset would convert to sRGB");
+    println!("  --ops <LIST>       Comma-separated operations run in the given order before the flags");
+    println!("                     below, e.g. autorotate,trim,resize=1600x,sharpen=0.6,strip; names");
+    println!("                     match the flags (rotate=90, crop=WxH+X+Y, curve=..., sepia, grayscale)");
+    println!("  --rotate <DEG>     Rotate clockwise by 90, 180 or 270 degrees");
+    println!("  --flip <h|v>       Mirror horizontally or vertically (repeatable)");
+    println!("  --deskew           Straighten scans by the dominant line angle (up to 20 degrees) first");
+    println!("  --deskew-color <HEX>  Fill for corners revealed by --deskew (default: #ffffff)");
+    println!("  --trim             Remove uniform borders (color of the top-left pixel) first");
+    println!("  --trim-tolerance <0-255>  Per-channel difference still treated as border (implies --trim)");
+    println!("  --crop <WxH+X+Y>   Crop a window before resizing (pixels or N% per component)");
+    println!("  --crop-center <WxH>  Crop a centred window before resizing");
+    println!("  --smart-crop <W:H> Crop to an aspect ratio around the busiest region instead of the centre");
+    println!("  --resize <SIZE>    Resize to WxH, Wx, xH or N% before encoding");
+    println!("  --fit              With WxH, fit inside the box keeping aspect ratio (default)");
+    println!("  --fill             With WxH, cover the box keeping aspect ratio and crop the overflow");
+    println!("  --exact            With WxH, stretch to exactly that size");
+    println!("  --shrink-only      Only resize images larger than --resize, never enlarging");
+    println!("  --white-balance <M>  Remove color casts: auto (gray-world) or white-patch (brightest = white)");
+    println!("  --temperature <N>  Warm (positive) or cool (negative) the white balance, -100 to 100");
+    println!("  --tint <N>         Shift the white balance to magenta (positive) or green (negative), -100 to 100");
+    println!("  --auto-level       Stretch each channel to the full range (also removes color casts)");
+    println!("  --auto-contrast    Stretch all channels together to the full range, keeping hues");
+    println!("  --level-clip <PCT> Darkest/lightest percent ignored by --auto-level/--auto-contrast (default: 0.1)");
+    println!("  --equalize         Equalize the lightness histogram over the whole image");
+    println!("  --clahe <T[,C]>    Adaptive equalization over TxT (or TxU) tiles, limited to C times the");
+    println!("                     average bin (default: 8,2): local contrast for scans and micrographs");
+    println!("  --brightness <N>   Brighten (positive) or darken (negative) by N%, -100 to 100");
+    println!("  --contrast <N>     Raise (positive) or lower (negative) contrast, -100 to 100");
+    println!("  --gamma <G>        Gamma correction, 0.1-10: above 1 brightens mid-tones (default: 1.0)");
+    println!("  --curve <POINTS>   Tone curve through in,out points in 0-1, e.g. \"0,0 0.25,0.2 0.75,0.85 1,1\";");
+    println!("                     prefix r:, g: or b: for a single channel (repeatable)");
+    println!("  --saturation <N>   Raise or lower saturation by N%, -100 (gray) to 100");
+    println!("  --hue-rotate <DEG> Rotate hues by DEG degrees");
+    println!("  --vibrance <N>     Like --saturation, but mostly boosting muted colors, -100 to 100");
+    println!("                     (color adjustments work in OKLCh, keeping perceived lightness)");
+    println!("  --lut <FILE.cube>  Apply a 3D color lookup table (trilinear interpolation)");
+    println!("  --filter <F>       sepia, invert, posterize=N or duotone=#shadow,#highlight (repeatable)");
+    println!("  --denoise <0-100>  Reduce noise before resizing, mostly in chroma (edge-preserving)");
+    println!("  --blur <SIGMA>     Gaussian blur after resizing, SIGMA in output pixels");
+    println!("  --sharpen <A[,R[,T]]>  Unsharp mask after resizing: amount, radius in pixels (default: 1,");
+    println!("                     scaled up beyond a 1000px long edge) and threshold 0-255 (default: 0)");
+    println!("  --pad <W:H>        Letterbox to an aspect ratio after resizing, without distortion");
+    println!("  --pad-color <HEX>  Fill for --pad: #rrggbb[aa] or transparent (default: #000000)");
+    println!("  --grayscale        Convert to luminance only (single-channel PNG/JPEG)");
+    println!("  --watermark <FILE> Composite an image onto every output");
+    println!("  --position <POS>   Watermark anchor: top-left, top, ..., center, ..., bottom-right (default)");
+    println!("  --opacity <0-1>    Watermark opacity (default: 1.0)");
+    println!("  --margin <PX>      Watermark/text distance from the anchored edges (default: 20)");
+    println!("  --watermark-scale <0-1>  Watermark width relative to the output width (default: 0.2)");
+    println!("  --text <TEXT>      Burn a caption into every output (requires --font)");
+    println!("  --font <FILE>      TrueType/OpenType font for --text");
+    println!("  --text-size <PX>   Caption size in pixels (default: 24)");
+    println!("  --text-pos <POS>   Caption anchor, same names as --position (default: bottom-left)");
+    println!("  --text-color <HEX> Caption color, e.g. #ffffff or #ffffff80 (default: white)");
+    println!("  --text-outline <HEX>  Draw an outline in this color around the caption");
+    println!("  --text-outline-width <PX>  Outline width (default: 2 when --text-outline is set)");
+    println!();
+    println!("Use - as the input or output file to read from stdin or write to stdout.");
+    println!();
+    println!("Ctrl+C during a batch lets the files in progress finish, prints the summary and exits");
+    println!("with status 130; a second Ctrl+C stops at once, removing partly written outputs.");
+    println!();
+    println!("Defaults for quality, jobs and format (the output format when none is given) are read");
+    println!("from ~/.config/image-converter/config.toml (or $IMAGE_CONVERTER_CONFIG), then from");
+    println!("IMAGE_CONVERTER_QUALITY, IMAGE_CONVERTER_JOBS and IMAGE_CONVERTER_FORMAT; flags win.");
+}
+
+fn main() {
+    let (args, options) = match parse_options(env::args().collect()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // `image-converter - --to webp` writes to stdout without naming it.
+    let mut args = args;
+    if args.len() == 2 && options.to.is_some() && !options.in_place {
+        args.push("-".to_string());
+    }
+
+    // `image-converter photo.jpg --in-place --to avif` names no output.
+    let in_place_file = options.in_place && args.len() == 2;
+    let files_from = options.files_from.is_some();
+    if args.len() < 3 && args.get(1).map(String::as_str) != Some("info") && !in_place_file && !files_from {
+        print_usage();
+        std::process::exit(1);
+    }
+
+    let mut format_quality = options.format_quality.clone();
+    if options.quality.is_none() {
+        format_quality.insert(0, (SupportedFormat::Jpeg, DEFAULT_JPEG_QUALITY));
+    }
+    let converter = ImageConverter::new(options.quality.unwrap_or(DEFAULT_QUALITY))
+        .with_format_quality(format_quality)
+        .with_suffix(options.suffix.clone())
+        .with_name_template(options.name_template.clone())
+        .with_rename_by_date(options.rename_by_date.clone())
+        .with_on_collision(options.on_collision)
+        .with_json(options.json)
+        .with_report(options.report.clone())
+        .with_incremental(options.incremental)
+        .with_overwrite(options.overwrite)
+        .with_verify(options.verify)
+        .with_in_place(options.in_place.then(|| in_place::InPlace {
+            backup_dir: options.backup_dir.clone(),
+        }))
+        .with_delete_original(options.delete_original)
+        .with_preserve(options.preserve)
+        .with_checkpoint(options.checkpoint.clone(), options.resume)
+        .with_jobs(options.jobs)
+        .with_max_memory(options.max_memory)
+        .with_recursive(options.recursive)
+        .with_walk(options.walk)
+        .with_input_filter(options.input_filter)
+        .with_auto_orient(!options.no_auto_orient)
+        .with_keep_metadata(options.keep_metadata)
+        .with_strip_metadata(options.strip)
+        .with_to_srgb(options.to_srgb.unwrap_or(false))
+        .with_page(options.page)
+        .with_animated(!options.first_frame)
+        .with_tga_rle(!options.no_rle)
+        .with_jxl_lossless(options.jxl_lossless)
+        .with_webp_lossless(options.webp_lossless)
+        .with_webp_near_lossless(options.webp_near_lossless)
+        .with_jpeg_recompress(options.jpeg_recompress)
+        .with_raster(options.raster)
+        .with_tone_map(options.tone_map)
+        .with_all_pages(options.all_pages)
+        .with_max_size(options.max_size)
+        .with_max_pixels(options.max_pixels)
+        .with_allow_downscale(options.allow_downscale)
+        .with_bit_depth(options.bit_depth)
+        .with_background(options.background)
+        .with_quantize(options.colors.map(|colors| quantize::Quantize {
+            colors,
+            method: options.quantizer,
+            dither: options.dither,
+        }))
+        .with_variants((!options.widths.is_empty() || !options.formats.is_empty()).then(|| responsive::Variants {
+            widths: options.widths.clone(),
+            suffix: options.width_suffix.clone(),
+            formats: options.formats.clone(),
+        }))
+        .with_skip_duplicates(options.skip_duplicates.then_some(options.dedupe))
+        .with_analysis(sidecar::Analysis {
+            blurhash: options.blurhash.then_some(options.blurhash_components),
+            thumbhash: options.thumbhash,
+            dominant_colors: options.dominant_colors,
+        })
+        .with_placeholder(options.placeholder.then(|| options.placeholder_options.clone()))
+        .with_jpeg_options(options.jpeg)
+        .with_png_options(options.png)
+        .with_avif_options(options.avif)
+        .with_pipeline(options.transforms().into_iter().collect())
+        .with_filters(options.include.clone(), options.exclude.clone());
+
+    // `--files-from` takes its inputs from a file or stdin rather than the
+    // command line.
+    if let Some(source) = &options.files_from {
+        if let Err(e) = files_from_batch(&converter, &options, &args, source) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        if interrupt::requested() {
+            std::process::exit(interrupt::EXIT_CODE);
+        }
+        return;
+    }
+
+    const MODES: [&str; 14] = [
+        "info", "compare", "dedupe", "histogram", "watch", "favicon", "extract-frames", "animate",
+        "contact-sheet", "spritesheet", "tiles", "stitch", "thumbnail", "to-pdf",
+    ];
+    if MODES.contains(&args[1].as_str()) {
+        for (flag, set) in [("--dry-run", options.dry_run), ("--in-place", options.in_place)] {
+            if set {
+                eprintln!("Error: {} only applies to single-file and --batch conversion", flag);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args[1] == "info" {
+        // Image details, identify-style
+        if args.len() < 3 {
+            eprintln!("Error: info mode requires at least one file");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let mut inputs = Vec::new();
+        for arg in &args[2..] {
+            let path = Path::new(arg);
+            if path.is_dir() {
+                match converter.selected_inputs(path) {
+                    Ok(mut files) => {
+                        files.sort();
+                        inputs.extend(files);
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}: {}", path.display(), e);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                inputs.push(path.to_path_buf());
+            }
+        }
+
+        let mut failed = false;
+        let mut reports = Vec::new();
+        for input in &inputs {
+            match info::inspect(&converter, input) {
+                Ok(info) if options.json => reports.push(info.to_json()),
+                Ok(info) => println!("{}", info.to_text()),
+                Err(e) => {
+                    eprintln!("Error: {}: {}", input.display(), e);
+                    failed = true;
+                }
+            }
+        }
+        if options.json {
+            println!("{}", serde_json::to_string_pretty(&reports).unwrap_or_default());
+        }
+        if failed {
+            std::process::exit(1);
+        }
+    } else if args[1] == "compare" {
+        // Quality metrics between a reference and a converted image
+        if args.len() != 4 {
+            eprintln!("Error: compare mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let load = |arg: &String| {
+            let path = Path::new(arg);
+            converter
+                .read_input(path)
+                .and_then(|data| converter.decode_image(path, &data))
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        let scores = load(&args[2])
+            .and_then(|reference| Ok((reference, load(&args[3])?)))
+            .and_then(|(reference, actual)| {
+                let scores = compare::compare(&reference, &actual, options.perceptual, options.diff_threshold)?;
+                if let Some(path) = &options.diff_output {
+                    let format = SupportedFormat::from_extension(&path.extension().unwrap_or_default().to_string_lossy())?;
+                    let diff = DynamicImage::ImageRgb8(compare::diff_image(&reference, &actual, options.diff_threshold));
+                    converter
+                        .save_image(&diff, &[], path, format)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                }
+                Ok(scores)
+            });
+        let scores = match scores {
+            Ok(scores) => scores,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if options.json {
+            println!("{}", serde_json::to_string_pretty(&scores.to_json()).unwrap_or_default());
+        } else {
+            println!("{}", scores.to_text());
+        }
+
+        let ssim = scores.ms_ssim.unwrap_or(scores.ssim);
+        let below_psnr = options.min_psnr.filter(|&min| scores.psnr < min);
+        let below_ssim = options.min_ssim.filter(|&min| ssim < min);
+        if let Some(min) = below_psnr {
+            eprintln!("PSNR below threshold of {} dB", min);
+        }
+        if let Some(min) = below_ssim {
+            eprintln!("{} below threshold of {}", if scores.ms_ssim.is_some() { "MS-SSIM" } else { "SSIM" }, min);
+        }
+        if below_psnr.is_some() || below_ssim.is_some() {
+            std::process::exit(2);
+        }
+    } else if args[1] == "dedupe" {
+        // Near-duplicate report
+        if args.len() != 3 {
+            eprintln!("Error: dedupe mode requires 2 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_dir = Path::new(&args[2]);
+        if !input_dir.is_dir() {
+            eprintln!("Error: Input directory does not exist or is not a directory");
+            std::process::exit(1);
+        }
+
+        let inputs = match converter.selected_inputs(input_dir) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("Error: {}: {}", input_dir.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let groups = dedupe::find_groups(&converter, &inputs, options.dedupe);
+        if options.json {
+            let groups: Vec<Vec<String>> = groups
+                .iter()
+                .map(|group| group.iter().map(|path| path.display().to_string()).collect())
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&groups).unwrap_or_default());
+        } else {
+            for (number, group) in groups.iter().enumerate() {
+                println!("Group {} ({} images):", number + 1, group.len());
+                for path in group {
+                    println!("  {}", path.display());
+                }
+            }
+            let duplicates: usize = groups.iter().map(|group| group.len() - 1).sum();
+            println!("{} near-duplicates in {} groups among {} images", duplicates, groups.len(), inputs.len());
+        }
+    } else if args[1] == "histogram" {
+        // Per-channel level counts
+        if args.len() != 3 && args.len() != 4 {
+            eprintln!("Error: histogram mode requires 2 or 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_path = Path::new(&args[2]);
+        let histogram = match converter
+            .read_input(input_path)
+            .and_then(|data| converter.decode_image(input_path, &data))
+        {
+            Ok(image) => histogram::compute(&image),
+            Err(e) => {
+                eprintln!("Error: {}: {}", input_path.display(), e);
+                std::process::exit(1);
+            }
+        };
+
+        let Some(output_path) = args.get(3).map(Path::new) else {
+            if options.json {
+                println!("{}", serde_json::to_string_pretty(&histogram.to_json()).unwrap_or_default());
+            } else {
+                print!("{}", histogram.to_csv());
+            }
+            return;
+        };
+        let extension = output_path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        let result: Result<(), Box<dyn std::error::Error>> = match extension.as_str() {
+            "json" => {
+                let json = serde_json::to_string_pretty(&histogram.to_json()).unwrap_or_default();
+                atomic::write(output_path, (json + "\n").as_bytes()).map_err(Into::into)
+            }
+            "csv" => atomic::write(output_path, histogram.to_csv().as_bytes()).map_err(Into::into),
+            _ => SupportedFormat::from_extension(&extension).map_err(Into::into).and_then(|format| {
+                let chart = DynamicImage::ImageRgb8(histogram.chart());
+                converter.save_image(&chart, &[], output_path, format).map_err(Into::into)
+            }),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}: {}", output_path.display(), e);
+            std::process::exit(1);
+        }
+        println!("{}", histogram.to_text());
+        println!("Created: {}", output_path.display());
+    } else if args[1] == "--batch" {
+        // Batch mode
+        // In place, outputs go beside the inputs rather than to a directory.
+        let (arguments, format_index) = if options.in_place { (3, 3) } else { (4, 4) };
+        if args.len() != arguments && args.len() != arguments + 1 {
+            eprintln!("Error: Batch mode requires {} arguments", arguments);
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_dir = Path::new(&args[2]);
+        let output_dir = if options.in_place { input_dir } else { Path::new(&args[3]) };
+        if options.in_place && !input_dir.is_dir() {
+            eprintln!("Error: --in-place batch mode requires an input directory");
+            std::process::exit(1);
+        }
+
+        // The format argument may be left to --to, e.g. from a recipe.
+        let target_format = match args.get(format_index) {
+            Some(format) => SupportedFormat::from_extension(format),
+            None => options
+                .to
+                .or(options.default_format)
+                .ok_or_else(|| "Batch mode requires a format argument or --to".to_string()),
+        };
+        let target_format = match target_format {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if options.dry_run {
+            let plan = if input_dir.is_file() {
+                read_input_list(input_dir).map(|listed| dry_run::Plan::list(&converter, listed, output_dir, target_format))
+            } else {
+                dry_run::Plan::directory(&converter, input_dir, output_dir, target_format)
+            };
+            match plan {
+                Ok(plan) => plan.print(options.json),
+                Err(e) => {
+                    eprintln!("Error: {}: {}", input_dir.display(), e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+
+        interrupt::install();
+        // A plain file is read as a list of paths/URLs to convert.
+        let result = if input_dir.is_file() {
+            read_input_list(input_dir)
+                .map_err(Into::into)
+                .and_then(|listed| converter.batch_convert_list(listed, output_dir, target_format))
+        } else if input_dir.is_dir() {
+            converter.batch_convert(input_dir, output_dir, target_format)
+        } else {
+            eprintln!("Error: Input directory does not exist or is not a directory");
+            std::process::exit(1);
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error during batch conversion: {}", e);
+            std::process::exit(1);
+        }
+        if interrupt::requested() {
+            std::process::exit(interrupt::EXIT_CODE);
+        }
+    } else if args[1] == "watch" {
+        // Watch mode
+        if args.len() != 5 {
+            eprintln!("Error: Watch mode requires 4 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_dir = Path::new(&args[2]);
+        let output_dir = Path::new(&args[3]);
+
+        let target_format = match SupportedFormat::from_extension(&args[4]) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if !input_dir.is_dir() {
+            eprintln!("Error: Input directory does not exist or is not a directory");
+            std::process::exit(1);
+        }
+
+        if let Err(e) = watch::watch(&converter, input_dir, output_dir, target_format) {
+            eprintln!("Error while watching: {}", e);
+            std::process::exit(1);
+        }
+    } else if args[1] == "favicon" {
+        // Favicon set
+        if args.len() != 4 {
+            eprintln!("Error: Favicon mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_path = Path::new(&args[2]);
+        let output_dir = Path::new(&args[3]);
+
+        if !is_stdio(input_path) && !remote::is_url(&args[2]) && !input_path.exists() {
+            eprintln!("Error: Input file does not exist: {}", input_path.display());
+            std::process::exit(1);
+        }
+
+        match favicon::generate(&converter, input_path, output_dir) {
+            Ok(written) => {
+                for path in written {
+                    println!("Created: {}", path.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("Error generating favicons: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "extract-frames" {
+        // Animation frames to stills
+        if args.len() != 5 {
+            eprintln!("Error: extract-frames mode requires 4 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_path = Path::new(&args[2]);
+        let output_dir = Path::new(&args[3]);
+
+        let target_format = match SupportedFormat::from_extension(&args[4]) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if !is_stdio(input_path) && !remote::is_url(&args[2]) && !input_path.exists() {
+            eprintln!("Error: Input file does not exist: {}", input_path.display());
+            std::process::exit(1);
+        }
+
+        match frames::extract(&converter, input_path, output_dir, target_format, options.frames) {
+            Ok(written) => {
+                for path in &written {
+                    println!("Created: {}", path.display());
+                }
+                println!("Extracted {} frames", written.len());
+            }
+            Err(e) => {
+                eprintln!("Error extracting frames: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "animate" {
+        // Stills to animation
+        if args.len() != 4 {
+            eprintln!("Error: animate mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let output_path = Path::new(&args[3]);
+
+        let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+        let target_format = match options.to.map(Ok).unwrap_or_else(|| SupportedFormat::from_extension(&extension)) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        match frames::assemble(&converter, &args[2], output_path, target_format, options.timing) {
+            Ok(count) => println!("Created: {} ({} frames)", output_path.display(), count),
+            Err(e) => {
+                eprintln!("Error creating animation: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "contact-sheet" {
+        // Thumbnail grid for review
+        if args.len() != 4 {
+            eprintln!("Error: contact-sheet mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_dir = Path::new(&args[2]);
+        let output_path = Path::new(&args[3]);
+
+        if !input_dir.is_dir() {
+            eprintln!("Error: Input directory does not exist or is not a directory");
+            std::process::exit(1);
+        }
+
+        let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+        let target_format = match options.to.map(Ok).unwrap_or_else(|| SupportedFormat::from_extension(&extension)) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let layout = contact_sheet::Layout {
+            font: options.font.clone(),
+            ..options.sheet.clone()
+        };
+        if layout.font.is_none() {
+            eprintln!("Warning: no --font given; thumbnails are left unlabelled");
+        }
+        match contact_sheet::generate(&converter, input_dir, output_path, target_format, &layout) {
+            Ok(pages) => {
+                for page in pages {
+                    println!("Created: {}", page.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("Error creating contact sheet: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "spritesheet" {
+        // Texture atlas with a coordinate map
+        if args.len() != 4 {
+            eprintln!("Error: spritesheet mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_dir = Path::new(&args[2]);
+        let output_path = Path::new(&args[3]);
+
+        if !input_dir.is_dir() {
+            eprintln!("Error: Input directory does not exist or is not a directory");
+            std::process::exit(1);
+        }
+
+        let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+        let target_format = match options.to.map(Ok).unwrap_or_else(|| SupportedFormat::from_extension(&extension)) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        match spritesheet::generate(&converter, input_dir, output_path, target_format, options.sprites) {
+            Ok((atlas, map)) => {
+                println!("Created: {}", atlas.display());
+                println!("Created: {}", map.display());
+            }
+            Err(e) => {
+                eprintln!("Error creating sprite sheet: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "tiles" {
+        // Deep Zoom / map tile pyramid
+        if args.len() != 4 {
+            eprintln!("Error: tiles mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_path = Path::new(&args[2]);
+        let output_dir = Path::new(&args[3]);
+
+        if !is_stdio(input_path) && !remote::is_url(&args[2]) && !input_path.exists() {
+            eprintln!("Error: Input file does not exist: {}", input_path.display());
+            std::process::exit(1);
+        }
+
+        let target_format = options.to.unwrap_or(SupportedFormat::Jpeg);
+        match tiles::generate(&converter, input_path, output_dir, target_format, options.tiles) {
+            Ok(count) => println!("Created: {} ({} tiles)", output_dir.display(), count),
+            Err(e) => {
+                eprintln!("Error creating tiles: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "stitch" {
+        // Tiles back into one image
+        if args.len() != 4 {
+            eprintln!("Error: stitch mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input = Path::new(&args[2]);
+        let output_path = Path::new(&args[3]);
+
+        if !input.exists() {
+            eprintln!("Error: Input does not exist: {}", input.display());
+            std::process::exit(1);
+        }
+
+        let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+        let target_format = match options.to.map(Ok).unwrap_or_else(|| SupportedFormat::from_extension(&extension)) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let result = stitch::stitch(&converter, input).and_then(|image| {
+            let image = converter.process(image);
+            converter.save_image(&image, &[], output_path, target_format)?;
+            Ok(image)
+        });
+        match result {
+            Ok(image) => println!("Created: {} ({}x{})", output_path.display(), image.width(), image.height()),
+            Err(e) => {
+                eprintln!("Error stitching tiles: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args[1] == "thumbnail" {
+        // Small previews: reduced JPEG decodes, fast resizing, lower quality
+        if args.len() != 4 {
+            eprintln!("Error: thumbnail mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input = Path::new(&args[2]);
+        let output = Path::new(&args[3]);
+        let converter = converter.with_quality(thumbnail::QUALITY);
+
+        if !is_stdio(input) && !remote::is_url(&args[2]) && !input.exists() {
+            eprintln!("Error: Input does not exist: {}", input.display());
+            std::process::exit(1);
+        }
+
+        let result = if input.is_dir() {
+            let target_format = options.to.unwrap_or(SupportedFormat::Jpeg);
+            thumbnail::generate_all(&converter, input, output, target_format, options.thumb_size).map(|_| ())
+        } else {
+            let extension = output.extension().unwrap_or_default().to_string_lossy();
+            let target_format = match options.to.map(Ok).unwrap_or_else(|| SupportedFormat::from_extension(&extension)) {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            thumbnail::generate(&converter, input, output, target_format, options.thumb_size).map(|conversion| {
+                if options.json {
+                    report::emit(&conversion.to_json());
+                } else {
+                    println!("Created: {}", output.display());
+                }
+            })
+        };
+        if let Err(e) = result {
+            eprintln!("Error generating thumbnails: {}", e);
+            std::process::exit(1);
+        }
+    } else if args[1] == "to-pdf" {
+        // Multi-page PDF
+        if args.len() != 4 {
+            eprintln!("Error: to-pdf mode requires 3 arguments");
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input = Path::new(&args[2]);
+        let output_path = Path::new(&args[3]);
+
+        if !input.exists() {
+            eprintln!("Error: Input does not exist: {}", input.display());
+            std::process::exit(1);
+        }
+
+        let layout = pdf::Layout {
+            size: options.page_size,
+            margin: options.page_margin,
+            dpi: options.raster.dpi,
+        };
+        match converter.images_to_pdf(input, output_path, layout) {
+            Ok(pages) => println!("Created: {} ({} pages)", output_path.display(), pages),
+            Err(e) => {
+                eprintln!("Error creating PDF: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        // Single file mode
+        let arguments = if options.in_place { 1 } else { 2 };
+        if args.len() != arguments + 1 {
+            eprintln!("Error: Single file mode requires {} arguments", arguments);
+            print_usage();
+            std::process::exit(1);
+        }
+
+        let input_path = Path::new(&args[1]);
+
+        let is_url = remote::is_url(&args[1]);
+        if !is_stdio(input_path) && !is_url && !input_path.exists() {
+            eprintln!("Error: Input file does not exist: {}", input_path.display());
+            std::process::exit(1);
+        }
+
+        // In place, the output is the input under the new extension.
+        let in_place_output;
+        let output_path = if options.in_place {
+            if is_stdio(input_path) || is_url {
+                eprintln!("Error: --in-place requires a local input file");
+                std::process::exit(1);
+            }
+            let Some(format) = options.to.or(options.default_format) else {
+                eprintln!("Error: --in-place requires --to <format>");
+                std::process::exit(1);
+            };
+            in_place_output = input_path.with_extension(format.extension());
+            in_place_output.as_path()
+        } else {
+            Path::new(&args[2])
+        };
+
+        let target_format = match options.to {
+            Some(format) => format,
+            None if is_stdio(output_path) => match options.default_format {
+                Some(format) => format,
+                None => {
+                    eprintln!("Error: Writing to stdout requires --to <format>");
+                    std::process::exit(1);
+                }
+            },
+            None => match output_path.extension() {
+                Some(ext) => match SupportedFormat::from_extension(&ext.to_string_lossy()) {
+                    Ok(format) => format,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+                None => {
+                    eprintln!("Error: Output file must have a valid extension");
+                    std::process::exit(1);
+                }
+            },
+        };
+
+        if !is_stdio(output_path) && converter.is_up_to_date(input_path, output_path, target_format) {
+            if options.json {
+                report::emit(&report::skipped(input_path, "up to date"));
+            } else {
+                println!("Up to date: {}", output_path.display());
+            }
+        } else if options.dry_run {
+            dry_run::Plan::single(&converter, input_path, output_path, target_format).print(options.json);
+        } else if let Some(in_place) = &converter.in_place {
+            let root = input_path.parent().unwrap_or(Path::new(""));
+            match converter.convert_in_place(in_place, root, input_path, output_path, target_format) {
+                Ok(conversion) if options.json => report::emit(&conversion.to_json()),
+                Ok(conversion) => {
+                    for path in &conversion.outputs {
+                        println!("Created: {}", path.display());
+                    }
+                    match &in_place.backup_dir {
+                        Some(dir) => println!("Moved original to: {}", dir.display()),
+                        None if !conversion.outputs.iter().any(|path| path == input_path) => {
+                            println!("Removed original: {}", input_path.display());
+                        }
+                        None => {}
+                    }
+                }
+                Err(e) if options.json => {
+                    report::emit(&report::failure(input_path, &e));
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Error during conversion: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        } else if options.json {
+            if is_stdio(output_path) {
+                eprintln!("Error: --json prints to stdout, so the image can't be written there");
+                std::process::exit(1);
+            }
+            match converter.convert_file(input_path, output_path, target_format) {
+                Ok(conversion) => report::emit(&conversion.to_json()),
+                Err(e) => {
+                    report::emit(&report::failure(input_path, &e));
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            let source = converter.preserve.source(input_path);
+            let outputs = match converter.convert(input_path, output_path, target_format) {
+                Ok(outputs) => outputs,
+                Err(e) => {
+                    eprintln!("Error during conversion: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = converter.preserve_metadata(source.as_ref(), &outputs) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            match converter.delete_original(input_path, &outputs) {
+                Ok(true) => println!("Deleted original: {}", input_path.display()),
+                Ok(false) => {}
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir for one test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("image-converter-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn in_place_batch_leaves_unrelated_target_files_alone() {
+        let dir = scratch_dir("in-place");
+        DynamicImage::new_rgb8(4, 4).save(dir.join("photo.png")).unwrap();
+        std::fs::write(dir.join("photo.webp"), b"unrelated").unwrap();
+
+        let converter = ImageConverter::new(DEFAULT_QUALITY).with_in_place(Some(in_place::InPlace::default()));
+        converter.batch_convert(&dir, &dir, SupportedFormat::WebP).unwrap();
+
+        assert_eq!(std::fs::read(dir.join("photo.webp")).unwrap(), b"unrelated");
+        assert!(dir.join("photo.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
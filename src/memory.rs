@@ -0,0 +1,69 @@
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+
+use crate::input_filter;
+
+/// Bytes per pixel converting an image takes at its peak: the decoded
+/// image, the processed copy and the encoder's working buffer, each as
+/// 8-bit RGBA.
+const BYTES_PER_PIXEL: u64 = 3 * 4;
+
+/// For inputs whose size can't be read from the header (SVG, PDF, HEIC,
+/// camera RAW, URLs): the estimate as a multiple of the file size.
+const FILE_SIZE_FACTOR: u64 = 20;
+
+/// `--max-memory`: a cap on the memory batch conversions running at once
+/// are expected to need. Each input reserves its estimate before it
+/// starts and waits while the others would leave too little, so a folder
+/// of panoramas converts a few at a time while small files still run on
+/// every core.
+pub struct Budget {
+    limit: u64,
+    reserved: Mutex<u64>,
+    released: Condvar,
+}
+
+/// Memory held for one conversion, given back when dropped.
+pub struct Reservation<'a> {
+    budget: &'a Budget,
+    bytes: u64,
+}
+
+impl Budget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            reserved: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until `bytes` fit in what's left of the budget and holds them.
+    /// An input needing more than the whole budget waits until nothing else
+    /// runs and then runs alone.
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let bytes = bytes.min(self.limit);
+        let mut reserved = self.reserved.lock().unwrap();
+        while *reserved + bytes > self.limit {
+            reserved = self.released.wait(reserved).unwrap();
+        }
+        *reserved += bytes;
+        Reservation { budget: self, bytes }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.reserved.lock().unwrap() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// Rough peak memory for converting `input`, from the image size in its
+/// header, or failing that from the file size.
+pub fn estimate(input: &Path) -> u64 {
+    if let Some((width, height)) = input_filter::header_dimensions(input) {
+        return u64::from(width) * u64::from(height) * BYTES_PER_PIXEL;
+    }
+    std::fs::metadata(input).map_or(0, |metadata| metadata.len()) * FILE_SIZE_FACTOR
+}
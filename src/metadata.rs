@@ -0,0 +1,367 @@
+use std::io::Cursor;
+
+use image::DynamicImage;
+use img_parts::jpeg::{markers, JpegSegment};
+use img_parts::png::PngChunk;
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+
+/// Reads the EXIF Orientation tag (1-8) from an encoded image, if present.
+pub fn read_orientation(data: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()?;
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+}
+
+/// Rotates/flips `image` so that it displays upright for the given EXIF
+/// orientation value. Unknown values leave the image untouched.
+pub fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+const XMP_JPEG_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+
+/// Metadata blocks lifted from a source image so they can be re-embedded
+/// into the converted output.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// Raw TIFF-structured EXIF payload, without the JPEG `Exif\0\0` prefix.
+    pub exif: Option<Vec<u8>>,
+    /// XMP packet (UTF-8 XML).
+    pub xmp: Option<Vec<u8>>,
+    /// Embedded ICC colour profile.
+    pub icc: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.xmp.is_none() && self.icc.is_none()
+    }
+
+    /// Names of the blocks present, for messages.
+    pub fn kinds(&self) -> Vec<&'static str> {
+        [(self.exif.is_some(), "EXIF"), (self.xmp.is_some(), "XMP"), (self.icc.is_some(), "ICC profile")]
+            .into_iter()
+            .filter_map(|(present, name)| present.then_some(name))
+            .collect()
+    }
+
+    /// Reads EXIF, XMP and the ICC profile from a JPEG, PNG or WebP file. Other containers
+    /// yield empty metadata.
+    pub fn extract(data: &[u8]) -> Self {
+        let Ok(Some(image)) = DynImage::from_bytes(Bytes::copy_from_slice(data)) else {
+            return Self::default();
+        };
+
+        let xmp = match &image {
+            DynImage::Jpeg(jpeg) => jpeg
+                .segments_by_marker(markers::APP1)
+                .find_map(|segment| segment.contents().strip_prefix(XMP_JPEG_PREFIX))
+                .map(<[u8]>::to_vec),
+            DynImage::Png(png) => png
+                .chunks_by_type(*b"iTXt")
+                .find_map(|chunk| png_itxt_xmp(chunk.contents())),
+            DynImage::WebP(_) => webp_chunks(data)
+                .and_then(|chunks| chunks.into_iter().find(|(id, _)| id == b"XMP "))
+                .map(|(_, body)| body.to_vec()),
+        };
+
+        Self {
+            exif: image.exif().map(|exif| exif.to_vec()),
+            xmp,
+            icc: image.icc_profile().map(|icc| icc.to_vec()),
+        }
+    }
+
+    /// Marks the EXIF orientation as "normal" after the pixels themselves
+    /// have been rotated upright.
+    pub fn reset_orientation(&mut self) {
+        if let Some(exif) = &mut self.exif {
+            set_tiff_short(exif, EXIF_ORIENTATION_TAG, 1);
+        }
+    }
+
+    /// Embeds the metadata blocks that are present into an encoded JPEG, PNG or WebP file. Returns
+    /// `None` when the container isn't one of those.
+    pub fn embed(&self, encoded: &[u8]) -> Option<Vec<u8>> {
+        if webp_chunks(encoded).is_some() {
+            return rebuild_webp(encoded, self.icc.as_deref(), self.exif.as_deref(), self.xmp.as_deref());
+        }
+
+        let mut image = DynImage::from_bytes(Bytes::copy_from_slice(encoded)).ok()??;
+        image.set_exif(self.exif.clone().map(Bytes::from));
+        image.set_icc_profile(self.icc.clone().map(Bytes::from));
+
+        if let Some(xmp) = &self.xmp {
+            match &mut image {
+                DynImage::Jpeg(jpeg) => {
+                    let contents = [XMP_JPEG_PREFIX, xmp].concat();
+                    let segments = jpeg.segments_mut();
+                    let position = segments
+                        .iter()
+                        .rposition(|segment| matches!(segment.marker(), markers::APP0 | markers::APP1))
+                        .map_or(0, |position| position + 1);
+                    segments.insert(position, JpegSegment::new_with_contents(markers::APP1, contents.into()));
+                }
+                DynImage::Png(png) => {
+                    // keyword, NUL, uncompressed, method 0, empty language and translated keyword
+                    let contents = [XMP_PNG_KEYWORD, b"\0\0\0\0\0", xmp].concat();
+                    let chunks = png.chunks_mut();
+                    chunks.insert(chunks.len() - 1, PngChunk::new(*b"iTXt", contents.into()));
+                }
+                DynImage::WebP(_) => unreachable!("WebP is handled above"),
+            }
+        }
+
+        let mut output = Vec::with_capacity(encoded.len());
+        image.encoder().write_to(&mut output).ok()?;
+        Some(output)
+    }
+}
+
+/// Lists the kinds of metadata present in an encoded image, for reporting
+/// what `--strip` removed.
+pub fn describe(data: &[u8]) -> Vec<&'static str> {
+    let mut found = Vec::new();
+
+    if let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(data)) {
+        found.push("EXIF");
+        if exif.fields().any(|field| field.tag.context() == exif::Context::Gps) {
+            found.push("GPS");
+        }
+        if exif.fields().any(|field| field.ifd_num == exif::In::THUMBNAIL) {
+            found.push("thumbnail");
+        }
+    }
+
+    let metadata = Metadata::extract(data);
+    if metadata.xmp.is_some() {
+        found.push("XMP");
+    }
+    if metadata.icc.is_some() {
+        found.push("ICC profile");
+    }
+
+    found
+}
+
+/// Removes every EXIF, XMP, ICC and text block from an encoded JPEG, PNG or
+/// WebP file. Other containers are returned unchanged; the AVIF encoder never
+/// writes metadata in the first place.
+pub fn strip(encoded: Vec<u8>) -> Vec<u8> {
+    if webp_chunks(&encoded).is_some() {
+        return rebuild_webp(&encoded, None, None, None).unwrap_or(encoded);
+    }
+
+    let Ok(Some(mut image)) = DynImage::from_bytes(Bytes::copy_from_slice(&encoded)) else {
+        return encoded;
+    };
+    image.set_exif(None);
+    image.set_icc_profile(None);
+    match &mut image {
+        DynImage::Jpeg(jpeg) => {
+            jpeg.segments_mut().retain(|segment| {
+                !(segment.marker() == markers::APP1 || segment.marker() == markers::COM)
+            });
+        }
+        DynImage::Png(png) => {
+            png.chunks_mut()
+                .retain(|chunk| !matches!(&chunk.kind(), b"tEXt" | b"zTXt" | b"iTXt" | b"tIME"));
+        }
+        DynImage::WebP(_) => {}
+    }
+
+    let mut output = Vec::with_capacity(encoded.len());
+    match image.encoder().write_to(&mut output) {
+        Ok(_) => output,
+        Err(_) => encoded,
+    }
+}
+
+/// Returns the XMP text of an uncompressed `XML:com.adobe.xmp` iTXt chunk.
+fn png_itxt_xmp(contents: &[u8]) -> Option<Vec<u8>> {
+    let rest = contents.strip_prefix(XMP_PNG_KEYWORD)?.strip_prefix(b"\0")?;
+    let (&compressed, rest) = rest.split_first()?;
+    if compressed != 0 {
+        return None;
+    }
+    // Skip the compression method, language tag and translated keyword.
+    let rest = rest.get(1..)?;
+    let rest = rest.get(rest.iter().position(|&b| b == 0)? + 1..)?;
+    let rest = rest.get(rest.iter().position(|&b| b == 0)? + 1..)?;
+    Some(rest.to_vec())
+}
+
+/// Overwrites a SHORT-typed tag in IFD0 of a TIFF-structured EXIF block.
+fn set_tiff_short(tiff: &mut [u8], tag: u16, value: u16) {
+    let big_endian = match tiff.get(..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return,
+    };
+    let read_u16 = |bytes: &[u8], at: usize| -> Option<u16> {
+        let raw = [*bytes.get(at)?, *bytes.get(at + 1)?];
+        Some(if big_endian { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) })
+    };
+    let read_u32 = |bytes: &[u8], at: usize| -> Option<u32> {
+        let raw: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(raw) } else { u32::from_le_bytes(raw) })
+    };
+
+    let Some(ifd) = read_u32(tiff, 4).map(|offset| offset as usize) else {
+        return;
+    };
+    let Some(count) = read_u16(tiff, ifd) else {
+        return;
+    };
+
+    for index in 0..count as usize {
+        let entry = ifd + 2 + index * 12;
+        // Type 3 is SHORT; its value is stored inline in the first two bytes.
+        if read_u16(tiff, entry) == Some(tag) && read_u16(tiff, entry + 2) == Some(3) {
+            if let Some(slot) = tiff.get_mut(entry + 8..entry + 10) {
+                let bytes = if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+                slot.copy_from_slice(&bytes);
+            }
+            return;
+        }
+    }
+}
+
+/// Splits a WebP file into its top-level RIFF chunks.
+pub fn webp_chunks(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id: [u8; 4] = data[offset..offset + 4].try_into().ok()?;
+        let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = data.get(offset + 8..offset + 8 + size)?;
+        chunks.push((id, body));
+        offset += 8 + size + (size & 1);
+    }
+    Some(chunks)
+}
+
+/// Rebuilds a WebP file as the extended (VP8X) format carrying exactly the
+/// given metadata chunks; any existing ICCP/EXIF/XMP chunks are dropped.
+fn rebuild_webp(
+    encoded: &[u8],
+    icc: Option<&[u8]>,
+    exif: Option<&[u8]>,
+    xmp: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    let chunks = webp_chunks(encoded)?;
+    let find = |wanted: &[u8; 4]| chunks.iter().find(|(id, _)| id == wanted).map(|(_, body)| *body);
+
+    let (width, height, mut flags) = match find(b"VP8X") {
+        Some(vp8x) if vp8x.len() >= 10 => {
+            let read_u24 = |at: usize| u32::from_le_bytes([vp8x[at], vp8x[at + 1], vp8x[at + 2], 0]);
+            (read_u24(4) + 1, read_u24(7) + 1, vp8x[0])
+        }
+        _ => {
+            let (width, height, alpha) = webp_bitstream_info(&chunks)?;
+            (width, height, if alpha { 0x10 } else { 0 })
+        }
+    };
+
+    for (present, bit) in [(icc.is_some(), 0x20), (exif.is_some(), 0x08), (xmp.is_some(), 0x04)] {
+        if present {
+            flags |= bit;
+        } else {
+            flags &= !bit;
+        }
+    }
+
+    let mut vp8x = vec![flags, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    // Chunk order mandated by the spec: VP8X, ICCP, image data, EXIF, XMP.
+    let mut ordered: Vec<(&[u8; 4], &[u8])> = vec![(b"VP8X", &vp8x)];
+    ordered.extend(icc.map(|body| (b"ICCP", body)));
+    ordered.extend(
+        chunks
+            .iter()
+            .filter(|(id, _)| !matches!(id, b"VP8X" | b"ICCP" | b"EXIF" | b"XMP "))
+            .map(|(id, body)| (id, *body)),
+    );
+    ordered.extend(exif.map(|body| (b"EXIF", body)));
+    ordered.extend(xmp.map(|body| (b"XMP ", body)));
+
+    Some(webp_container(&ordered))
+}
+
+/// Wraps `chunks` in a RIFF/WEBP header, padding odd-sized bodies.
+pub fn webp_container(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut output = b"RIFF\0\0\0\0WEBP".to_vec();
+    for (id, body) in chunks {
+        output.extend_from_slice(*id);
+        output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        output.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            output.push(0);
+        }
+    }
+    let riff_size = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    output
+}
+
+/// Canvas size and alpha usage of a simple (non-VP8X) WebP bitstream.
+fn webp_bitstream_info(chunks: &[([u8; 4], &[u8])]) -> Option<(u32, u32, bool)> {
+    for (id, body) in chunks {
+        match id {
+            b"VP8L" if body.len() >= 5 => {
+                let bits = u32::from_le_bytes(body[1..5].try_into().ok()?);
+                let width = (bits & 0x3FFF) + 1;
+                let height = ((bits >> 14) & 0x3FFF) + 1;
+                return Some((width, height, bits >> 28 & 1 == 1));
+            }
+            b"VP8 " if body.len() >= 10 => {
+                let width = u16::from_le_bytes([body[6], body[7]]) as u32 & 0x3FFF;
+                let height = u16::from_le_bytes([body[8], body[9]]) as u32 & 0x3FFF;
+                return Some((width, height, false));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_xmp_itxt_chunk_is_ignored() {
+        let mut chunk = XMP_PNG_KEYWORD.to_vec();
+        chunk.extend_from_slice(b"\0\0");
+        assert_eq!(png_itxt_xmp(&chunk), None);
+        chunk.push(0);
+        assert_eq!(png_itxt_xmp(&chunk), None);
+    }
+
+    #[test]
+    fn xmp_itxt_chunk_text_is_read() {
+        let mut chunk = XMP_PNG_KEYWORD.to_vec();
+        chunk.extend_from_slice(b"\0\0\0en\0\0<x:xmpmeta/>");
+        assert_eq!(png_itxt_xmp(&chunk).as_deref(), Some(&b"<x:xmpmeta/>"[..]));
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+
+/// What to do when an output file is already there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Replace it.
+    #[default]
+    Overwrite,
+    /// Leave it be and fail the input.
+    Fail,
+    /// Write next to it under the first free numbered name, `photo-1.webp`,
+    /// `photo-2.webp` and so on.
+    Rename,
+}
+
+impl Policy {
+    /// Picks where to write `output_path` given which candidate paths are
+    /// `taken`, either on disk or by another input of the same run.
+    pub fn resolve(self, output_path: &Path, taken: impl Fn(&Path) -> bool) -> Result<PathBuf, String> {
+        if self == Policy::Overwrite || !taken(output_path) {
+            return Ok(output_path.to_path_buf());
+        }
+        if self == Policy::Fail {
+            return Err(format!(
+                "{} already exists (use --overwrite to replace it or --rename-on-conflict to keep both)",
+                output_path.display()
+            ));
+        }
+
+        let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = output_path.extension().map(|extension| format!(".{}", extension.to_string_lossy()));
+        (1..)
+            .map(|number| output_path.with_file_name(format!("{}-{}{}", stem, number, extension.as_deref().unwrap_or(""))))
+            .find(|candidate| !taken(candidate))
+            .ok_or_else(|| format!("no free name for {}", output_path.display()))
+    }
+
+    /// How a dry run notes an output that already exists.
+    pub fn describe_existing(self) -> &'static str {
+        match self {
+            Policy::Overwrite => "overwrites existing file",
+            Policy::Fail => "exists, so the input fails",
+            Policy::Rename => "exists, so a numbered name is used",
+        }
+    }
+}
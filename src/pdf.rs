@@ -0,0 +1,236 @@
+use std::path::PathBuf;
+
+use hayro::hayro_interpret::InterpreterSettings;
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{PixmapSettings, RenderCache, RenderSettings};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat, RgbaImage};
+use miniz_oxide::deflate::{compress_to_vec_zlib, CompressionLevel};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref};
+
+use crate::raster::Raster;
+use crate::{metadata, ImageConverter};
+
+/// PDF user space units (points) per inch.
+const POINTS_PER_INCH: f32 = 72.0;
+
+fn error(message: impl Into<String>) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Name("PDF".to_string()), message.into()))
+}
+
+/// Whether `data` is a PDF. The header may be preceded by junk, which
+/// readers are required to tolerate within the first kilobyte.
+pub fn is_pdf(data: &[u8]) -> bool {
+    data[..data.len().min(1024)].windows(5).any(|window| window == b"%PDF-")
+}
+
+/// A parsed PDF whose pages can be rendered one at a time.
+pub struct Document {
+    pdf: hayro::hayro_syntax::Pdf,
+}
+
+impl Document {
+    pub fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        let pdf = hayro::hayro_syntax::Pdf::new(data.to_vec()).map_err(|e| error(format!("{:?}", e)))?;
+        Ok(Self { pdf })
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pdf.pages().len()
+    }
+
+    /// Renders page `index` (0-based) onto a white background.
+    pub fn render(&self, index: usize, raster: Raster) -> Result<DynamicImage, ImageError> {
+        let pages = self.pdf.pages();
+        let page = pages
+            .get(index)
+            .ok_or_else(|| error(format!("page {} not found ({} pages)", index + 1, pages.len())))?;
+
+        let (width, height) = page.render_dimensions();
+        let (x_scale, y_scale) = raster.scale(width, height, POINTS_PER_INCH);
+        if width * x_scale > f32::from(u16::MAX) || height * y_scale > f32::from(u16::MAX) {
+            return Err(error(format!("page {} is too large to render at this size", index + 1)));
+        }
+
+        let pixmap = hayro::render(
+            page,
+            &RenderCache::new(),
+            &InterpreterSettings::default(),
+            &RenderSettings::default(),
+            &PixmapSettings {
+                x_scale,
+                y_scale,
+                bg_color: WHITE,
+            },
+        );
+
+        // The background is opaque, so premultiplied and straight alpha agree.
+        let (width, height) = (u32::from(pixmap.width()), u32::from(pixmap.height()));
+        let image = RgbaImage::from_raw(width, height, pixmap.data_as_u8_slice().to_vec())
+            .ok_or_else(|| error("rendered page size mismatch"))?;
+        Ok(DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()))
+    }
+}
+
+/// Output page size for `to-pdf`, in points.
+#[derive(Debug, Clone, Copy)]
+pub enum PageSize {
+    /// Each page takes the size of its image at the chosen DPI.
+    Fit,
+    Fixed(f32, f32),
+}
+
+impl PageSize {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "fit" => Ok(PageSize::Fit),
+            "a3" => Ok(PageSize::Fixed(842.0, 1191.0)),
+            "a4" => Ok(PageSize::Fixed(595.0, 842.0)),
+            "a5" => Ok(PageSize::Fixed(420.0, 595.0)),
+            "letter" => Ok(PageSize::Fixed(612.0, 792.0)),
+            "legal" => Ok(PageSize::Fixed(612.0, 1008.0)),
+            _ => Err(format!("Invalid page size: {} (expected a3, a4, a5, letter, legal or fit)", value)),
+        }
+    }
+}
+
+/// Page geometry for `to-pdf`. Images are placed at `dpi`, shrunk to fit
+/// inside the margins when needed, and centered on the page.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub size: PageSize,
+    /// Margin on every side, in points.
+    pub margin: f32,
+    pub dpi: f32,
+}
+
+/// Builds a PDF with one page per input, each run through the converter's
+/// pipeline. Baseline JPEGs that the pipeline leaves untouched are embedded
+/// as-is; everything else is stored losslessly (Flate), with alpha kept as a
+/// soft mask.
+pub fn write_document(
+    converter: &ImageConverter,
+    inputs: &[PathBuf],
+    layout: Layout,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let mut next_id = 3;
+    let mut alloc = || {
+        next_id += 1;
+        Ref::new(next_id - 1)
+    };
+
+    let mut page_ids = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let data = converter.read_input(input)?;
+        let image = converter.process(
+            converter
+                .decode_image(input, &data)
+                .map_err(|e| format!("{}: {}", input.display(), e))?,
+        );
+        let (pixel_width, pixel_height) = (image.width(), image.height());
+
+        let (page_id, content_id, image_id) = (alloc(), alloc(), alloc());
+        page_ids.push(page_id);
+
+        // Natural size at the chosen DPI, shrunk to the printable area.
+        let natural = (
+            pixel_width as f32 * POINTS_PER_INCH / layout.dpi,
+            pixel_height as f32 * POINTS_PER_INCH / layout.dpi,
+        );
+        let (page_width, page_height) = match layout.size {
+            PageSize::Fit => (natural.0 + 2.0 * layout.margin, natural.1 + 2.0 * layout.margin),
+            PageSize::Fixed(width, height) => (width, height),
+        };
+        let area = (
+            (page_width - 2.0 * layout.margin).max(1.0),
+            (page_height - 2.0 * layout.margin).max(1.0),
+        );
+        let scale = (area.0 / natural.0).min(area.1 / natural.1).min(1.0);
+        let (width, height) = (natural.0 * scale, natural.1 * scale);
+        let (x, y) = ((page_width - width) / 2.0, (page_height - height) / 2.0);
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, page_width, page_height));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(Name(b"Im1"), image_id);
+        page.finish();
+
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([width, 0.0, 0.0, height, x, y]);
+        content.x_object(Name(b"Im1"));
+        content.restore_state();
+        pdf.stream(content_id, &content.finish());
+
+        let untouched = converter.pipeline.is_empty()
+            && !converter.to_srgb
+            && matches!(metadata::read_orientation(&data), None | Some(1));
+        let gray = !image.color().has_color();
+        let passthrough = untouched
+            && image::guess_format(&data).ok() == Some(ImageFormat::Jpeg)
+            && jpeg_components(&data) == Some(if gray { 1 } else { 3 });
+
+        let level = CompressionLevel::DefaultLevel as u8;
+        let (filter, encoded) = if passthrough {
+            (Filter::DctDecode, data)
+        } else if gray {
+            (Filter::FlateDecode, compress_to_vec_zlib(image.to_luma8().as_raw(), level))
+        } else {
+            (Filter::FlateDecode, compress_to_vec_zlib(image.to_rgb8().as_raw(), level))
+        };
+        let mask = (!passthrough && image.color().has_alpha()).then(|| {
+            let alpha: Vec<u8> = image.to_rgba8().pixels().map(|pixel| pixel[3]).collect();
+            (alloc(), compress_to_vec_zlib(&alpha, level))
+        });
+
+        let mut xobject = pdf.image_xobject(image_id, &encoded);
+        xobject.filter(filter);
+        xobject.width(pixel_width as i32);
+        xobject.height(pixel_height as i32);
+        if gray {
+            xobject.color_space().device_gray();
+        } else {
+            xobject.color_space().device_rgb();
+        }
+        xobject.bits_per_component(8);
+        if let Some((mask_id, _)) = &mask {
+            xobject.s_mask(*mask_id);
+        }
+        xobject.finish();
+
+        if let Some((mask_id, alpha)) = &mask {
+            let mut s_mask = pdf.image_xobject(*mask_id, alpha);
+            s_mask.filter(Filter::FlateDecode);
+            s_mask.width(pixel_width as i32);
+            s_mask.height(pixel_height as i32);
+            s_mask.color_space().device_gray();
+            s_mask.bits_per_component(8);
+        }
+    }
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).count(page_ids.len() as i32).kids(page_ids);
+    Ok(pdf.finish())
+}
+
+/// Number of color components in a JPEG's frame header.
+fn jpeg_components(data: &[u8]) -> Option<u8> {
+    let mut offset = 2;
+    while offset + 4 <= data.len() {
+        if data[offset] != 0xFF {
+            return None;
+        }
+        let marker = data[offset + 1];
+        let length = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC).
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return data.get(offset + 9).copied();
+        }
+        offset += 2 + length;
+    }
+    None
+}
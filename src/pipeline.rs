@@ -0,0 +1,234 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::DynamicImage;
+
+use crate::adjust::{Curves, Effect, Hue, Illuminant, Levels, Tone, WhiteBalance};
+use crate::deskew::Deskew;
+use crate::equalize::Equalize;
+use crate::filter::{Blur, Denoise, Sharpen};
+use crate::lut::Lut;
+use crate::transform::{parse_color, AspectRatio, Crop, Flip, Pad, Resize, ResizeSpec, Rotation, SmartCrop, Transform, Trim};
+use crate::{parse_percent_adjustment, CliOptions};
+
+/// One step between decoding and encoding.
+pub trait Operation: fmt::Debug + Send + Sync {
+    fn apply(&self, image: DynamicImage) -> DynamicImage;
+}
+
+impl Operation for Transform {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        Transform::apply(self, image)
+    }
+}
+
+/// Operations run in order on every decoded image.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    operations: Vec<Arc<dyn Operation>>,
+}
+
+impl Pipeline {
+    pub fn push(&mut self, operation: impl Operation + 'static) {
+        self.operations.push(Arc::new(operation));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    pub fn run(&self, image: DynamicImage) -> DynamicImage {
+        self.operations.iter().fold(image, |image, operation| operation.apply(image))
+    }
+}
+
+impl FromIterator<Transform> for Pipeline {
+    fn from_iter<I: IntoIterator<Item = Transform>>(transforms: I) -> Self {
+        let mut pipeline = Pipeline::default();
+        for transform in transforms {
+            pipeline.push(transform);
+        }
+        pipeline
+    }
+}
+
+/// Operation names `--ops` accepts.
+const OPERATIONS: [&str; 35] = [
+    "autorotate",
+    "strip",
+    "deskew",
+    "trim",
+    "rotate",
+    "flip",
+    "crop",
+    "crop-center",
+    "smart-crop",
+    "denoise",
+    "resize",
+    "white-balance",
+    "temperature",
+    "tint",
+    "auto-level",
+    "auto-contrast",
+    "equalize",
+    "clahe",
+    "brightness",
+    "contrast",
+    "gamma",
+    "curve",
+    "saturation",
+    "hue-rotate",
+    "vibrance",
+    "lut",
+    "filter",
+    "sepia",
+    "invert",
+    "posterize",
+    "duotone",
+    "blur",
+    "sharpen",
+    "pad",
+    "grayscale",
+];
+
+/// Operations whose values contain commas themselves, as in `sharpen=0.6,1,2`:
+/// after one of these, a part that isn't an operation name continues the value.
+const COMMA_VALUES: [&str; 5] = ["sharpen", "duotone", "curve", "clahe", "filter"];
+
+/// Parses an `--ops` list such as `autorotate,trim,resize=1600x,sharpen=0.6,strip`
+/// into transforms run in the order given. `autorotate` and `strip` are
+/// decode- and encode-time settings and are switched on in `options`
+/// instead.
+pub fn parse_ops(spec: &str, options: &mut CliOptions) -> Result<Vec<Transform>, String> {
+    let mut steps: Vec<(String, Option<String>)> = Vec::new();
+    for part in spec.split(',') {
+        let (name, value) = match part.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (part.trim(), None),
+        };
+        match steps.last_mut() {
+            Some((previous_name, Some(previous)))
+                if COMMA_VALUES.contains(&previous_name.as_str()) && !OPERATIONS.contains(&name) =>
+            {
+                previous.push(',');
+                previous.push_str(part.trim());
+            }
+            _ if name.is_empty() => {}
+            _ => steps.push((name.to_string(), value.map(str::to_string))),
+        }
+    }
+
+    let mut transforms = Vec::new();
+    for (name, value) in steps {
+        let required = || value.clone().ok_or_else(|| format!("Operation {} needs a value ({}=...)", name, name));
+        let number = |range: RangeInclusive<f32>, expected: &str| -> Result<f32, String> {
+            let value = required()?;
+            value
+                .parse()
+                .ok()
+                .filter(|number| range.contains(number))
+                .ok_or_else(|| format!("Invalid value for {}: {} (expected {})", name, value, expected))
+        };
+        let percent = || parse_percent_adjustment(&name, &required()?);
+
+        let transform = match name.as_str() {
+            "autorotate" => {
+                options.no_auto_orient = false;
+                continue;
+            }
+            "strip" => {
+                options.strip = true;
+                continue;
+            }
+            "deskew" => Transform::Deskew(Deskew {
+                color: value.as_deref().map(parse_color).transpose()?.unwrap_or(options.deskew_color),
+            }),
+            "trim" => Transform::Trim(Trim {
+                tolerance: match &value {
+                    Some(tolerance) => tolerance
+                        .parse()
+                        .map_err(|_| format!("Invalid value for trim: {} (expected 0-255)", tolerance))?,
+                    None => 0,
+                },
+            }),
+            "rotate" => Transform::Rotate(Rotation::parse(&required()?)?),
+            "flip" => Transform::Flip(Flip::parse(&required()?)?),
+            "crop" => Transform::Crop(Crop::parse(&required()?)?),
+            "crop-center" => Transform::Crop(Crop::parse_center(&required()?)?),
+            "smart-crop" => Transform::SmartCrop(SmartCrop {
+                ratio: AspectRatio::parse(&required()?)?,
+            }),
+            "denoise" => Transform::Denoise(Denoise { strength: number(0.0..=100.0, "0-100")? }),
+            "resize" => Transform::Resize(Resize {
+                spec: ResizeSpec::parse(&required()?)?,
+                mode: options.resize_mode,
+                shrink_only: options.shrink_only,
+            }),
+            "white-balance" => Transform::WhiteBalance(WhiteBalance {
+                auto: Some(Illuminant::parse(value.as_deref().unwrap_or("auto"))?),
+                ..WhiteBalance::default()
+            }),
+            "temperature" => Transform::WhiteBalance(WhiteBalance {
+                temperature: percent()?,
+                ..WhiteBalance::default()
+            }),
+            "tint" => Transform::WhiteBalance(WhiteBalance {
+                tint: percent()?,
+                ..WhiteBalance::default()
+            }),
+            "auto-level" | "auto-contrast" => Transform::Levels(Levels {
+                per_channel: name == "auto-level",
+                clip: if value.is_some() { number(0.0..=49.9, "a percentage below 50")? } else { options.level_clip },
+            }),
+            "equalize" => Transform::Equalize(Equalize::Global),
+            "clahe" => Transform::Equalize(Equalize::parse_clahe(value.as_deref().unwrap_or("8"))?),
+            "brightness" => Transform::Tone(Tone {
+                brightness: percent()?,
+                ..Tone::default()
+            }),
+            "contrast" => Transform::Tone(Tone {
+                contrast: percent()?,
+                ..Tone::default()
+            }),
+            "gamma" => Transform::Tone(Tone {
+                gamma: number(0.1..=10.0, "0.1-10")?,
+                ..Tone::default()
+            }),
+            "curve" => {
+                let mut curves = Curves::default();
+                curves.add(&required()?)?;
+                Transform::Curves(curves)
+            }
+            "saturation" => Transform::Hue(Hue {
+                saturation: percent()?,
+                ..Hue::default()
+            }),
+            "hue-rotate" => Transform::Hue(Hue {
+                rotate: number(f32::MIN..=f32::MAX, "degrees")?,
+                ..Hue::default()
+            }),
+            "vibrance" => Transform::Hue(Hue {
+                vibrance: percent()?,
+                ..Hue::default()
+            }),
+            "lut" => Transform::Lut(Arc::new(Lut::load(Path::new(&required()?))?)),
+            "filter" => Transform::Effect(Effect::parse(&required()?)?),
+            "sepia" | "invert" | "posterize" | "duotone" => Transform::Effect(Effect::parse(&match &value {
+                Some(value) => format!("{}={}", name, value),
+                None => name.clone(),
+            })?),
+            "blur" => Transform::Blur(Blur { sigma: number(f32::MIN_POSITIVE..=1000.0, "a sigma in pixels")? }),
+            "sharpen" => Transform::Sharpen(Sharpen::parse(value.as_deref().unwrap_or("1"))?),
+            "pad" => Transform::Pad(Pad {
+                ratio: AspectRatio::parse(&required()?)?,
+                color: options.pad_color,
+            }),
+            "grayscale" => Transform::Grayscale,
+            _ => return Err(format!("Unknown operation in --ops: {}", name)),
+        };
+        transforms.push(transform);
+    }
+    Ok(transforms)
+}
@@ -0,0 +1,66 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, ImageError};
+
+use crate::{metadata, ImageConverter, SupportedFormat};
+
+pub const DEFAULT_WIDTH: u32 = 20;
+pub const DEFAULT_SUFFIX: &str = "-placeholder";
+/// Placeholders are stretched across the full image's box, so compression
+/// artifacts barely show.
+pub const QUALITY: u8 = 30;
+
+/// A low-quality image placeholder (LQIP): a tiny preview written beside
+/// each output, for pages to show while the real image loads.
+#[derive(Debug, Clone)]
+pub struct Placeholder {
+    pub width: u32,
+    /// Inserted before the extension.
+    pub suffix: String,
+    /// Gaussian blur sigma in placeholder pixels, applied after shrinking.
+    pub blur: Option<f32>,
+}
+
+impl Default for Placeholder {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            suffix: DEFAULT_SUFFIX.to_string(),
+            blur: None,
+        }
+    }
+}
+
+impl Placeholder {
+    /// `photo.webp` gets `photo-placeholder.webp`.
+    pub fn path(&self, output_path: &Path) -> PathBuf {
+        let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+        match output_path.extension() {
+            Some(ext) => output_path.with_file_name(format!("{}{}.{}", stem, self.suffix, ext.to_string_lossy())),
+            None => output_path.with_file_name(format!("{}{}", stem, self.suffix)),
+        }
+    }
+
+    /// Shrinks `image` to the placeholder width and writes it next to
+    /// `output_path` in `format`, at low quality and without metadata.
+    pub fn write(
+        &self,
+        converter: &ImageConverter,
+        image: &DynamicImage,
+        output_path: &Path,
+        format: SupportedFormat,
+    ) -> Result<PathBuf, ImageError> {
+        let mut preview = image.thumbnail(self.width.min(image.width()), u32::MAX);
+        if let Some(sigma) = self.blur {
+            preview = preview.blur(sigma);
+        }
+
+        let path = self.path(output_path);
+        let preview = converter.flatten(&preview, format, &path);
+        let mut buffer = Cursor::new(Vec::new());
+        converter.write_image(&preview, &mut buffer, format, QUALITY)?;
+        ImageConverter::write_output(&metadata::strip(buffer.into_inner()), &path)?;
+        Ok(path)
+    }
+}
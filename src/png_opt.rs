@@ -0,0 +1,236 @@
+use std::io::Cursor;
+
+use image::error::{EncodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat};
+use img_parts::png::{Png, PngChunk};
+use img_parts::Bytes;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use oxipng::{Interlacing, StripChunks};
+
+/// miniz's strongest setting, beyond zlib's 9.
+const MAX_LEVEL: u8 = 10;
+/// Adam7 passes as (first column, first row, column step, row step).
+const ADAM7: [(usize, usize, usize, usize); 7] =
+    [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+/// PNG row filter applied before compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+    /// Picks the filter per row that leaves the smallest residuals.
+    Adaptive,
+}
+
+impl Filter {
+    const ALL: [Filter; 6] = [Filter::None, Filter::Sub, Filter::Up, Filter::Average, Filter::Paeth, Filter::Adaptive];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(Filter::None),
+            "sub" => Ok(Filter::Sub),
+            "up" => Ok(Filter::Up),
+            "avg" | "average" => Ok(Filter::Average),
+            "paeth" => Ok(Filter::Paeth),
+            "adaptive" => Ok(Filter::Adaptive),
+            _ => Err(format!("Invalid PNG filter: {} (expected none, sub, up, avg, paeth or adaptive)", value)),
+        }
+    }
+}
+
+/// PNG encoder settings. With everything unset the `image` encoder's
+/// defaults are used as-is.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PngOptions {
+    /// zlib level, 0 (store) to 9 (smallest).
+    pub compression: Option<u8>,
+    pub filter: Option<Filter>,
+    /// Tries every filter at maximum compression and keeps the smallest
+    /// result, like `optipng`.
+    pub optimize: bool,
+    /// Stores the pixels in Adam7 order so a coarse preview can be shown
+    /// before the whole file has loaded.
+    pub interlace: bool,
+    /// Runs the finished file through oxipng, which also tries deflate
+    /// strategies and smaller color types and strips chunks that don't
+    /// affect display. Metadata the converter copies is added afterwards.
+    pub oxipng: bool,
+}
+
+impl PngOptions {
+    fn recompresses(&self) -> bool {
+        self.compression.is_some() || self.filter.is_some() || self.optimize || self.interlace
+    }
+}
+
+/// Encodes `image` as PNG. The `image` encoder chooses the color type and
+/// writes the header; the pixel data is then refiltered, interlaced and
+/// recompressed according to `options`, and finally optimized with oxipng.
+pub fn encode(image: &DynamicImage, options: PngOptions) -> Result<Vec<u8>, ImageError> {
+    let mut base = Vec::new();
+    image.write_to(&mut Cursor::new(&mut base), ImageFormat::Png)?;
+    let encoded = if options.recompresses() { recompress(image, base, options)? } else { base };
+    optimize(encoded, options)
+}
+
+/// Runs a finished PNG through oxipng when `options` asks for it.
+pub fn optimize(encoded: Vec<u8>, options: PngOptions) -> Result<Vec<u8>, ImageError> {
+    if !options.oxipng {
+        return Ok(encoded);
+    }
+
+    let oxipng_options = oxipng::Options {
+        interlace: Some(if options.interlace { Interlacing::Adam7 } else { Interlacing::None }),
+        strip: StripChunks::Safe,
+        ..oxipng::Options::default()
+    };
+    oxipng::optimize_from_memory(&encoded, &oxipng_options)
+        .map_err(|e| ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::Png), e.to_string())))
+}
+
+fn recompress(image: &DynamicImage, base: Vec<u8>, options: PngOptions) -> Result<Vec<u8>, ImageError> {
+    // PNG samples are big-endian.
+    let mut pixels = image.as_bytes().to_vec();
+    if image.color().bytes_per_pixel() / image.color().channel_count() == 2 {
+        pixels.chunks_exact_mut(2).for_each(|sample| sample.swap(0, 1));
+    }
+    let bpp = usize::from(image.color().bytes_per_pixel());
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let scanlines = |filter| {
+        if options.interlace {
+            interlaced_rows(&pixels, width, height, bpp, filter)
+        } else {
+            filter_rows(&pixels, width * bpp, bpp, filter)
+        }
+    };
+
+    let idat = if options.optimize {
+        let filters = match &options.filter {
+            Some(filter) => std::slice::from_ref(filter),
+            None => &Filter::ALL[..],
+        };
+        filters
+            .iter()
+            .map(|&filter| compress_to_vec_zlib(&scanlines(filter), MAX_LEVEL))
+            .min_by_key(Vec::len)
+            .unwrap_or_default()
+    } else {
+        let filter = options.filter.unwrap_or(Filter::Adaptive);
+        compress_to_vec_zlib(&scanlines(filter), options.compression.unwrap_or(6))
+    };
+
+    let Ok(mut png) = Png::from_bytes(Bytes::from(base.clone())) else {
+        return Ok(base);
+    };
+    let chunks = png.chunks_mut();
+    let Some(first_idat) = chunks.iter().position(|chunk| &chunk.kind() == b"IDAT") else {
+        return Ok(base);
+    };
+    chunks.retain(|chunk| &chunk.kind() != b"IDAT");
+    chunks.insert(first_idat, PngChunk::new(*b"IDAT", Bytes::from(idat)));
+    if options.interlace {
+        // The interlace method is the last byte of IHDR.
+        if let Some(header) = chunks.iter_mut().find(|chunk| &chunk.kind() == b"IHDR") {
+            let mut contents = header.contents().to_vec();
+            if let Some(method) = contents.last_mut() {
+                *method = 1;
+            }
+            *header = PngChunk::new(*b"IHDR", Bytes::from(contents));
+        }
+    }
+
+    let mut output = Vec::with_capacity(base.len());
+    png.encoder().write_to(&mut output)?;
+    // The default encoder occasionally wins against a single fixed filter,
+    // but its output is never interlaced.
+    Ok(if options.optimize && !options.interlace && base.len() < output.len() { base } else { output })
+}
+
+/// Prefixes each row with its filter type and replaces its bytes with the
+/// filter residuals.
+fn filter_rows(pixels: &[u8], row_len: usize, bpp: usize, filter: Filter) -> Vec<u8> {
+    let mut output = Vec::with_capacity(pixels.len() + pixels.len() / row_len.max(1));
+    let zero_row = vec![0u8; row_len];
+    let mut candidate = vec![0u8; row_len];
+    let mut best = vec![0u8; row_len];
+
+    for (index, row) in pixels.chunks_exact(row_len).enumerate() {
+        let previous = if index == 0 { &zero_row[..] } else { &pixels[(index - 1) * row_len..index * row_len] };
+        let kind = if filter == Filter::Adaptive {
+            // Minimum sum of absolute differences, the heuristic libpng uses.
+            let mut best_kind = 0;
+            let mut best_score = u64::MAX;
+            for kind in 0..5 {
+                apply(kind, row, previous, bpp, &mut candidate);
+                let score: u64 = candidate.iter().map(|&byte| u64::from((byte as i8).unsigned_abs())).sum();
+                if score < best_score {
+                    best_score = score;
+                    best_kind = kind;
+                    std::mem::swap(&mut best, &mut candidate);
+                }
+            }
+            best_kind
+        } else {
+            let kind = filter as u8;
+            apply(kind, row, previous, bpp, &mut best);
+            kind
+        };
+        output.push(kind);
+        output.extend_from_slice(&best);
+    }
+    output
+}
+
+/// Filters each Adam7 pass as its own small image, one after another.
+/// Passes with no pixels are left out entirely.
+fn interlaced_rows(pixels: &[u8], width: usize, height: usize, bpp: usize, filter: Filter) -> Vec<u8> {
+    let mut output = Vec::new();
+    for (x0, y0, dx, dy) in ADAM7 {
+        let pass_width = width.saturating_sub(x0).div_ceil(dx);
+        if pass_width == 0 || y0 >= height {
+            continue;
+        }
+        let pass: Vec<u8> = (y0..height)
+            .step_by(dy)
+            .flat_map(|y| (x0..width).step_by(dx).map(move |x| (y * width + x) * bpp))
+            .flat_map(|at| &pixels[at..at + bpp])
+            .copied()
+            .collect();
+        output.extend(filter_rows(&pass, pass_width * bpp, bpp, filter));
+    }
+    output
+}
+
+fn apply(kind: u8, row: &[u8], previous: &[u8], bpp: usize, output: &mut [u8]) {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        output[i] = row[i].wrapping_sub(match kind {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+            _ => paeth(left, up, up_left),
+        });
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let (to_left, to_up, to_up_left) = (
+        (estimate - i16::from(left)).abs(),
+        (estimate - i16::from(up)).abs(),
+        (estimate - i16::from(up_left)).abs(),
+    );
+    if to_left <= to_up && to_left <= to_up_left {
+        left
+    } else if to_up <= to_up_left {
+        up
+    } else {
+        up_left
+    }
+}
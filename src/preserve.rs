@@ -0,0 +1,42 @@
+use std::fs::{File, FileTimes, Metadata};
+use std::path::{Path, PathBuf};
+
+use crate::is_stdio;
+
+/// `--preserve-times` and `--preserve-permissions`: outputs take the
+/// modification and access times, or the permissions, of their input
+/// rather than those of a freshly written file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Preserve {
+    pub times: bool,
+    pub permissions: bool,
+}
+
+impl Preserve {
+    /// The metadata of `input` to carry over, read before converting since
+    /// an output may replace the input. `None` when nothing is preserved or
+    /// `input` isn't a local file (stdin, a URL).
+    pub fn source(self, input: &Path) -> Option<Metadata> {
+        if !self.times && !self.permissions {
+            return None;
+        }
+        std::fs::metadata(input).ok().filter(Metadata::is_file)
+    }
+
+    /// Gives `outputs` the times and permissions recorded in `source`.
+    pub fn apply(self, source: &Metadata, outputs: &[PathBuf]) -> std::io::Result<()> {
+        for output in outputs.iter().filter(|output| !is_stdio(output)) {
+            // Times first: read-only permissions don't stop the owner
+            // setting them, but set afterwards they'd be one more thing
+            // that can fail.
+            if self.times {
+                let times = FileTimes::new().set_accessed(source.accessed()?).set_modified(source.modified()?);
+                File::open(output)?.set_times(times)?;
+            }
+            if self.permissions {
+                std::fs::set_permissions(output, source.permissions())?;
+            }
+        }
+        Ok(())
+    }
+}
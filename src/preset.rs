@@ -0,0 +1,91 @@
+use crate::transform::ResizeSpec;
+use crate::{CliOptions, SupportedFormat};
+
+/// A named bundle of settings for a common destination. Presets only fill
+/// in what the command line left unset, so any flag overrides them.
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    /// Used when neither `--to` nor a format argument names one.
+    pub format: SupportedFormat,
+    pub quality: u8,
+    /// Longest side; larger images shrink to fit, smaller ones are kept.
+    pub max_dimension: Option<u32>,
+    pub strip: bool,
+    pub keep_metadata: bool,
+    pub to_srgb: bool,
+    /// Rasterization resolution for SVG and PDF input.
+    pub dpi: Option<f32>,
+}
+
+impl Preset {
+    pub const NAMES: [&'static str; 4] = ["web", "thumbnail", "archive", "print"];
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let preset = match name.to_lowercase().as_str() {
+            // Browser delivery: capped at 1920px, sRGB, nothing private left in.
+            "web" => Preset {
+                format: SupportedFormat::WebP,
+                quality: 80,
+                max_dimension: Some(1920),
+                strip: true,
+                keep_metadata: false,
+                to_srgb: true,
+                dpi: None,
+            },
+            "thumbnail" => Preset {
+                format: SupportedFormat::WebP,
+                quality: 70,
+                max_dimension: Some(320),
+                strip: true,
+                keep_metadata: false,
+                to_srgb: true,
+                dpi: None,
+            },
+            // Lossless at full size, metadata and color profile intact.
+            "archive" => Preset {
+                format: SupportedFormat::Png,
+                quality: 100,
+                max_dimension: None,
+                strip: false,
+                keep_metadata: true,
+                to_srgb: false,
+                dpi: None,
+            },
+            "print" => Preset {
+                format: SupportedFormat::Jpeg,
+                quality: 95,
+                max_dimension: None,
+                strip: false,
+                keep_metadata: true,
+                to_srgb: false,
+                dpi: Some(300.0),
+            },
+            _ => {
+                return Err(format!("Invalid preset: {} (expected {})", name, Self::NAMES.join(", ")));
+            }
+        };
+        Ok(preset)
+    }
+
+    /// Fills in the settings `options` doesn't already have from flags.
+    pub fn apply(&self, options: &mut CliOptions) {
+        options.default_format.get_or_insert(self.format);
+        options.quality.get_or_insert(self.quality);
+        if let (Some(max), None) = (self.max_dimension, options.resize) {
+            options.resize = Some(ResizeSpec::Dimensions {
+                width: Some(max),
+                height: Some(max),
+            });
+            options.shrink_only = true;
+        }
+        // Metadata flags given explicitly rule out the preset's opposite one.
+        if !options.keep_metadata && !options.strip {
+            options.strip = self.strip;
+            options.keep_metadata = self.keep_metadata;
+        }
+        options.to_srgb.get_or_insert(self.to_srgb);
+        if let Some(dpi) = self.dpi {
+            options.dpi.get_or_insert(dpi);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::report::{self, Conversion};
+
+/// Reports batch progress either as a live progress bar (when stdout is a
+/// terminal), as one plain line per finished file, or with `--json` as one
+/// JSON event per finished file.
+pub struct BatchProgress {
+    bar: Option<ProgressBar>,
+    json: bool,
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl BatchProgress {
+    pub fn new(total: usize, json: bool) -> Self {
+        let bar = (!json && std::io::stdout().is_terminal()).then(|| {
+            let bar = ProgressBar::new(total as u64);
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{spinner} [{elapsed_precise}] {bar:40} {pos}/{len} ({per_sec}, ETA {eta}) {wide_msg}",
+                )
+                .unwrap(),
+            );
+            bar.enable_steady_tick(Duration::from_millis(120));
+            bar
+        });
+
+        Self {
+            bar,
+            json,
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn start(&self, path: &Path) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(display_name(path));
+        }
+    }
+
+    pub fn success(&self, path: &Path, conversion: &Conversion) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        match &self.bar {
+            _ if self.json => report::emit(&conversion.to_json()),
+            Some(bar) => bar.inc(1),
+            None => println!("[{}/{}] ✓ Converted: {}", done, self.total, display_name(path)),
+        }
+    }
+
+    pub fn failure(&self, path: &Path, error: &dyn std::fmt::Display) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.json {
+            report::emit(&report::failure(path, error));
+            return;
+        }
+        let line = format!("[{}/{}] ✗ Failed to convert {}: {}", done, self.total, path.display(), error);
+        match &self.bar {
+            Some(bar) => {
+                bar.suspend(|| eprintln!("{}", line));
+                bar.inc(1);
+            }
+            None => eprintln!("{}", line),
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, HashMap};
+
+use color_quant::NeuQuant;
+use image::error::{EncodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, ImageFormat, RgbaImage};
+
+/// Palette selection algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Recursively splits the color box with the widest spread at its
+    /// median: fast, and faithful to large flat areas like UI screenshots.
+    MedianCut,
+    /// Kohonen neural network: slower, often better on photos and gradients.
+    NeuQuant,
+}
+
+impl Method {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "median-cut" | "mediancut" => Ok(Method::MedianCut),
+            "neuquant" => Ok(Method::NeuQuant),
+            _ => Err(format!("Invalid quantizer: {} (expected median-cut or neuquant)", value)),
+        }
+    }
+}
+
+/// Settings for reducing output to an indexed palette.
+#[derive(Debug, Clone, Copy)]
+pub struct Quantize {
+    /// Palette size, 2-256.
+    pub colors: u16,
+    pub method: Method,
+    /// Floyd-Steinberg error diffusion, which trades banding for grain.
+    pub dither: bool,
+}
+
+/// An image as palette indices.
+pub struct Indexed {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA entries, the translucent ones first so PNG's `tRNS` stays short.
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+/// Reduces `image` to at most `settings.colors` colors. With `binary_alpha`
+/// (for GIF, which has a single transparent index) pixels are either fully
+/// opaque or fully transparent.
+pub fn quantize(image: &DynamicImage, settings: Quantize, binary_alpha: bool) -> Indexed {
+    let mut pixels = image.to_rgba8();
+    for pixel in pixels.pixels_mut() {
+        if binary_alpha {
+            pixel[3] = if pixel[3] < 128 { 0 } else { 255 };
+        }
+        // Invisible pixels all share one palette entry, whatever their color.
+        if pixel[3] == 0 {
+            pixel.0 = [0; 4];
+        }
+    }
+
+    let has_transparent = pixels.pixels().any(|pixel| pixel[3] == 0);
+    let colors = usize::from(settings.colors.clamp(2, 256)) - usize::from(has_transparent);
+    let visible: Vec<[u8; 4]> = pixels.pixels().filter(|pixel| pixel[3] > 0).map(|pixel| pixel.0).collect();
+
+    let mut palette = match settings.method {
+        Method::MedianCut => median_cut(&visible, colors),
+        Method::NeuQuant => neuquant(&visible, colors),
+    };
+    if has_transparent {
+        palette.push([0; 4]);
+    }
+    palette.sort_unstable();
+    palette.dedup();
+    palette.sort_by_key(|color| color[3]);
+
+    let indices = if settings.dither { dither(&pixels, &palette) } else { remap(&pixels, &palette) };
+    Indexed {
+        width: pixels.width(),
+        height: pixels.height(),
+        palette,
+        indices,
+    }
+}
+
+/// Writes an indexed PNG at the smallest bit depth that holds the palette.
+pub fn encode_png(indexed: &Indexed) -> Result<Vec<u8>, ImageError> {
+    let error = |e: png::EncodingError| ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::Png), e));
+    let bits: u8 = match indexed.palette.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+
+    let width = indexed.width as usize;
+    let per_byte = usize::from(8 / bits);
+    let mut packed = Vec::with_capacity(indexed.indices.len() / per_byte + indexed.height as usize);
+    for row in indexed.indices.chunks_exact(width.max(1)) {
+        for group in row.chunks(per_byte) {
+            let byte = group
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &index)| byte | index << (8 - bits * (i as u8 + 1)));
+            packed.push(byte);
+        }
+    }
+
+    let mut output = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut output, indexed.width, indexed.height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(match bits {
+            1 => png::BitDepth::One,
+            2 => png::BitDepth::Two,
+            4 => png::BitDepth::Four,
+            _ => png::BitDepth::Eight,
+        });
+        encoder.set_palette(indexed.palette.iter().flat_map(|color| [color[0], color[1], color[2]]).collect::<Vec<_>>());
+        let alphas: Vec<u8> = indexed.palette.iter().map(|color| color[3]).take_while(|&alpha| alpha < 255).collect();
+        if !alphas.is_empty() {
+            encoder.set_trns(alphas);
+        }
+        let mut writer = encoder.write_header().map_err(error)?;
+        writer.write_image_data(&packed).map_err(error)?;
+        writer.finish().map_err(error)?;
+    }
+    Ok(output)
+}
+
+/// Writes a single-frame GIF with `indexed`'s palette, so the `image`
+/// encoder's own quantizer never runs.
+pub fn encode_gif(indexed: &Indexed) -> Result<Vec<u8>, ImageError> {
+    let hint = || ImageFormatHint::Exact(ImageFormat::Gif);
+    let (Ok(width), Ok(height)) = (u16::try_from(indexed.width), u16::try_from(indexed.height)) else {
+        return Err(ImageError::Encoding(EncodingError::new(hint(), "GIF is limited to 65535 pixels per side")));
+    };
+    let error = |e: gif::EncodingError| ImageError::Encoding(EncodingError::new(hint(), e));
+
+    // GIF palettes hold a power of two entries, 2 to 256.
+    let mut palette: Vec<u8> = indexed.palette.iter().flat_map(|color| [color[0], color[1], color[2]]).collect();
+    palette.resize(3 * indexed.palette.len().next_power_of_two().max(2), 0);
+    let transparent = indexed.palette.iter().position(|color| color[3] == 0).map(|index| index as u8);
+
+    let mut output = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut output, width, height, &palette).map_err(error)?;
+        let frame = gif::Frame {
+            width,
+            height,
+            transparent,
+            buffer: indexed.indices.as_slice().into(),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).map_err(error)?;
+    }
+    Ok(output)
+}
+
+/// Median cut over the distinct colors in `pixels`, each box averaged by
+/// population. Colors are kept in order throughout, so the same pixels
+/// always give the same palette.
+pub fn median_cut(pixels: &[[u8; 4]], colors: usize) -> Vec<[u8; 4]> {
+    let mut histogram: BTreeMap<[u8; 4], u32> = BTreeMap::new();
+    for &pixel in pixels {
+        *histogram.entry(pixel).or_default() += 1;
+    }
+    let entries: Vec<([u8; 4], u32)> = histogram.into_iter().collect();
+    if entries.len() <= colors {
+        return entries.into_iter().map(|(color, _)| color).collect();
+    }
+
+    let spread = |entries: &[([u8; 4], u32)]| -> (usize, u8) {
+        (0..4)
+            .map(|channel| {
+                let (min, max) = entries
+                    .iter()
+                    .fold((u8::MAX, u8::MIN), |(min, max), (color, _)| (min.min(color[channel]), max.max(color[channel])));
+                (channel, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![entries];
+    while boxes.len() < colors {
+        // Split the box whose widest channel spans the most, weighted by how
+        // many pixels it covers so busy regions get more entries.
+        let Some((at, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|(at, entries)| {
+                let (channel, range) = spread(entries);
+                let population: u64 = entries.iter().map(|&(_, count)| u64::from(count)).sum();
+                (at, channel, u64::from(range) * population)
+            })
+            .max_by_key(|&(_, _, score)| score)
+            .map(|(at, channel, _)| (at, channel))
+        else {
+            break;
+        };
+
+        let mut entries = boxes.swap_remove(at);
+        entries.sort_unstable_by_key(|&(color, _)| (color[channel], color));
+        let half: u64 = entries.iter().map(|&(_, count)| u64::from(count)).sum::<u64>() / 2;
+        let mut seen = 0;
+        let median = entries
+            .iter()
+            .position(|&(_, count)| {
+                seen += u64::from(count);
+                seen >= half
+            })
+            .unwrap_or(0)
+            .clamp(0, entries.len() - 2);
+        let upper = entries.split_off(median + 1);
+        boxes.push(entries);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|entries| {
+            let total: u64 = entries.iter().map(|&(_, count)| u64::from(count)).sum();
+            let mut sums = [0u64; 4];
+            for (color, count) in entries {
+                for channel in 0..4 {
+                    sums[channel] += u64::from(color[channel]) * u64::from(*count);
+                }
+            }
+            sums.map(|sum| ((sum + total / 2) / total.max(1)) as u8)
+        })
+        .collect()
+}
+
+fn neuquant(pixels: &[[u8; 4]], colors: usize) -> Vec<[u8; 4]> {
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+    let flat: Vec<u8> = pixels.iter().flatten().copied().collect();
+    NeuQuant::new(10, colors, &flat)
+        .color_map_rgba()
+        .chunks_exact(4)
+        .map(|color| [color[0], color[1], color[2], color[3]])
+        .collect()
+}
+
+fn nearest(palette: &[[u8; 4]], color: [f32; 4]) -> usize {
+    palette
+        .iter()
+        .map(|entry| {
+            entry
+                .iter()
+                .zip(color)
+                .map(|(&a, b)| {
+                    let difference = f32::from(a) - b;
+                    difference * difference
+                })
+                .sum::<f32>()
+        })
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(index, _)| index)
+}
+
+fn remap(pixels: &RgbaImage, palette: &[[u8; 4]]) -> Vec<u8> {
+    let mut cache: HashMap<[u8; 4], u8> = HashMap::new();
+    pixels
+        .pixels()
+        .map(|pixel| *cache.entry(pixel.0).or_insert_with(|| nearest(palette, pixel.0.map(f32::from)) as u8))
+        .collect()
+}
+
+/// Floyd-Steinberg: each pixel's rounding error is pushed onto its
+/// unvisited neighbours (7/16 right, 3/16 below-left, 5/16 below,
+/// 1/16 below-right).
+fn dither(pixels: &RgbaImage, palette: &[[u8; 4]]) -> Vec<u8> {
+    let (width, height) = (pixels.width() as usize, pixels.height() as usize);
+    let mut errors = vec![[0.0f32; 4]; width * height];
+    let mut indices = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let source = pixels.get_pixel(x as u32, y as u32).0;
+            // Transparent pixels keep their exact entry and pass no error on.
+            if source[3] == 0 {
+                indices.push(nearest(palette, [0.0; 4]) as u8);
+                continue;
+            }
+            let wanted: [f32; 4] = std::array::from_fn(|c| (f32::from(source[c]) + errors[y * width + x][c]).clamp(0.0, 255.0));
+            let index = nearest(palette, wanted);
+            indices.push(index as u8);
+
+            let error: [f32; 4] = std::array::from_fn(|c| wanted[c] - f32::from(palette[index][c]));
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    let target = &mut errors[(y + dy) * width + nx as usize];
+                    for c in 0..4 {
+                        target[c] += error[c] * weight;
+                    }
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+    indices
+}
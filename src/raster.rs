@@ -0,0 +1,56 @@
+/// How vector input (SVG, PDF) is rasterized: at `dpi`, or scaled to a
+/// target size. A single target dimension keeps the drawing's aspect ratio.
+#[derive(Debug, Clone, Copy)]
+pub struct Raster {
+    pub dpi: f32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Lets SVG text fall back on the fonts installed on this machine.
+    pub system_fonts: bool,
+}
+
+impl Default for Raster {
+    fn default() -> Self {
+        Self {
+            dpi: 96.0,
+            width: None,
+            height: None,
+            system_fonts: true,
+        }
+    }
+}
+
+impl Raster {
+    /// Parses `--raster-size` values: `800x600`, `800x` or `x600`.
+    pub fn parse_size(value: &str) -> Result<(Option<u32>, Option<u32>), String> {
+        let (width, height) = value
+            .split_once('x')
+            .ok_or_else(|| format!("Invalid raster size: {} (expected WxH, Wx or xH)", value))?;
+        let parse = |part: &str| -> Result<Option<u32>, String> {
+            if part.is_empty() {
+                return Ok(None);
+            }
+            part.parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(Some)
+                .ok_or_else(|| format!("Invalid raster size: {}", value))
+        };
+
+        match (parse(width)?, parse(height)?) {
+            (None, None) => Err(format!("Invalid raster size: {} (give a width, a height or both)", value)),
+            size => Ok(size),
+        }
+    }
+
+    /// Horizontal and vertical scale factors for a drawing `width` x
+    /// `height` units in size, where `units_per_inch` of those make an inch.
+    pub fn scale(&self, width: f32, height: f32, units_per_inch: f32) -> (f32, f32) {
+        match (self.width, self.height) {
+            (Some(target_width), Some(target_height)) => (target_width as f32 / width, target_height as f32 / height),
+            (Some(target_width), None) => (target_width as f32 / width, target_width as f32 / width),
+            (None, Some(target_height)) => (target_height as f32 / height, target_height as f32 / height),
+            (None, None) => (self.dpi / units_per_inch, self.dpi / units_per_inch),
+        }
+    }
+}
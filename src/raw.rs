@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError};
+
+/// Camera RAW extensions handed to the RAW decoder.
+pub const EXTENSIONS: [&str; 10] = ["cr2", "nef", "nrw", "arw", "srf", "dng", "raf", "orf", "rw2", "pef"];
+
+fn hint() -> ImageFormatHint {
+    ImageFormatHint::Name("RAW".to_string())
+}
+
+/// Whether `path` has a camera RAW extension. Most RAW formats are TIFF
+/// underneath, so the extension is the only reliable way to tell them apart.
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Develops a camera RAW file into a 16-bit sRGB image: demosaic, as-shot
+/// white balance, camera color matrix, gamma and the camera's orientation.
+#[cfg(feature = "raw")]
+pub fn decode(data: &[u8]) -> Result<DynamicImage, ImageError> {
+    use image::ImageBuffer;
+    use imagepipe::{ImageSource, Pipeline};
+
+    let error = |e: &dyn std::fmt::Display| ImageError::Decoding(DecodingError::new(hint(), e.to_string()));
+
+    let raw = rawloader::decode(&mut std::io::Cursor::new(data)).map_err(|e| error(&e))?;
+    let mut pipeline = Pipeline::new_from_source(ImageSource::Raw(raw)).map_err(|e| error(&e))?;
+    let developed = pipeline.output_16bit(None).map_err(|e| error(&e))?;
+
+    ImageBuffer::from_raw(developed.width as u32, developed.height as u32, developed.data)
+        .map(DynamicImage::ImageRgb16)
+        .ok_or_else(|| error(&"developed image is smaller than its dimensions"))
+}
+
+#[cfg(not(feature = "raw"))]
+pub fn decode(_data: &[u8]) -> Result<DynamicImage, ImageError> {
+    Err(ImageError::Decoding(DecodingError::new(
+        hint(),
+        "camera RAW input requires building with `--features raw`",
+    )))
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+/// Reads a recipe file and returns the command-line arguments it stands
+/// for, to be parsed ahead of the real ones so explicit flags still win.
+///
+/// Recipes are TOML, or YAML for `.yaml`/`.yml` files. Each key names a
+/// flag without its dashes (`_` or `-` between words): `true` switches it
+/// on, a string or number is its value and an array repeats it. A few keys
+/// read more naturally than their flags:
+///
+/// ```toml
+/// ops = ["autorotate", "resize=1600x", "sharpen=0.6", "strip"]
+/// format = "webp"           # --to
+/// formats = ["avif", "webp"]
+/// suffix = "-web"
+///
+/// [quality]
+/// default = 80
+/// avif = 55
+/// ```
+pub fn load(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read recipe {}: {}", path.display(), e))?;
+    let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    let parsed: Result<Value, String> = if extension == "yaml" || extension == "yml" {
+        serde_yaml::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(&text).map_err(|e| e.to_string())
+    };
+    let recipe = match parsed {
+        Ok(Value::Object(recipe)) => recipe,
+        Ok(Value::Null) => Map::new(),
+        Ok(_) => return Err(format!("Invalid recipe {}: expected a table of settings", path.display())),
+        Err(e) => return Err(format!("Invalid recipe {}: {}", path.display(), e)),
+    };
+    to_args(&recipe).map_err(|e| format!("Invalid recipe {}: {}", path.display(), e))
+}
+
+fn to_args(recipe: &Map<String, Value>) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (key, value) in recipe {
+        let flag = key.replace('_', "-");
+        match (flag.as_str(), value) {
+            ("recipe", _) => return Err("recipes can't include other recipes".to_string()),
+            ("format", value) => {
+                args.push("--to".to_string());
+                args.push(scalar(key, value)?);
+            }
+            ("ops" | "formats" | "widths", Value::Array(items)) => {
+                let items = items.iter().map(|item| scalar(key, item)).collect::<Result<Vec<_>, _>>()?;
+                args.push(format!("--{}", flag));
+                args.push(items.join(","));
+            }
+            ("quality", Value::Object(qualities)) => {
+                for (format, quality) in qualities {
+                    args.push("--quality".to_string());
+                    args.push(match format.as_str() {
+                        "default" => scalar(key, quality)?,
+                        _ => format!("{}={}", format, scalar(key, quality)?),
+                    });
+                }
+            }
+            (_, Value::Bool(true)) => args.push(format!("--{}", flag)),
+            (_, Value::Bool(false)) => {}
+            (_, Value::Array(items)) => {
+                for item in items {
+                    args.push(format!("--{}", flag));
+                    args.push(scalar(key, item)?);
+                }
+            }
+            (_, value) => {
+                args.push(format!("--{}", flag));
+                args.push(scalar(key, value)?);
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// A flag value: strings as-is, numbers in their shortest form.
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        _ => Err(format!("{} must be a string or number, not {}", key, value)),
+    }
+}
@@ -0,0 +1,49 @@
+use std::io::Read;
+use std::time::Duration;
+
+/// Downloads larger than this are rejected rather than buffered.
+pub const MAX_DOWNLOAD_BYTES: u64 = 64 * 1024 * 1024;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Fetches `url` into memory, enforcing the timeout and size limit.
+pub fn download(url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let response = agent.get(url).call()?;
+
+    if let Some(length) = response
+        .header("Content-Length")
+        .and_then(|value| value.parse::<u64>().ok())
+    {
+        if length > MAX_DOWNLOAD_BYTES {
+            return Err(format!("{} is {} bytes, over the {} byte limit", url, length, MAX_DOWNLOAD_BYTES).into());
+        }
+    }
+
+    // Content-Length can be absent or wrong, so cap the actual read too.
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD_BYTES + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_DOWNLOAD_BYTES {
+        return Err(format!("{} exceeds the {} byte limit", url, MAX_DOWNLOAD_BYTES).into());
+    }
+
+    Ok(data)
+}
+
+/// File stem derived from the last path segment of `url`, ignoring any
+/// query string or fragment.
+pub fn url_stem(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let (_, path) = path.split_once('/')?;
+    let segment = path.rsplit('/').find(|segment| !segment.is_empty())?;
+    let stem = segment.rsplit_once('.').map_or(segment, |(stem, _)| stem);
+    (!stem.is_empty()).then(|| stem.to_string())
+}
@@ -0,0 +1,302 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::atomic;
+use crate::info::format_size;
+
+/// What converting one input produced, for `--json` events and reports.
+#[derive(Debug, Clone, Default)]
+pub struct Conversion {
+    pub input: PathBuf,
+    /// Every file written for the input: one normally, several for
+    /// `--pages all`, `--widths` or `--formats`.
+    pub outputs: Vec<PathBuf>,
+    pub input_size: u64,
+    /// Total size of `outputs` on disk.
+    pub output_size: u64,
+    /// Source and output size in pixels; unknown for animations and PDF
+    /// pages, which are converted frame by frame.
+    pub dimensions: Option<(u32, u32)>,
+    pub output_dimensions: Option<(u32, u32)>,
+    /// The input was deleted afterwards, by `--delete-original` or
+    /// `--in-place`.
+    pub deleted: bool,
+}
+
+impl Conversion {
+    pub fn new(input: &Path, input_size: u64) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            input_size,
+            ..Self::default()
+        }
+    }
+
+    /// Records `outputs` as written, adding up their sizes. Outputs that
+    /// can't be measured, such as stdout, count as empty.
+    pub fn with_outputs(mut self, outputs: Vec<PathBuf>) -> Self {
+        self.output_size = outputs
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        self.outputs = outputs;
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        let size = |dimensions: Option<(u32, u32)>| match dimensions {
+            Some((width, height)) => json!({ "width": width, "height": height }),
+            None => Value::Null,
+        };
+        json!({
+            "event": "converted",
+            "input": self.input.display().to_string(),
+            "outputs": self.outputs.iter().map(|path| path.display().to_string()).collect::<Vec<_>>(),
+            "input_size": self.input_size,
+            "output_size": self.output_size,
+            "dimensions": size(self.dimensions),
+            "output_dimensions": size(self.output_dimensions),
+            "deleted": self.deleted,
+        })
+    }
+}
+
+/// One input of a batch run, as listed in a `--report`.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub input: PathBuf,
+    /// The error message when the input failed.
+    pub outcome: Result<Conversion, String>,
+    pub duration: Duration,
+}
+
+impl Entry {
+    /// Percentage of the input's size saved by the outputs; negative when
+    /// they came out larger.
+    pub fn saved_percent(&self) -> Option<f64> {
+        match &self.outcome {
+            Ok(conversion) if conversion.input_size > 0 => {
+                Some(100.0 * (1.0 - conversion.output_size as f64 / conversion.input_size as f64))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let (outputs, input_size, output_size, error) = match &self.outcome {
+            Ok(conversion) => (
+                conversion.outputs.iter().map(|path| path.display().to_string()).collect(),
+                Some(conversion.input_size),
+                Some(conversion.output_size),
+                None,
+            ),
+            Err(error) => (Vec::new(), None, None, Some(error.clone())),
+        };
+        json!({
+            "input": self.input.display().to_string(),
+            "outputs": outputs,
+            "input_size": input_size,
+            "output_size": output_size,
+            "saved_percent": self.saved_percent().map(|percent| (percent * 10.0).round() / 10.0),
+            "duration_ms": self.duration.as_millis() as u64,
+            "error": error,
+        })
+    }
+
+    fn to_csv(&self) -> String {
+        let (outputs, input_size, output_size, error) = match &self.outcome {
+            Ok(conversion) => (
+                conversion.outputs.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(";"),
+                conversion.input_size.to_string(),
+                conversion.output_size.to_string(),
+                String::new(),
+            ),
+            Err(error) => (String::new(), String::new(), String::new(), error.clone()),
+        };
+        [
+            csv_field(&self.input.display().to_string()),
+            csv_field(&outputs),
+            input_size,
+            output_size,
+            self.saved_percent().map_or(String::new(), |percent| format!("{:.1}", percent)),
+            self.duration.as_millis().to_string(),
+            csv_field(&error),
+        ]
+        .join(",")
+    }
+}
+
+/// How many of the slowest inputs a summary names.
+const SLOWEST: usize = 3;
+
+/// Totals over a batch run, printed when it ends.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub converted: usize,
+    pub failed: usize,
+    /// Inputs left out before converting, such as near-duplicates.
+    pub skipped: usize,
+    pub input_size: u64,
+    pub output_size: u64,
+    /// Inputs deleted after converting, and their total size.
+    pub deleted: usize,
+    pub reclaimed: u64,
+    /// Wall-clock time for the whole run.
+    pub elapsed: Duration,
+    /// Sum of the time spent on each input, across all threads.
+    pub busy: Duration,
+    pub slowest: Vec<(PathBuf, Duration)>,
+}
+
+impl Summary {
+    pub fn new(entries: &[Entry], skipped: usize, elapsed: Duration) -> Self {
+        let converted: Vec<&Conversion> = entries.iter().filter_map(|entry| entry.outcome.as_ref().ok()).collect();
+        let mut slowest: Vec<(PathBuf, Duration)> =
+            entries.iter().map(|entry| (entry.input.clone(), entry.duration)).collect();
+        slowest.sort_by_key(|&(_, duration)| std::cmp::Reverse(duration));
+        slowest.truncate(SLOWEST);
+        Self {
+            converted: converted.len(),
+            failed: entries.len() - converted.len(),
+            skipped,
+            input_size: converted.iter().map(|conversion| conversion.input_size).sum(),
+            output_size: converted.iter().map(|conversion| conversion.output_size).sum(),
+            deleted: converted.iter().filter(|conversion| conversion.deleted).count(),
+            reclaimed: converted
+                .iter()
+                .filter(|conversion| conversion.deleted)
+                .map(|conversion| conversion.input_size)
+                .sum(),
+            elapsed,
+            busy: entries.iter().map(|entry| entry.duration).sum(),
+            slowest,
+        }
+    }
+
+    /// Mean time per processed input.
+    fn average(&self) -> Duration {
+        self.busy / (self.converted + self.failed).max(1) as u32
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![format!(
+            "Batch conversion completed! {} converted, {} failed, {} skipped.",
+            self.converted, self.failed, self.skipped
+        )];
+        if self.converted > 0 {
+            let saved = 100.0 * (1.0 - self.output_size as f64 / self.input_size.max(1) as f64);
+            lines.push(format!(
+                "  Size: {} -> {} ({:.1}% {})",
+                format_size(self.input_size),
+                format_size(self.output_size),
+                saved.abs(),
+                if saved >= 0.0 { "smaller" } else { "larger" }
+            ));
+        }
+        if self.deleted > 0 {
+            lines.push(format!(
+                "  Deleted: {} originals ({})",
+                self.deleted,
+                format_size(self.reclaimed)
+            ));
+        }
+        if self.converted + self.failed > 0 {
+            lines.push(format!(
+                "  Time: {:.2}s total, {:.2}s per file on average",
+                self.elapsed.as_secs_f64(),
+                self.average().as_secs_f64()
+            ));
+            let slowest: Vec<String> = self
+                .slowest
+                .iter()
+                .map(|(path, duration)| format!("{} ({:.2}s)", path.display(), duration.as_secs_f64()))
+                .collect();
+            lines.push(format!("  Slowest: {}", slowest.join(", ")));
+        }
+        lines.join("\n")
+    }
+
+    /// The closing `--json` event of a batch run.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "event": "completed",
+            "converted": self.converted,
+            "failed": self.failed,
+            "skipped": self.skipped,
+            "input_size": self.input_size,
+            "output_size": self.output_size,
+            "deleted": self.deleted,
+            "reclaimed": self.reclaimed,
+            "elapsed_ms": self.elapsed.as_millis() as u64,
+            "average_ms": self.average().as_millis() as u64,
+            "slowest": self
+                .slowest
+                .iter()
+                .map(|(path, duration)| json!({ "input": path.display().to_string(), "duration_ms": duration.as_millis() as u64 }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Writes `entries` to `path` as JSON for a `.json` path, CSV otherwise,
+/// one row per input. Several outputs for one input are `;`-separated in CSV.
+pub fn write(path: &Path, entries: &[Entry]) -> std::io::Result<()> {
+    let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    let text = if is_json {
+        let rows: Vec<Value> = entries.iter().map(Entry::to_json).collect();
+        serde_json::to_string_pretty(&rows).unwrap_or_default() + "\n"
+    } else {
+        let mut csv = String::from("input,output,input_size,output_size,saved_percent,duration_ms,error\n");
+        for entry in entries {
+            csv.push_str(&entry.to_csv());
+            csv.push('\n');
+        }
+        csv
+    };
+    atomic::write(path, text.as_bytes())
+}
+
+/// Quotes a CSV field when it holds a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The `--json` event for an input that failed to convert.
+pub fn failure(input: &Path, error: &dyn std::fmt::Display) -> Value {
+    json!({
+        "event": "failed",
+        "input": input.display().to_string(),
+        "error": error.to_string(),
+    })
+}
+
+/// The `--json` event for an input left out of a batch, and why.
+pub fn skipped(input: &Path, reason: &str) -> Value {
+    json!({
+        "event": "skipped",
+        "input": input.display().to_string(),
+        "reason": reason,
+    })
+}
+
+/// The `--json` event closing a batch stopped by Ctrl+C, after its
+/// summary: how many inputs were never started.
+pub fn interrupted(not_started: usize) -> Value {
+    json!({
+        "event": "interrupted",
+        "not_started": not_started,
+    })
+}
+
+/// Prints `event` as a single line of JSON, so a run's events can be read
+/// one line at a time.
+pub fn emit(event: &Value) {
+    println!("{}", event);
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use rayon::prelude::*;
+
+use crate::{is_stdio, ImageConverter, SupportedFormat};
+
+/// File name suffix appended per width unless `--width-suffix` says otherwise.
+pub const DEFAULT_SUFFIX: &str = "-{w}";
+
+/// A responsive image set (`srcset`): one output per width, optionally in
+/// several formats, all from a single decode. Without widths, the image is
+/// written once per format at its own size.
+#[derive(Debug, Clone, Default)]
+pub struct Variants {
+    pub widths: Vec<u32>,
+    /// Inserted before the extension, with `{w}` replaced by the width.
+    pub suffix: String,
+    /// Formats written for every width; the output path's when empty.
+    pub formats: Vec<SupportedFormat>,
+}
+
+impl Variants {
+    /// Parses a comma-separated width list such as `320,640,1280`.
+    pub fn parse_widths(spec: &str) -> Result<Vec<u32>, String> {
+        let mut widths = spec
+            .split(',')
+            .map(|width| width.trim().parse().ok().filter(|&width: &u32| width > 0))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or_else(|| format!("Invalid widths: {} (expected e.g. 320,640,1280)", spec))?;
+        widths.sort_unstable();
+        widths.dedup();
+        Ok(widths)
+    }
+
+    /// Parses a comma-separated format list such as `avif,webp,jpg`.
+    pub fn parse_formats(spec: &str) -> Result<Vec<SupportedFormat>, String> {
+        spec.split(',').map(|format| SupportedFormat::from_extension(format.trim())).collect()
+    }
+
+    /// Every path [`write`](Self::write) may produce for `output_path`;
+    /// widths above the image's own are skipped when actually writing.
+    pub fn paths(&self, output_path: &Path, format: SupportedFormat) -> Vec<PathBuf> {
+        let formats = if self.formats.is_empty() { vec![format] } else { self.formats.clone() };
+        let widths: Vec<Option<u32>> = if self.widths.is_empty() {
+            vec![None]
+        } else {
+            self.widths.iter().copied().map(Some).collect()
+        };
+        widths
+            .iter()
+            .flat_map(|&width| formats.iter().map(move |&format| self.path(output_path, width, format)))
+            .collect()
+    }
+
+    fn path(&self, output_path: &Path, width: Option<u32>, format: SupportedFormat) -> PathBuf {
+        let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+        let suffix = width.map_or(String::new(), |width| self.suffix.replace("{w}", &width.to_string()));
+        output_path.with_file_name(format!("{}{}.{}", stem, suffix, format.extension()))
+    }
+
+    /// Resizes `image` to every width and encodes each in every format,
+    /// returning the paths written. Widths above the image's own are
+    /// skipped, since upscaling only adds bytes; it's an error when that
+    /// leaves none.
+    pub fn write(
+        &self,
+        converter: &ImageConverter,
+        image: &DynamicImage,
+        source: &[u8],
+        output_path: &Path,
+        format: SupportedFormat,
+    ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        if is_stdio(output_path) {
+            return Err("--widths and --formats write several files and can't write to stdout".into());
+        }
+
+        let formats = if self.formats.is_empty() { vec![format] } else { self.formats.clone() };
+        if self.widths.is_empty() {
+            return formats
+                .par_iter()
+                .map(|&format| {
+                    let path = self.path(output_path, None, format);
+                    converter
+                        .save_image(image, source, &path, format)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    Ok(path)
+                })
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(Into::into);
+        }
+
+        let (widths, skipped): (Vec<u32>, Vec<u32>) = self.widths.iter().partition(|&&width| width <= image.width());
+        let skipped: Vec<String> = skipped.iter().map(u32::to_string).collect();
+        if widths.is_empty() {
+            return Err(format!(
+                "every width ({}) is above the image's {}px, so nothing was written",
+                skipped.join(", "),
+                image.width()
+            )
+            .into());
+        }
+        if !skipped.is_empty() {
+            eprintln!(
+                "Warning: {}: skipping widths {} above the image's {}px",
+                output_path.display(),
+                skipped.join(", "),
+                image.width()
+            );
+        }
+
+        widths
+            .par_iter()
+            .map(|&width| -> Result<Vec<PathBuf>, String> {
+                let height = ((u64::from(image.height()) * u64::from(width) + u64::from(image.width()) / 2)
+                    / u64::from(image.width()))
+                .max(1) as u32;
+                let resized = if width == image.width() {
+                    image.clone()
+                } else {
+                    image.resize_exact(width, height, FilterType::Lanczos3)
+                };
+                formats
+                    .iter()
+                    .map(|&format| {
+                        let path = self.path(output_path, Some(width), format);
+                        converter
+                            .save_image(&resized, source, &path, format)
+                            .map_err(|e| format!("{}: {}", path.display(), e))?;
+                        Ok(path)
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|written| written.into_iter().flatten().collect())
+            .map_err(Into::into)
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use serde_json::{Map, Value};
+
+use crate::{atomic, blurhash, dominant};
+
+/// Per-image data computed from the converted pixels and written to a JSON
+/// file beside each output, for web frontends to pick up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Analysis {
+    /// BlurHash components across and down, e.g. 4x3.
+    pub blurhash: Option<(u32, u32)>,
+    pub thumbhash: bool,
+    /// Number of dominant colors to extract.
+    pub dominant_colors: Option<usize>,
+}
+
+impl Analysis {
+    pub fn is_empty(&self) -> bool {
+        self.blurhash.is_none() && !self.thumbhash && self.dominant_colors.is_none()
+    }
+
+    /// Parses BlurHash components as `XxY`, 1-9 each.
+    pub fn parse_components(spec: &str) -> Result<(u32, u32), String> {
+        let invalid = || format!("Invalid BlurHash components: {} (expected XxY, 1-9 each, e.g. 4x3)", spec);
+        let (x, y) = spec.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let parse = |value: &str| value.parse().ok().filter(|count| (1..=9).contains(count)).ok_or_else(invalid);
+        Ok((parse(x)?, parse(y)?))
+    }
+
+    /// `photo.webp` gets `photo.json`.
+    pub fn path(output_path: &Path) -> PathBuf {
+        output_path.with_extension("json")
+    }
+
+    pub fn analyze(&self, image: &DynamicImage) -> Map<String, Value> {
+        let mut fields = Map::new();
+        fields.insert("width".to_string(), Value::from(image.width()));
+        fields.insert("height".to_string(), Value::from(image.height()));
+        if let Some((x, y)) = self.blurhash {
+            fields.insert("blurhash".to_string(), Value::from(blurhash::blurhash(image, x, y)));
+        }
+        if self.thumbhash {
+            fields.insert("thumbhash".to_string(), Value::from(blurhash::thumbhash(image)));
+        }
+        if let Some(count) = self.dominant_colors {
+            let average = dominant::average(image).map(dominant::hex);
+            fields.insert("average_color".to_string(), Value::from(average));
+            let colors: Vec<String> = dominant::dominant(image, count).iter().map(|swatch| dominant::hex(swatch.color)).collect();
+            fields.insert("dominant_colors".to_string(), Value::from(colors));
+        }
+        fields
+    }
+
+    /// Writes the sidecar for `image`, converted to `output_path`.
+    pub fn write(&self, image: &DynamicImage, output_path: &Path) -> std::io::Result<PathBuf> {
+        let path = Self::path(output_path);
+        let json = serde_json::to_string_pretty(&Value::Object(self.analyze(image)))?;
+        atomic::write(&path, (json + "\n").as_bytes())?;
+        Ok(path)
+    }
+}
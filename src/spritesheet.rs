@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use image::{imageops, DynamicImage, RgbaImage};
+use rayon::prelude::*;
+use serde_json::json;
+
+use crate::{atomic, ImageConverter, SupportedFormat};
+
+/// How sprites are arranged in the atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Packing {
+    /// Equal cells sized to the largest sprite, in a roughly square grid:
+    /// predictable offsets, wasted space when sizes vary.
+    Grid,
+    /// Shelf bin packing: sprites sorted by height fill rows left to right,
+    /// which keeps mixed sizes tight.
+    #[default]
+    Packed,
+}
+
+impl Packing {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "grid" => Ok(Packing::Grid),
+            "packed" | "shelf" => Ok(Packing::Packed),
+            _ => Err(format!("Invalid packing: {} (expected grid or packed)", value)),
+        }
+    }
+}
+
+/// Coordinate map written next to the atlas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapFormat {
+    /// `{"frames": {"name": {"x", "y", "w", "h"}}, "meta": {...}}`, the
+    /// TexturePacker hash layout most game engines import.
+    #[default]
+    Json,
+    /// One `.sprite-name` class per sprite with `background-position`.
+    Css,
+}
+
+impl MapFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(MapFormat::Json),
+            "css" => Ok(MapFormat::Css),
+            _ => Err(format!("Invalid sprite map format: {} (expected json or css)", value)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            MapFormat::Json => "json",
+            MapFormat::Css => "css",
+        }
+    }
+
+    /// What a sprite called `name` is looked up by in the map.
+    fn key(self, name: &str) -> String {
+        match self {
+            MapFormat::Json => name.to_string(),
+            MapFormat::Css => css_class(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub packing: Packing,
+    /// Transparent pixels around each sprite, against texture bleeding.
+    pub padding: u32,
+    pub map: MapFormat,
+}
+
+struct Sprite {
+    name: String,
+    image: RgbaImage,
+    x: u32,
+    y: u32,
+}
+
+/// Packs every selected image under `input_dir`, run through the transform
+/// pipeline, into one atlas at `output_path` and writes the coordinate map
+/// beside it. Returns the atlas and map paths.
+pub fn generate(
+    converter: &ImageConverter,
+    input_dir: &Path,
+    output_path: &Path,
+    format: SupportedFormat,
+    options: Options,
+) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+    let mut inputs = converter.selected_inputs(input_dir)?;
+    inputs.sort();
+    if inputs.is_empty() {
+        return Err(format!("no images found in {}", input_dir.display()).into());
+    }
+    let mut named: HashMap<String, &Path> = HashMap::new();
+    for input in &inputs {
+        if let Some(other) = named.insert(options.map.key(&sprite_name(input)), input) {
+            return Err(format!(
+                "{} and {} would have the same sprite name; rename one of them",
+                other.display(),
+                input.display()
+            )
+            .into());
+        }
+    }
+
+    let mut sprites = inputs
+        .par_iter()
+        .map(|input| -> Result<Sprite, String> {
+            let image = converter
+                .read_input(input)
+                .and_then(|data| converter.decode_image(input, &data))
+                .map_err(|e| format!("{}: {}", input.display(), e))?;
+            Ok(Sprite {
+                name: sprite_name(input),
+                image: converter.process(image).into_rgba8(),
+                x: 0,
+                y: 0,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (width, height) = match options.packing {
+        Packing::Grid => pack_grid(&mut sprites, options.padding),
+        Packing::Packed => pack_shelves(&mut sprites, options.padding),
+    };
+    let mut atlas = RgbaImage::new(width, height);
+    for sprite in &sprites {
+        imageops::replace(&mut atlas, &sprite.image, i64::from(sprite.x), i64::from(sprite.y));
+    }
+    converter.save_image(&DynamicImage::ImageRgba8(atlas), &[], output_path, format)?;
+
+    sprites.sort_by(|a, b| a.name.cmp(&b.name));
+    let image_name = output_path.file_name().unwrap_or_default().to_string_lossy();
+    let map = match options.map {
+        MapFormat::Json => json_map(&sprites, &image_name, (width, height)),
+        MapFormat::Css => css_map(&sprites, &image_name),
+    };
+    let map_path = output_path.with_extension(options.map.extension());
+    atomic::write(&map_path, map.as_bytes())?;
+    Ok((output_path.to_path_buf(), map_path))
+}
+
+/// A sprite is named after its file, without the extension.
+fn sprite_name(input: &Path) -> String {
+    input.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Places sprites in cells the size of the largest one, as square as
+/// possible. Returns the atlas size.
+fn pack_grid(sprites: &mut [Sprite], padding: u32) -> (u32, u32) {
+    let cell_width = sprites.iter().map(|sprite| sprite.image.width()).max().unwrap_or(0) + padding;
+    let cell_height = sprites.iter().map(|sprite| sprite.image.height()).max().unwrap_or(0) + padding;
+    let columns = (sprites.len() as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (sprites.len() as u32).div_ceil(columns);
+    for (index, sprite) in sprites.iter_mut().enumerate() {
+        sprite.x = padding + (index as u32 % columns) * cell_width;
+        sprite.y = padding + (index as u32 / columns) * cell_height;
+    }
+    (padding + columns * cell_width, padding + rows * cell_height)
+}
+
+/// Tallest-first shelf packing into rows about as wide as a square holding
+/// every sprite's area. Returns the atlas size.
+fn pack_shelves(sprites: &mut [Sprite], padding: u32) -> (u32, u32) {
+    let area: u64 = sprites
+        .iter()
+        .map(|sprite| u64::from(sprite.image.width() + padding) * u64::from(sprite.image.height() + padding))
+        .sum();
+    let widest = sprites.iter().map(|sprite| sprite.image.width() + padding).max().unwrap_or(0);
+    let row_width = ((area as f64).sqrt().ceil() as u32).max(widest);
+
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(sprites[index].image.height()));
+
+    let (mut x, mut y, mut shelf_height, mut width) = (padding, padding, 0, 0);
+    for index in order {
+        let sprite = &mut sprites[index];
+        let (sprite_width, sprite_height) = sprite.image.dimensions();
+        if x > padding && x + sprite_width > row_width {
+            x = padding;
+            y += shelf_height + padding;
+            shelf_height = 0;
+        }
+        sprite.x = x;
+        sprite.y = y;
+        x += sprite_width + padding;
+        width = width.max(x);
+        shelf_height = shelf_height.max(sprite_height);
+    }
+    (width, y + shelf_height + padding)
+}
+
+fn json_map(sprites: &[Sprite], image_name: &str, (width, height): (u32, u32)) -> String {
+    let frames: serde_json::Map<String, serde_json::Value> = sprites
+        .iter()
+        .map(|sprite| {
+            let frame = json!({"x": sprite.x, "y": sprite.y, "w": sprite.image.width(), "h": sprite.image.height()});
+            (sprite.name.clone(), frame)
+        })
+        .collect();
+    let map = json!({
+        "frames": frames,
+        "meta": {"image": image_name, "size": {"w": width, "h": height}},
+    });
+    serde_json::to_string_pretty(&map).unwrap_or_default() + "\n"
+}
+
+/// Class names keep letters, digits, `-` and `_`; anything else becomes `-`.
+fn css_class(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+/// A background offset shifting the atlas left or up by `pixels`.
+fn offset(pixels: u32) -> String {
+    if pixels == 0 {
+        "0".to_string()
+    } else {
+        format!("-{}px", pixels)
+    }
+}
+
+fn css_map(sprites: &[Sprite], image_name: &str) -> String {
+    let mut css = format!(".sprite {{\n  background-image: url(\"{}\");\n  background-repeat: no-repeat;\n}}\n", image_name);
+    for sprite in sprites {
+        let _ = write!(
+            css,
+            "\n.sprite-{} {{\n  width: {}px;\n  height: {}px;\n  background-position: {} {};\n}}\n",
+            css_class(&sprite.name),
+            sprite.image.width(),
+            sprite.image.height(),
+            offset(sprite.x),
+            offset(sprite.y)
+        );
+    }
+    css
+}
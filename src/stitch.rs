@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use image::{imageops, ColorType, DynamicImage, ImageBuffer, Rgba};
+use rayon::prelude::*;
+
+use crate::transform::convert_to;
+use crate::ImageConverter;
+
+struct Tile {
+    row: u32,
+    column: u32,
+    path: PathBuf,
+}
+
+/// Reads the grid position from a tile's file name: the last two numbers in
+/// its stem, row first, so `3_5.png`, `r03_c05.tif` and `scan-3-5.jpg` are
+/// all row 3, column 5.
+fn position(path: &Path) -> Option<(u32, u32)> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let numbers: Vec<u32> = stem
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        [.., row, column] => Some((*row, *column)),
+        _ => None,
+    }
+}
+
+/// Tiles in `input` and the pixels each repeats from its neighbours. See
+/// [`read_tiles`] for what `input` can be.
+fn collect_tiles(converter: &ImageConverter, input: &Path) -> Result<(Vec<Tile>, u32), Box<dyn std::error::Error>> {
+    let (tiles, overlap) = read_tiles(converter, input)?;
+    let mut placed: HashMap<(u32, u32), &Path> = HashMap::new();
+    for tile in &tiles {
+        if let Some(other) = placed.insert((tile.row, tile.column), &tile.path) {
+            return Err(format!(
+                "{} and {} are both row {}, column {}",
+                other.display(),
+                tile.path.display(),
+                tile.row,
+                tile.column
+            )
+            .into());
+        }
+    }
+    Ok((tiles, overlap))
+}
+
+/// The tiles in `input`, in the order found, before positions are checked,
+/// and their overlap. `input` is one of:
+/// - a Deep Zoom descriptor (`name.dzi`), stitched from its largest level,
+///   or a level directory (`name_files/<level>`) beside one; tiles are named
+///   `<col>_<row>` and overlap as the descriptor says;
+/// - a map tile directory (`<z>/<x>/<y>.<ext>`), either the root, stitched
+///   from its largest zoom, or one zoom level;
+/// - a directory of row_col-named images;
+/// - an index file with one `<row> <col> <path>` line per tile, paths
+///   relative to the index.
+fn read_tiles(converter: &ImageConverter, input: &Path) -> Result<(Vec<Tile>, u32), Box<dyn std::error::Error>> {
+    if input.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dzi")) {
+        let overlap = dzi_overlap(input)?;
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let levels = input.with_file_name(format!("{}_files", stem));
+        let level = largest_level(&levels)?.ok_or_else(|| format!("no tile levels in {}", levels.display()))?;
+        return Ok((dzi_tiles(converter, &level)?, overlap));
+    }
+
+    if input.is_dir() {
+        if let Some(descriptor) = dzi_descriptor(input) {
+            return Ok((dzi_tiles(converter, input)?, dzi_overlap(&descriptor)?));
+        }
+        if let Some(level) = xyz_level(input)? {
+            return Ok((xyz_tiles(converter, &level)?, 0));
+        }
+        let tiles = converter
+            .selected_inputs(input)?
+            .into_iter()
+            .map(|path| match position(&path) {
+                Some((row, column)) => Ok(Tile { row, column, path }),
+                None => Err(format!("{}: no row and column in the file name", path.display()).into()),
+            })
+            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+        return Ok((tiles, 0));
+    }
+
+    let base = input.parent().unwrap_or(Path::new(""));
+    std::fs::read_to_string(input)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let invalid = || format!("Invalid index line: {} (expected <row> <col> <path>)", line);
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let row = parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid)?;
+            let column = parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid)?;
+            let path = parts.next().map(str::trim).filter(|path| !path.is_empty()).ok_or_else(invalid)?;
+            Ok(Tile { row, column, path: base.join(path) })
+        })
+        .collect::<Result<_, String>>()
+        .map(|tiles| (tiles, 0))
+        .map_err(Into::into)
+}
+
+/// The `name.dzi` a Deep Zoom level directory (`name_files/<level>`) belongs
+/// to, if it has one.
+fn dzi_descriptor(level: &Path) -> Option<PathBuf> {
+    let files = level.parent()?;
+    let stem = files.file_name()?.to_str()?.strip_suffix("_files")?;
+    let descriptor = files.with_file_name(format!("{}.dzi", stem));
+    descriptor.is_file().then_some(descriptor)
+}
+
+/// The `Overlap` attribute of a Deep Zoom descriptor.
+fn dzi_overlap(descriptor: &Path) -> Result<u32, Box<dyn std::error::Error>> {
+    let xml = std::fs::read_to_string(descriptor)?;
+    let value = xml
+        .split_once("Overlap=\"")
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .map(|(value, _)| value)
+        .ok_or_else(|| format!("{}: no Overlap attribute", descriptor.display()))?;
+    value
+        .parse()
+        .map_err(|_| format!("{}: invalid Overlap: {}", descriptor.display(), value).into())
+}
+
+/// Tiles of one Deep Zoom level, named `<col>_<row>`.
+fn dzi_tiles(converter: &ImageConverter, level: &Path) -> Result<Vec<Tile>, Box<dyn std::error::Error>> {
+    converter
+        .selected_inputs(level)?
+        .into_iter()
+        .map(|path| match position(&path) {
+            Some((column, row)) => Ok(Tile { row, column, path }),
+            None => Err(format!("{}: not a <col>_<row> tile name", path.display()).into()),
+        })
+        .collect()
+}
+
+/// The map zoom level to stitch from `dir`: `dir` itself when its numbered
+/// subdirectories hold tiles, the largest zoom when they hold further
+/// numbered directories, and `None` when `dir` isn't laid out as map tiles.
+fn xyz_level(dir: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    if std::fs::read_dir(dir)?.any(|entry| entry.is_ok_and(|entry| entry.path().is_file())) {
+        return Ok(None);
+    }
+    let Some(largest) = largest_level(dir)? else {
+        return Ok(None);
+    };
+    Ok(match largest_level(&largest)? {
+        Some(_) => Some(largest),
+        None => Some(dir.to_path_buf()),
+    })
+}
+
+/// Tiles of one map zoom level, in `<x>/<y>.<ext>`.
+fn xyz_tiles(converter: &ImageConverter, level: &Path) -> Result<Vec<Tile>, Box<dyn std::error::Error>> {
+    let mut tiles = Vec::new();
+    for entry in std::fs::read_dir(level)? {
+        let dir = entry?.path();
+        let Some(column) = dir.file_name().and_then(|name| name.to_str()?.parse().ok()) else {
+            continue;
+        };
+        for path in converter.selected_inputs(&dir)? {
+            let row = path
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse().ok())
+                .ok_or_else(|| format!("{}: not a <y> tile name", path.display()))?;
+            tiles.push(Tile { row, column, path });
+        }
+    }
+    Ok(tiles)
+}
+
+/// The numerically largest subdirectory of `dir` named by a number.
+fn largest_level(dir: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let mut largest: Option<(u32, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(level) = path.file_name().and_then(|name| name.to_str()?.parse::<u32>().ok()) else {
+            continue;
+        };
+        if path.is_dir() && largest.as_ref().is_none_or(|(largest, _)| level > *largest) {
+            largest = Some((level, path));
+        }
+    }
+    Ok(largest.map(|(_, path)| path))
+}
+
+/// Reassembles a grid of tiles into one image. Pixels tiles share with their
+/// neighbours (Deep Zoom overlap) are kept once. Each column is as wide as
+/// its widest tile and each row as tall as its tallest, so ragged edge tiles
+/// line up; missing tiles stay transparent. Map tiles keep the padding of
+/// their edge tiles. The result keeps 16 bits when
+/// any tile has them and drops alpha when no tile has it.
+pub fn stitch(converter: &ImageConverter, input: &Path) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let (tiles, overlap) = collect_tiles(converter, input)?;
+    if tiles.is_empty() {
+        return Err(format!("no tiles found in {}", input.display()).into());
+    }
+
+    let images = tiles
+        .par_iter()
+        .map(|tile| {
+            converter
+                .read_input(&tile.path)
+                .and_then(|data| converter.decode_image(&tile.path, &data))
+                .map_err(|e| format!("{}: {}", tile.path.display(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Numbering may start at 0 or 1; the grid starts at the smallest.
+    let first_row = tiles.iter().map(|tile| tile.row).min().unwrap_or(0);
+    let first_column = tiles.iter().map(|tile| tile.column).min().unwrap_or(0);
+    let rows = tiles.iter().map(|tile| tile.row - first_row).max().unwrap_or(0) as usize + 1;
+    let columns = tiles.iter().map(|tile| tile.column - first_column).max().unwrap_or(0) as usize + 1;
+
+    // Drop the overlap on every side that has a neighbour.
+    let images: Vec<DynamicImage> = tiles
+        .iter()
+        .zip(images)
+        .map(|(tile, image)| {
+            if overlap == 0 {
+                return image;
+            }
+            let (row, column) = ((tile.row - first_row) as usize, (tile.column - first_column) as usize);
+            let left = if column > 0 { overlap } else { 0 };
+            let top = if row > 0 { overlap } else { 0 };
+            let right = if column + 1 < columns { overlap } else { 0 };
+            let bottom = if row + 1 < rows { overlap } else { 0 };
+            let width = image.width().saturating_sub(left + right).max(1);
+            let height = image.height().saturating_sub(top + bottom).max(1);
+            image.crop_imm(left, top, width, height)
+        })
+        .collect();
+
+    let mut heights = vec![0u32; rows];
+    let mut widths = vec![0u32; columns];
+    for (tile, image) in tiles.iter().zip(&images) {
+        let (row, column) = ((tile.row - first_row) as usize, (tile.column - first_column) as usize);
+        heights[row] = heights[row].max(image.height());
+        widths[column] = widths[column].max(image.width());
+    }
+    let offsets = |sizes: &[u32]| -> Vec<u32> {
+        sizes.iter().scan(0, |offset, size| {
+            let start = *offset;
+            *offset += size;
+            Some(start)
+        })
+        .collect()
+    };
+    let (tops, lefts) = (offsets(&heights), offsets(&widths));
+    let (width, height) = (widths.iter().sum::<u32>(), heights.iter().sum::<u32>());
+
+    let missing = (rows * columns).saturating_sub(tiles.len());
+    if missing > 0 {
+        eprintln!("Warning: {} of {} tiles missing; left transparent", missing, rows * columns);
+    }
+
+    let deep = images
+        .iter()
+        .any(|image| image.color().bytes_per_pixel() / image.color().channel_count() > 1);
+    let has_alpha = missing > 0 || images.iter().any(|image| image.color().has_alpha());
+    let mut canvas = ImageBuffer::from_pixel(width, height, Rgba([0u16; 4]));
+    for (tile, image) in tiles.iter().zip(&images) {
+        let (row, column) = ((tile.row - first_row) as usize, (tile.column - first_column) as usize);
+        imageops::replace(&mut canvas, &image.to_rgba16(), i64::from(lefts[column]), i64::from(tops[row]));
+    }
+
+    let color = match (deep, has_alpha) {
+        (true, true) => ColorType::Rgba16,
+        (true, false) => ColorType::Rgb16,
+        (false, true) => ColorType::Rgba8,
+        (false, false) => ColorType::Rgb8,
+    };
+    Ok(convert_to(DynamicImage::ImageRgba16(canvas), color))
+}
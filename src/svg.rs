@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError, RgbaImage};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+
+use crate::raster::Raster;
+
+/// CSS pixels per inch, the unit SVG lengths resolve to.
+const CSS_DPI: f32 = 96.0;
+
+fn hint() -> ImageFormatHint {
+    ImageFormatHint::Name("SVG".to_string())
+}
+
+/// Whether the input is SVG, by `.svg`/`.svgz` extension or, for stdin and
+/// URLs, by an `<svg` element near the start of otherwise unrecognized data.
+pub fn is_svg(path: &Path, data: &[u8]) -> bool {
+    let by_extension = path
+        .extension()
+        .map(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "svg" | "svgz"))
+        .unwrap_or(false);
+    by_extension
+        || (image::guess_format(data).is_err()
+            && data[..data.len().min(4096)].windows(4).any(|window| window == b"<svg"))
+}
+
+/// Rasterizes an SVG (or gzipped SVGZ) document to RGBA.
+pub fn decode(data: &[u8], raster: Raster) -> Result<DynamicImage, ImageError> {
+    let error = |e: &dyn std::fmt::Display| ImageError::Decoding(DecodingError::new(hint(), e.to_string()));
+
+    // Lengths resolve at the CSS 96 DPI; `raster.dpi` only scales the output.
+    let mut options = Options::default();
+    if raster.system_fonts {
+        options.fontdb_mut().load_system_fonts();
+    }
+    let tree = Tree::from_data(data, &options).map_err(|e| error(&e))?;
+
+    let size = tree.size();
+    let (scale_x, scale_y) = raster.scale(size.width(), size.height(), CSS_DPI);
+    let width = (size.width() * scale_x).round().max(1.0) as u32;
+    let height = (size.height() * scale_y).round().max(1.0) as u32;
+
+    let mut pixmap = Pixmap::new(width, height).ok_or_else(|| error(&format!("can't allocate {}x{}", width, height)))?;
+    resvg::render(&tree, Transform::from_scale(scale_x, scale_y), &mut pixmap.as_mut());
+
+    // tiny-skia works in premultiplied alpha.
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let image = RgbaImage::from_raw(width, height, pixels).ok_or_else(|| error(&"pixmap size mismatch"))?;
+    Ok(DynamicImage::ImageRgba8(image))
+}
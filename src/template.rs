@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::dated::Timestamp;
+use crate::{remote, SupportedFormat};
+
+const PLACEHOLDERS: [&str; 9] = ["stem", "ext", "format", "width", "height", "quality", "date", "counter", "hash"];
+
+/// Hex digits `{hash}` gives without a length.
+const HASH_LENGTH: usize = 16;
+
+/// An output file name pattern from `--name-template`, such as
+/// `{stem}_{width}x{height}_q{quality}.{ext}`.
+///
+/// `{counter}` numbers the inputs of a run from 1 and `{hash}` is a hash of
+/// the input file's contents; `{counter:4}` pads to four digits and
+/// `{hash:8}` shortens to eight; no other placeholder takes a length. `{date}` is today's date (UTC) as
+/// YYYY-MM-DD. `{width}` and `{height}`, the output size, are only filled
+/// in once the image has been processed.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pattern: String,
+}
+
+/// What's known about an output before its input is decoded.
+pub struct Fields<'a> {
+    pub input: &'a Path,
+    pub stem: &'a str,
+    pub format: SupportedFormat,
+    pub quality: u8,
+    pub number: usize,
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder { name: &'a str, length: Option<usize>, source: &'a str },
+}
+
+impl Template {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        if pattern.is_empty() {
+            return Err("--name-template can't be empty".to_string());
+        }
+        for part in parts(pattern)? {
+            if let Part::Placeholder { name, length, source } = part {
+                if !PLACEHOLDERS.contains(&name) {
+                    return Err(format!(
+                        "Unknown placeholder in --name-template: {} (expected {})",
+                        source,
+                        PLACEHOLDERS.join(", ")
+                    ));
+                }
+                if length.is_some() && !matches!(name, "counter" | "hash") {
+                    return Err(format!(
+                        "Invalid length in --name-template: {} (only {{counter:N}} and {{hash:N}} take one)",
+                        source
+                    ));
+                }
+            }
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+        })
+    }
+
+    /// Whether names depend on the output size, which isn't known until
+    /// the image has been decoded and processed.
+    pub fn uses_dimensions(&self) -> bool {
+        self.pattern.contains("{width}") || self.pattern.contains("{height}")
+    }
+
+    /// Whether names depend on today's date, which `--deterministic` rules out.
+    pub fn uses_date(&self) -> bool {
+        self.pattern.contains("{date}")
+    }
+
+    /// The file name for `fields`, with `{width}` and `{height}` left in
+    /// place for [`fill_dimensions`].
+    pub fn render(&self, fields: &Fields) -> String {
+        let mut name = String::new();
+        // Parsed successfully in `parse`.
+        for part in parts(&self.pattern).unwrap_or_default() {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Placeholder { name: placeholder, length, source } => match placeholder {
+                    "stem" => name.push_str(fields.stem),
+                    "ext" => name.push_str(fields.format.extension()),
+                    "format" => name.push_str(match fields.format {
+                        SupportedFormat::Jpeg => "jpeg",
+                        format => format.extension(),
+                    }),
+                    "quality" => name.push_str(&fields.quality.to_string()),
+                    "date" => name.push_str(&today()),
+                    "counter" => name.push_str(&format!("{:0width$}", fields.number, width = length.unwrap_or(1))),
+                    "hash" => {
+                        let hash = format!("{:016x}", content_hash(fields.input));
+                        name.push_str(&hash[..length.unwrap_or(HASH_LENGTH).min(HASH_LENGTH)]);
+                    }
+                    _ => name.push_str(source),
+                },
+            }
+        }
+        name
+    }
+}
+
+/// Replaces the `{width}` and `{height}` a [`Template`] left in `path`.
+pub fn fill_dimensions(path: &Path, (width, height): (u32, u32)) -> PathBuf {
+    let path = path.to_string_lossy();
+    PathBuf::from(path.replace("{width}", &width.to_string()).replace("{height}", &height.to_string()))
+}
+
+/// Splits `pattern` into literal text and `{name}` or `{name:N}` placeholders.
+fn parts(pattern: &str) -> Result<Vec<Part<'_>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err(format!("Invalid --name-template: {} (unmatched }})", pattern));
+        }
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .filter(|&end| !rest[start + 1..end].contains('{'))
+            .ok_or_else(|| format!("Invalid --name-template: {} (unmatched {{)", pattern))?;
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let source = &rest[start..=end];
+        let inner = &rest[start + 1..end];
+        let (name, length) = match inner.split_once(':') {
+            Some((name, length)) => (
+                name,
+                Some(length.parse().map_err(|_| format!("Invalid length in --name-template: {} (expected a number)", source))?),
+            ),
+            None => (inner, None),
+        };
+        parts.push(Part::Placeholder { name, length, source });
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    Ok(parts)
+}
+
+/// 64-bit FNV-1a of the input's bytes, or of the URL itself for remote
+/// inputs, which aren't fetched just to be named.
+pub fn content_hash(input: &Path) -> u64 {
+    let text = input.to_string_lossy();
+    let data = if remote::is_url(&text) {
+        text.as_bytes().to_vec()
+    } else {
+        std::fs::read(input).unwrap_or_default()
+    };
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Today's date in UTC as YYYY-MM-DD.
+fn today() -> String {
+    Timestamp::from_system_time(SystemTime::now()).format("%Y-%m-%d")
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::transform::{overlay, Anchor};
+
+/// A single line of text burned into the image.
+#[derive(Clone)]
+pub struct Caption {
+    pub text: String,
+    pub font: FontArc,
+    /// Font size in pixels.
+    pub size: f32,
+    pub anchor: Anchor,
+    pub margin: u32,
+    pub color: Rgba<u8>,
+    /// Outline colour and width in pixels; a width of 0 disables it.
+    pub outline: Rgba<u8>,
+    pub outline_width: u32,
+}
+
+impl fmt::Debug for Caption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Caption")
+            .field("text", &self.text)
+            .field("size", &self.size)
+            .field("anchor", &self.anchor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Caption {
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let label = self.render();
+        let (x, y) = self.anchor.place(image.dimensions(), label.dimensions(), self.margin);
+        overlay(image, &label, x, y)
+    }
+
+    /// Rasterises the caption onto a transparent label just large enough to
+    /// hold the text and its outline.
+    pub fn render(&self) -> RgbaImage {
+        let font = self.font.as_scaled(PxScale::from(self.size));
+        let pad = self.outline_width as i64;
+
+        let mut glyphs = Vec::new();
+        let mut caret = 0.0;
+        let mut previous = None;
+        for c in self.text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += font.kern(previous, id);
+            }
+            glyphs.push(id.with_scale_and_position(self.size, point(caret, font.ascent())));
+            caret += font.h_advance(id);
+            previous = Some(id);
+        }
+
+        let width = (caret.ceil() as i64 + 2 * pad).max(1);
+        let height = (font.height().ceil() as i64 + 2 * pad).max(1);
+        let index = |x: i64, y: i64| (y * width + x) as usize;
+
+        let mut coverage = vec![0.0f32; (width * height) as usize];
+        for glyph in glyphs {
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, value| {
+                    let x = bounds.min.x as i64 + gx as i64 + pad;
+                    let y = bounds.min.y as i64 + gy as i64 + pad;
+                    if (0..width).contains(&x) && (0..height).contains(&y) {
+                        let cell = &mut coverage[index(x, y)];
+                        *cell = cell.max(value.min(1.0));
+                    }
+                });
+            }
+        }
+
+        // The outline is the text coverage dilated by a disc of the outline width.
+        let mut outline = vec![0.0f32; coverage.len()];
+        if pad > 0 {
+            for y in 0..height {
+                for x in 0..width {
+                    let mut strongest: f32 = 0.0;
+                    for dy in -pad..=pad {
+                        for dx in -pad..=pad {
+                            let (sx, sy) = (x + dx, y + dy);
+                            if dx * dx + dy * dy <= pad * pad
+                                && (0..width).contains(&sx)
+                                && (0..height).contains(&sy)
+                            {
+                                strongest = strongest.max(coverage[index(sx, sy)]);
+                            }
+                        }
+                    }
+                    outline[index(x, y)] = strongest;
+                }
+            }
+        }
+
+        RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+            let i = index(x as i64, y as i64);
+            let text_alpha = coverage[i] * self.color[3] as f32 / 255.0;
+            let outline_alpha = outline[i] * self.outline[3] as f32 / 255.0;
+
+            // Text "over" outline.
+            let alpha = text_alpha + outline_alpha * (1.0 - text_alpha);
+            if alpha <= 0.0 {
+                return Rgba([0, 0, 0, 0]);
+            }
+            let channel = |c: usize| {
+                let value = self.color[c] as f32 * text_alpha
+                    + self.outline[c] as f32 * outline_alpha * (1.0 - text_alpha);
+                (value / alpha).round() as u8
+            };
+            Rgba([channel(0), channel(1), channel(2), (alpha * 255.0).round() as u8])
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, ImageBuffer, ImageError};
+
+use crate::report::{Conversion, Summary};
+use crate::{ImageConverter, SupportedFormat};
+
+/// Longest side of a thumbnail unless `--thumb-size` says otherwise.
+pub const DEFAULT_SIZE: u32 = 256;
+/// Encoder quality for thumbnails: artifacts don't show at preview sizes.
+pub const QUALITY: u8 = 70;
+
+/// Writes a thumbnail of `input` fitting in a `size`x`size` box.
+pub fn generate(
+    converter: &ImageConverter,
+    input: &Path,
+    output_path: &Path,
+    format: SupportedFormat,
+    size: u32,
+) -> Result<Conversion, Box<dyn std::error::Error>> {
+    let data = converter.read_input(input)?;
+    let image = shrink(converter.process(decode(converter, input, &data, size)?), size);
+    converter.save_image(&image, &data, output_path, format)?;
+    Ok(Conversion {
+        output_dimensions: Some((image.width(), image.height())),
+        ..Conversion::new(input, data.len() as u64).with_outputs(vec![output_path.to_path_buf()])
+    })
+}
+
+/// Decodes `data` for a preview no larger than `size`: JPEGs come out of
+/// the decoder already reduced, other formats at full size.
+pub fn decode(converter: &ImageConverter, input: &Path, data: &[u8], size: u32) -> Result<DynamicImage, ImageError> {
+    match decode_jpeg_scaled(converter, data, size)? {
+        Some(image) => converter.finish_decode(input, data, image, false),
+        None => converter.decode_image(input, data),
+    }
+}
+
+/// Fits `image` in a `size`x`size` box. `thumbnail` averages whole source
+/// pixels per output pixel: much faster than Lanczos and just as clean when
+/// shrinking a long way.
+pub fn shrink(image: DynamicImage, size: u32) -> DynamicImage {
+    if image.width() > size || image.height() > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    }
+}
+
+/// Thumbnails every selected image under `input_dir` into the same layout
+/// under `output_dir`.
+pub fn generate_all(
+    converter: &ImageConverter,
+    input_dir: &Path,
+    output_dir: &Path,
+    format: SupportedFormat,
+    size: u32,
+) -> Result<Summary, Box<dyn std::error::Error>> {
+    let inputs = converter.selected_inputs(input_dir)?;
+    converter.run_batch(&inputs, 0, |input| {
+        let relative = input.strip_prefix(input_dir).unwrap_or(input);
+        let output_path: PathBuf = output_dir.join(relative).with_extension(format.extension());
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        generate(converter, input, &output_path, format, size)
+    })
+}
+
+/// Decodes a JPEG at 1/2, 1/4 or 1/8 scale straight out of the DCT, the
+/// smallest that still covers `size`, so full-resolution pixels are never
+/// produced. Returns `None` for anything that needs the regular decoder:
+/// other formats, CMYK or 16-bit JPEGs, or a corrupt stream. The full size
+/// in the header is held to `--max-pixels` first.
+fn decode_jpeg_scaled(converter: &ImageConverter, data: &[u8], size: u32) -> Result<Option<DynamicImage>, ImageError> {
+    if image::guess_format(data).ok() != Some(image::ImageFormat::Jpeg) {
+        return Ok(None);
+    }
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let Some(info) = decoder.read_info().ok().and_then(|()| decoder.info()) else {
+        return Ok(None);
+    };
+    converter.check_pixels((u32::from(info.width), u32::from(info.height)))?;
+    let requested = u16::try_from(size).unwrap_or(u16::MAX);
+    let Ok((width, height)) = decoder.scale(requested, requested) else {
+        return Ok(None);
+    };
+    let Ok(pixels) = decoder.decode() else {
+        return Ok(None);
+    };
+    let (width, height) = (u32::from(width), u32::from(height));
+    Ok(match decoder.info().map(|info| info.pixel_format) {
+        Some(jpeg_decoder::PixelFormat::RGB24) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Some(jpeg_decoder::PixelFormat::L8) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        _ => None,
+    })
+}
@@ -0,0 +1,65 @@
+use std::io::Cursor;
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageBuffer, ImageError, ImageFormat};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::ColorType;
+
+/// Number of pages (images) in a TIFF.
+pub fn page_count(data: &[u8]) -> Option<usize> {
+    let mut decoder = Decoder::new(Cursor::new(data)).ok()?;
+    let mut count = 1;
+    while decoder.more_images() {
+        decoder.next_image().ok()?;
+        count += 1;
+    }
+    Some(count)
+}
+
+/// Decodes page `index` (0-based) of a multi-page TIFF. The `image` crate
+/// only ever reads the first page.
+pub fn decode(data: &[u8], index: usize) -> Result<DynamicImage, ImageError> {
+    let error = |message: String| {
+        ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(ImageFormat::Tiff), message))
+    };
+
+    let mut decoder = Decoder::new(Cursor::new(data)).map_err(|e| error(e.to_string()))?;
+    decoder
+        .seek_to_image(index)
+        .map_err(|_| error(format!("page {} not found", index + 1)))?;
+
+    let (width, height) = decoder.dimensions().map_err(|e| error(e.to_string()))?;
+    let color = decoder.colortype().map_err(|e| error(e.to_string()))?;
+    let pixels = decoder.read_image().map_err(|e| error(e.to_string()))?;
+
+    let unsupported = || error(format!("unsupported TIFF page layout {:?}", color));
+    let image = match (color, pixels) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        _ => return Err(unsupported()),
+    };
+
+    image.ok_or_else(unsupported)
+}
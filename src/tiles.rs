@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use image::imageops::{self, FilterType};
+use image::{ColorType, DynamicImage, ImageBuffer, Rgba};
+use rayon::prelude::*;
+
+use crate::transform::{convert_to, flatten};
+use crate::{atomic, ImageConverter, SupportedFormat};
+
+/// Directory layout of a tile pyramid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Deep Zoom: `name.dzi` plus `name_files/<level>/<col>_<row>.<ext>`,
+    /// level 0 being 1x1 pixel. Read by OpenSeadragon.
+    #[default]
+    Dzi,
+    /// Slippy map: `<z>/<x>/<y>.<ext>`, zoom 0 being the whole image in one
+    /// tile. Read by Leaflet and OpenLayers.
+    Xyz,
+}
+
+impl Layout {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "dzi" | "deepzoom" => Ok(Layout::Dzi),
+            "xyz" | "google" => Ok(Layout::Xyz),
+            _ => Err(format!("Invalid tile layout: {} (expected dzi or xyz)", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub layout: Layout,
+    pub tile_size: u32,
+    /// Pixels each tile repeats from its neighbours, so viewers can blend
+    /// seams. Deep Zoom only; map tiles never overlap.
+    pub overlap: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Dzi,
+            tile_size: 256,
+            overlap: 1,
+        }
+    }
+}
+
+/// Slices `input`, run through the transform pipeline, into a pyramid of
+/// `format` tiles under `output_dir`. Each level halves the one above it.
+/// Deep Zoom edge tiles are only as large as the image; map clients expect
+/// every tile at full size, so XYZ edge tiles are padded. Returns the tile
+/// count.
+pub fn generate(
+    converter: &ImageConverter,
+    input: &Path,
+    output_dir: &Path,
+    format: SupportedFormat,
+    options: Options,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let data = converter.read_input(input)?;
+    let image = converter.process(converter.decode_image(input, &data)?);
+    let (width, height) = (image.width(), image.height());
+    let name = input.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+
+    let longest = f64::from(width.max(height));
+    // Deep Zoom goes down to a single pixel, maps to a single tile.
+    let (top, root, options) = match options.layout {
+        Layout::Dzi => (longest.log2().ceil() as u32, output_dir.join(format!("{}_files", name)), options),
+        Layout::Xyz => (
+            (longest / f64::from(options.tile_size)).log2().ceil().max(0.0) as u32,
+            output_dir.to_path_buf(),
+            Options { overlap: 0, ..options },
+        ),
+    };
+
+    let mut count = 0;
+    let mut level_image = image;
+    for level in (0..=top).rev() {
+        if level < top {
+            let scale = 1u64 << (top - level);
+            let side = |full: u32| (u64::from(full).div_ceil(scale)).max(1) as u32;
+            level_image = level_image.resize_exact(side(width), side(height), FilterType::Triangle);
+        }
+        count += write_level(converter, &level_image, &root, level, format, options)?;
+    }
+
+    if options.layout == Layout::Dzi {
+        let descriptor = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"{}\" Overlap=\"{}\" Format=\"{}\">\n  <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+            options.tile_size,
+            options.overlap,
+            format.extension(),
+            width,
+            height
+        );
+        atomic::write(&output_dir.join(format!("{}.dzi", name)), descriptor.as_bytes())?;
+    }
+    Ok(count)
+}
+
+fn write_level(
+    converter: &ImageConverter,
+    image: &DynamicImage,
+    root: &Path,
+    level: u32,
+    format: SupportedFormat,
+    options: Options,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let Options { layout, tile_size, overlap } = options;
+    let (width, height) = (image.width(), image.height());
+    let (columns, rows) = (width.div_ceil(tile_size), height.div_ceil(tile_size));
+
+    let tiles: Vec<(u32, u32)> = (0..columns).flat_map(|column| (0..rows).map(move |row| (column, row))).collect();
+    let path = |column: u32, row: u32| -> PathBuf {
+        match layout {
+            Layout::Dzi => root.join(level.to_string()).join(format!("{}_{}.{}", column, row, format.extension())),
+            Layout::Xyz => root
+                .join(level.to_string())
+                .join(column.to_string())
+                .join(format!("{}.{}", row, format.extension())),
+        }
+    };
+
+    tiles
+        .par_iter()
+        .map(|&(column, row)| -> Result<(), String> {
+            let x = (column * tile_size).saturating_sub(overlap);
+            let y = (row * tile_size).saturating_sub(overlap);
+            let right = ((column + 1) * tile_size + overlap).min(width);
+            let bottom = ((row + 1) * tile_size + overlap).min(height);
+            let tile = image.crop_imm(x, y, right - x, bottom - y);
+            let tile = if layout == Layout::Xyz && (tile.width(), tile.height()) != (tile_size, tile_size) {
+                pad(converter, &tile, tile_size, format)
+            } else {
+                tile
+            };
+
+            let tile_path = path(column, row);
+            let fail = |e: &dyn std::fmt::Display| format!("{}: {}", tile_path.display(), e);
+            if let Some(parent) = tile_path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| fail(&e))?;
+            }
+            converter.save_image(&tile, &[], &tile_path, format).map_err(|e| fail(&e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tiles.len())
+}
+
+/// Fills out an edge tile to `tile_size` square: transparent where `format`
+/// has alpha, otherwise in `--background` (white by default).
+fn pad(converter: &ImageConverter, tile: &DynamicImage, tile_size: u32, format: SupportedFormat) -> DynamicImage {
+    let deep = tile.color().bytes_per_pixel() / tile.color().channel_count() > 1;
+    let mut canvas = ImageBuffer::from_pixel(tile_size, tile_size, Rgba([0u16; 4]));
+    imageops::replace(&mut canvas, &tile.to_rgba16(), 0, 0);
+    let padded = convert_to(DynamicImage::ImageRgba16(canvas), if deep { ColorType::Rgba16 } else { ColorType::Rgba8 });
+    if format.has_alpha() {
+        padded
+    } else {
+        flatten(&padded, converter.background.unwrap_or(Rgba([255; 4])))
+    }
+}
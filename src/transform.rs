@@ -0,0 +1,659 @@
+use std::sync::Arc;
+
+use image::imageops::FilterType;
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, Rgba, RgbaImage};
+
+use crate::adjust::{Curves, Effect, Hue, Levels, Tone, WhiteBalance};
+use crate::deskew::Deskew;
+use crate::equalize::Equalize;
+use crate::filter::{Blur, Denoise, Sharpen};
+use crate::lut::Lut;
+use crate::text::Caption;
+
+/// A single step applied to the decoded image before it is encoded.
+#[derive(Debug, Clone)]
+pub enum Transform {
+    Deskew(Deskew),
+    Trim(Trim),
+    Rotate(Rotation),
+    Flip(Flip),
+    Crop(Crop),
+    SmartCrop(SmartCrop),
+    Denoise(Denoise),
+    Resize(Resize),
+    WhiteBalance(WhiteBalance),
+    Levels(Levels),
+    Equalize(Equalize),
+    Tone(Tone),
+    Curves(Curves),
+    Hue(Hue),
+    Lut(Arc<Lut>),
+    Effect(Effect),
+    Blur(Blur),
+    Sharpen(Sharpen),
+    Pad(Pad),
+    Grayscale,
+    Watermark(Watermark),
+    Text(Caption),
+}
+
+impl Transform {
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Transform::Deskew(deskew) => deskew.apply(image),
+            Transform::Trim(trim) => trim.apply(image),
+            Transform::Rotate(rotation) => rotation.apply(image),
+            Transform::Flip(flip) => flip.apply(image),
+            Transform::Crop(crop) => crop.apply(image),
+            Transform::SmartCrop(crop) => crop.apply(image),
+            Transform::Denoise(denoise) => denoise.apply(image),
+            Transform::Resize(resize) => resize.apply(image),
+            Transform::WhiteBalance(balance) => balance.apply(image),
+            Transform::Levels(levels) => levels.apply(image),
+            Transform::Equalize(equalize) => equalize.apply(image),
+            Transform::Tone(tone) => tone.apply(image),
+            Transform::Curves(curves) => curves.apply(image),
+            Transform::Hue(hue) => hue.apply(image),
+            Transform::Lut(lut) => lut.apply(image),
+            Transform::Effect(effect) => effect.apply(image),
+            Transform::Blur(blur) => blur.apply(image),
+            Transform::Sharpen(sharpen) => sharpen.apply(image),
+            Transform::Pad(pad) => pad.apply(image),
+            // Rec. 709 luma weights; alpha and 16-bit depth are kept, and the
+            // single-channel result lets PNG/JPEG store one sample per pixel.
+            Transform::Grayscale => image.grayscale(),
+            Transform::Watermark(watermark) => watermark.apply(image),
+            Transform::Text(caption) => caption.apply(image),
+        }
+    }
+}
+
+/// Converts `image` back to `color` after an operation that had to work on a
+/// wider pixel format.
+pub fn convert_to(image: DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.into_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.into_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.into_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(image.into_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.into_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.into_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.into_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.into_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.into_rgb32f()),
+        _ => DynamicImage::ImageRgba32F(image.into_rgba32f()),
+    }
+}
+
+/// Composites `layer` onto `image` with its top-left corner at (`x`, `y`).
+/// Images with more than 8 bits per sample are composited in 16 bits so
+/// they keep their depth.
+pub fn overlay(image: DynamicImage, layer: &RgbaImage, x: i64, y: i64) -> DynamicImage {
+    let color = image.color();
+    let composited = if color.bytes_per_pixel() / color.channel_count() > 1 {
+        let mut canvas = image.into_rgba16();
+        image::imageops::overlay(&mut canvas, &DynamicImage::ImageRgba8(layer.clone()).into_rgba16(), x, y);
+        DynamicImage::ImageRgba16(canvas)
+    } else {
+        let mut canvas = image.into_rgba8();
+        image::imageops::overlay(&mut canvas, layer, x, y);
+        DynamicImage::ImageRgba8(canvas)
+    };
+    convert_to(composited, color)
+}
+
+/// Composites `image` over an opaque `background` and drops the alpha
+/// channel, keeping the image's depth and gray or color layout.
+pub fn flatten(image: &DynamicImage, background: Rgba<u8>) -> DynamicImage {
+    let opaque = match image.color() {
+        ColorType::La8 => ColorType::L8,
+        ColorType::La16 => ColorType::L16,
+        ColorType::Rgba8 => ColorType::Rgb8,
+        ColorType::Rgba16 => ColorType::Rgb16,
+        ColorType::Rgba32F => ColorType::Rgb32F,
+        color => color,
+    };
+    let fill = background.0.map(|channel| f32::from(channel) / 255.0);
+    let mut pixels = image.to_rgba32f();
+    for pixel in pixels.pixels_mut() {
+        let alpha = pixel[3];
+        for channel in 0..3 {
+            pixel[channel] = pixel[channel] * alpha + fill[channel] * (1.0 - alpha);
+        }
+        pixel[3] = 1.0;
+    }
+    convert_to(DynamicImage::ImageRgba32F(pixels), opaque)
+}
+
+/// Parses `#rgb`, `#rrggbb`, `#rrggbbaa`, `white`, `black` or `transparent`.
+pub fn parse_color(value: &str) -> Result<Rgba<u8>, String> {
+    let invalid = || format!("Invalid color: {} (expected #rrggbb or #rrggbbaa)", value);
+    match value {
+        "white" => return Ok(Rgba([255, 255, 255, 255])),
+        "black" => return Ok(Rgba([0, 0, 0, 255])),
+        "transparent" => return Ok(Rgba([0, 0, 0, 0])),
+        _ => {}
+    }
+
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+
+    match digits.as_slice() {
+        [r, g, b] => Ok(Rgba([r * 17, g * 17, b * 17, 255])),
+        [r1, r2, g1, g2, b1, b2] => Ok(Rgba([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2, 255])),
+        [r1, r2, g1, g2, b1, b2, a1, a2] => {
+            Ok(Rgba([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2, a1 * 16 + a2]))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// One of nine placement points on the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "top-left" => Ok(Anchor::TopLeft),
+            "top" => Ok(Anchor::Top),
+            "top-right" => Ok(Anchor::TopRight),
+            "left" => Ok(Anchor::Left),
+            "center" => Ok(Anchor::Center),
+            "right" => Ok(Anchor::Right),
+            "bottom-left" => Ok(Anchor::BottomLeft),
+            "bottom" => Ok(Anchor::Bottom),
+            "bottom-right" => Ok(Anchor::BottomRight),
+            _ => Err(format!(
+                "Invalid position: {} (expected top-left, top, top-right, left, center, right, bottom-left, bottom or bottom-right)",
+                name
+            )),
+        }
+    }
+
+    /// Top-left corner for an `inner`-sized box placed inside `outer`,
+    /// keeping `margin` pixels from the edges it is anchored to.
+    pub fn place(self, outer: (u32, u32), inner: (u32, u32), margin: u32) -> (i64, i64) {
+        let axis = |outer: u32, inner: u32, start: bool, end: bool| -> i64 {
+            let (outer, inner, margin) = (outer as i64, inner as i64, margin as i64);
+            match (start, end) {
+                (true, _) => margin,
+                (_, true) => outer - inner - margin,
+                _ => (outer - inner) / 2,
+            }
+        };
+
+        use Anchor::*;
+        let left = matches!(self, TopLeft | Left | BottomLeft);
+        let right = matches!(self, TopRight | Right | BottomRight);
+        let top = matches!(self, TopLeft | Top | TopRight);
+        let bottom = matches!(self, BottomLeft | Bottom | BottomRight);
+
+        (axis(outer.0, inner.0, left, right), axis(outer.1, inner.1, top, bottom))
+    }
+}
+
+/// Composites a logo onto the image.
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    pub logo: Arc<RgbaImage>,
+    pub anchor: Anchor,
+    /// Multiplier applied to the logo's own alpha, 0.0-1.0.
+    pub opacity: f32,
+    pub margin: u32,
+    /// Logo width as a fraction of the output width, so the mark looks the
+    /// same on every size in a batch.
+    pub scale: f32,
+}
+
+impl Watermark {
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let width = ((image.width() as f32 * self.scale).round() as u32).max(1);
+        let height = ((self.logo.height() as f32 * width as f32 / self.logo.width() as f32).round() as u32).max(1);
+        let mut logo = image::imageops::resize(self.logo.as_ref(), width, height, FilterType::CatmullRom);
+        for pixel in logo.pixels_mut() {
+            pixel[3] = (pixel[3] as f32 * self.opacity).round() as u8;
+        }
+
+        let (x, y) = self.anchor.place(image.dimensions(), logo.dimensions(), self.margin);
+        overlay(image, &logo, x, y)
+    }
+}
+
+/// Removes uniform borders: transparent padding around logos, white margins
+/// around scans. The border color is taken from the top-left pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trim {
+    /// Largest per-channel difference (0-255) still counted as border.
+    pub tolerance: u8,
+}
+
+impl Trim {
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        let pixels = image.to_rgba16();
+        let (width, height) = pixels.dimensions();
+        if width == 0 || height == 0 {
+            return image;
+        }
+
+        let tolerance = i32::from(self.tolerance) * 257;
+        let border = pixels.get_pixel(0, 0).0;
+        let is_border = |x: u32, y: u32| {
+            let pixel = pixels.get_pixel(x, y).0;
+            // Fully transparent borders match anything invisible enough,
+            // whatever color the hidden pixels carry.
+            if border[3] == 0 {
+                return i32::from(pixel[3]) <= tolerance;
+            }
+            pixel.iter().zip(border).all(|(&a, b)| (i32::from(a) - i32::from(b)).abs() <= tolerance)
+        };
+
+        let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+        for y in 0..height {
+            for x in 0..width {
+                if !is_border(x, y) {
+                    left = left.min(x);
+                    right = right.max(x);
+                    top = top.min(y);
+                    bottom = bottom.max(y);
+                }
+            }
+        }
+
+        // A uniform image has nothing to keep; leave it alone.
+        if left > right {
+            return image;
+        }
+        image.crop_imm(left, top, right - left + 1, bottom - top + 1)
+    }
+}
+
+/// Clockwise rotation in quarter turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    pub fn parse(degrees: &str) -> Result<Self, String> {
+        match degrees {
+            "90" => Ok(Rotation::Rotate90),
+            "180" => Ok(Rotation::Rotate180),
+            "270" | "-90" => Ok(Rotation::Rotate270),
+            _ => Err(format!("Invalid rotation: {} (expected 90, 180 or 270)", degrees)),
+        }
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Rotation::Rotate90 => image.rotate90(),
+            Rotation::Rotate180 => image.rotate180(),
+            Rotation::Rotate270 => image.rotate270(),
+        }
+    }
+}
+
+/// Mirrors the image across the vertical (`h`) or horizontal (`v`) axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+impl Flip {
+    pub fn parse(axis: &str) -> Result<Self, String> {
+        match axis {
+            "h" | "horizontal" => Ok(Flip::Horizontal),
+            "v" | "vertical" => Ok(Flip::Vertical),
+            _ => Err(format!("Invalid flip: {} (expected h or v)", axis)),
+        }
+    }
+
+    pub fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Flip::Horizontal => image.fliph(),
+            Flip::Vertical => image.flipv(),
+        }
+    }
+}
+
+/// A length in pixels or relative to the matching image dimension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl Length {
+    fn parse(value: &str) -> Option<Self> {
+        match value.strip_suffix('%') {
+            Some(percent) => percent
+                .parse()
+                .ok()
+                .filter(|percent: &f32| (0.0..=100.0).contains(percent))
+                .map(Length::Percent),
+            None => value.parse().ok().map(Length::Pixels),
+        }
+    }
+
+    fn resolve(self, total: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => (total as f64 * percent as f64 / 100.0).round() as u32,
+        }
+    }
+}
+
+/// Where a crop window is placed within the source image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CropAnchor {
+    Offset { x: Length, y: Length },
+    Center,
+}
+
+/// Cuts a `width`x`height` window out of the image. Like ImageMagick, a
+/// window reaching past the right or bottom edge is clipped to the image
+/// bounds, so it comes out smaller; one starting outside the image leaves
+/// the image uncropped, with a warning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub width: Length,
+    pub height: Length,
+    pub anchor: CropAnchor,
+}
+
+impl Crop {
+    /// Parses `WxH+X+Y`; each component may be pixels or a percentage.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid crop spec: {} (expected WxH+X+Y)", spec);
+
+        let mut parts = spec.split('+');
+        let size = parts.next().ok_or_else(invalid)?;
+        let x = parts.next().and_then(Length::parse).ok_or_else(invalid)?;
+        let y = parts.next().and_then(Length::parse).ok_or_else(invalid)?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let (width, height) = Self::parse_size(size).ok_or_else(invalid)?;
+        Ok(Crop { width, height, anchor: CropAnchor::Offset { x, y } })
+    }
+
+    /// Parses `WxH` for a window centred on the image.
+    pub fn parse_center(spec: &str) -> Result<Self, String> {
+        let (width, height) = Self::parse_size(spec)
+            .ok_or_else(|| format!("Invalid crop spec: {} (expected WxH)", spec))?;
+        Ok(Crop { width, height, anchor: CropAnchor::Center })
+    }
+
+    fn parse_size(size: &str) -> Option<(Length, Length)> {
+        let (width, height) = size.split_once(['x', 'X'])?;
+        let (width, height) = (Length::parse(width)?, Length::parse(height)?);
+        let is_empty = |length| matches!(length, Length::Pixels(0)) || length == Length::Percent(0.0);
+        (!is_empty(width) && !is_empty(height)).then_some((width, height))
+    }
+
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let (src_width, src_height) = (image.width(), image.height());
+        let width = self.width.resolve(src_width).clamp(1, src_width);
+        let height = self.height.resolve(src_height).clamp(1, src_height);
+
+        let (x, y) = match self.anchor {
+            CropAnchor::Offset { x, y } => (x.resolve(src_width), y.resolve(src_height)),
+            CropAnchor::Center => ((src_width - width) / 2, (src_height - height) / 2),
+        };
+        if x >= src_width || y >= src_height {
+            eprintln!(
+                "Warning: crop offset +{}+{} is outside the {}x{} image; not cropping",
+                x, y, src_width, src_height
+            );
+            return image;
+        }
+
+        image.crop_imm(x, y, width.min(src_width - x), height.min(src_height - y))
+    }
+}
+
+/// A width-to-height ratio such as `16:9`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatio(pub f64);
+
+impl AspectRatio {
+    /// Parses `W:H` (or `W/H`) or a single decimal like `1.5`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid aspect ratio: {} (expected W:H, e.g. 16:9)", spec);
+        let ratio = match spec.split_once([':', '/']) {
+            Some((width, height)) => {
+                let width: f64 = width.trim().parse().map_err(|_| invalid())?;
+                let height: f64 = height.trim().parse().map_err(|_| invalid())?;
+                width / height
+            }
+            None => spec.trim().parse().map_err(|_| invalid())?,
+        };
+        if !(ratio > 0.0 && ratio.is_finite()) {
+            return Err(invalid());
+        }
+        Ok(AspectRatio(ratio))
+    }
+}
+
+/// Letterboxes the image to an aspect ratio: the canvas grows on one axis
+/// and the image sits centred on it, unscaled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pad {
+    pub ratio: AspectRatio,
+    pub color: Rgba<u8>,
+}
+
+impl Pad {
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let (width, height) = (image.width(), image.height());
+        let current = f64::from(width) / f64::from(height);
+        let (canvas_width, canvas_height) = if current < self.ratio.0 {
+            ((f64::from(height) * self.ratio.0).round() as u32, height)
+        } else {
+            (width, (f64::from(width) / self.ratio.0).round() as u32)
+        };
+        if (canvas_width, canvas_height) == (width, height) {
+            return image;
+        }
+
+        // A translucent fill needs somewhere to keep its alpha.
+        let color = match image.color() {
+            ColorType::L8 if self.color[3] < 255 => ColorType::La8,
+            ColorType::L16 if self.color[3] < 255 => ColorType::La16,
+            ColorType::Rgb8 if self.color[3] < 255 => ColorType::Rgba8,
+            ColorType::Rgb16 if self.color[3] < 255 => ColorType::Rgba16,
+            ColorType::Rgb32F if self.color[3] < 255 => ColorType::Rgba32F,
+            color => color,
+        };
+        let (x, y) = ((canvas_width - width) / 2, (canvas_height - height) / 2);
+        let fill = self.color.0.map(|channel| u16::from(channel) * 257);
+        let mut canvas = ImageBuffer::from_pixel(canvas_width, canvas_height, Rgba(fill));
+        image::imageops::replace(&mut canvas, &image.to_rgba16(), i64::from(x), i64::from(y));
+        convert_to(DynamicImage::ImageRgba16(canvas), color)
+    }
+}
+
+/// Edge-energy analysis runs on a copy at most this many pixels across.
+const SMART_CROP_ANALYSIS_SIZE: u32 = 256;
+
+/// Crops to an aspect ratio, keeping the largest window that fits and
+/// sliding it to where the image is busiest (most edge energy) rather than
+/// the centre, so subjects off-centre don't lose their heads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmartCrop {
+    pub ratio: AspectRatio,
+}
+
+impl SmartCrop {
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let (width, height) = (image.width(), image.height());
+        let horizontal = f64::from(width) / f64::from(height) > self.ratio.0;
+        let (crop_width, crop_height) = if horizontal {
+            (((f64::from(height) * self.ratio.0).round() as u32).clamp(1, width), height)
+        } else {
+            (width, ((f64::from(width) / self.ratio.0).round() as u32).clamp(1, height))
+        };
+        if (crop_width, crop_height) == (width, height) {
+            return image;
+        }
+
+        let scale = (f64::from(SMART_CROP_ANALYSIS_SIZE) / f64::from(width.max(height))).min(1.0);
+        let scaled = |side: u32| ((f64::from(side) * scale).round() as u32).max(1);
+        let luma = image.resize_exact(scaled(width), scaled(height), FilterType::Triangle).into_luma8();
+
+        // Gradient magnitude summed along the axis the window slides on.
+        let (length, window) = if horizontal {
+            (luma.width(), scaled(crop_width).min(luma.width()))
+        } else {
+            (luma.height(), scaled(crop_height).min(luma.height()))
+        };
+        let mut energy = vec![0u64; length as usize];
+        for (x, y, pixel) in luma.enumerate_pixels() {
+            let value = i32::from(pixel[0]);
+            let right = luma.get_pixel((x + 1).min(luma.width() - 1), y)[0];
+            let below = luma.get_pixel(x, (y + 1).min(luma.height() - 1))[0];
+            let gradient = (value - i32::from(right)).unsigned_abs() + (value - i32::from(below)).unsigned_abs();
+            energy[if horizontal { x } else { y } as usize] += u64::from(gradient);
+        }
+
+        // Best window by a running sum; ties go to the one nearest the centre.
+        let centre = (length - window) / 2;
+        let mut sum: u64 = energy[..window as usize].iter().sum();
+        let mut best = (sum, 0u32);
+        for start in 1..=length - window {
+            sum = sum + energy[(start + window - 1) as usize] - energy[(start - 1) as usize];
+            if sum > best.0 || (sum == best.0 && start.abs_diff(centre) < best.1.abs_diff(centre)) {
+                best = (sum, start);
+            }
+        }
+
+        let offset = (f64::from(best.1) / scale).round() as u32;
+        if horizontal {
+            image.crop_imm(offset.min(width - crop_width), 0, crop_width, crop_height)
+        } else {
+            image.crop_imm(0, offset.min(height - crop_height), crop_width, crop_height)
+        }
+    }
+}
+
+/// How a resize treats the aspect ratio when both dimensions are given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale to fit inside the box, preserving the aspect ratio.
+    #[default]
+    Fit,
+    /// Scale to cover the box, preserving the aspect ratio, then crop the overflow.
+    Fill,
+    /// Stretch to exactly the requested size.
+    Exact,
+}
+
+/// Target size as given on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeSpec {
+    /// `WxH`, `Wx`, `xH` or a bare `W`; a missing side follows the aspect ratio.
+    Dimensions { width: Option<u32>, height: Option<u32> },
+    /// `N%` of the source size.
+    Percent(f32),
+}
+
+impl ResizeSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid resize spec: {} (expected WxH, Wx, xH or N%)", spec);
+
+        if let Some(percent) = spec.strip_suffix('%') {
+            let percent: f32 = percent.parse().map_err(|_| invalid())?;
+            if !(percent > 0.0 && percent.is_finite()) {
+                return Err(invalid());
+            }
+            return Ok(ResizeSpec::Percent(percent));
+        }
+
+        let (width, height) = spec.split_once(['x', 'X']).unwrap_or((spec, ""));
+        let parse_side = |side: &str| -> Result<Option<u32>, String> {
+            if side.is_empty() {
+                return Ok(None);
+            }
+            match side.parse() {
+                Ok(0) | Err(_) => Err(invalid()),
+                Ok(value) => Ok(Some(value)),
+            }
+        };
+
+        let (width, height) = (parse_side(width)?, parse_side(height)?);
+        if width.is_none() && height.is_none() {
+            return Err(invalid());
+        }
+        Ok(ResizeSpec::Dimensions { width, height })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Resize {
+    pub spec: ResizeSpec,
+    pub mode: ResizeMode,
+    /// Leaves images that already fit the target alone instead of
+    /// enlarging them.
+    pub shrink_only: bool,
+}
+
+impl Resize {
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let (src_width, src_height) = (image.width(), image.height());
+        if self.shrink_only && self.fits(src_width, src_height) {
+            return image;
+        }
+        let scale = |value: u32, factor: f64| ((value as f64 * factor).round() as u32).max(1);
+
+        let (width, height) = match self.spec {
+            ResizeSpec::Percent(percent) => {
+                let factor = percent as f64 / 100.0;
+                (scale(src_width, factor), scale(src_height, factor))
+            }
+            ResizeSpec::Dimensions { width: Some(width), height: Some(height) } => {
+                return match self.mode {
+                    ResizeMode::Fit => image.resize(width, height, FilterType::Lanczos3),
+                    ResizeMode::Fill => image.resize_to_fill(width, height, FilterType::Lanczos3),
+                    ResizeMode::Exact => image.resize_exact(width, height, FilterType::Lanczos3),
+                };
+            }
+            ResizeSpec::Dimensions { width: Some(width), height: None } => {
+                (width, scale(src_height, width as f64 / src_width as f64))
+            }
+            ResizeSpec::Dimensions { width: None, height: Some(height) } => {
+                (scale(src_width, height as f64 / src_height as f64), height)
+            }
+            ResizeSpec::Dimensions { width: None, height: None } => return image,
+        };
+
+        image.resize_exact(width, height, FilterType::Lanczos3)
+    }
+
+    fn fits(&self, width: u32, height: u32) -> bool {
+        match self.spec {
+            ResizeSpec::Percent(percent) => percent >= 100.0,
+            ResizeSpec::Dimensions { width: max_width, height: max_height } => {
+                max_width.is_none_or(|max| width <= max) && max_height.is_none_or(|max| height <= max)
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat};
+
+use crate::{animation, compare, is_stdio, jxl, ImageConverter, SupportedFormat};
+
+/// Checks each written output with `--verify`, so an encoder bug or a bad
+/// write fails the input instead of counting as converted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Verify {
+    /// Lowest PSNR in dB the decoded output may have against the pixels it
+    /// was encoded from.
+    pub min_psnr: Option<f64>,
+}
+
+impl Verify {
+    /// Reads `output_path` back, makes sure it holds exactly `encoded`, and
+    /// decodes it to compare its size (and, with a bound, its pixels) with
+    /// `image`. AVIF can't be decoded here, nor ICO holding an RGB PNG, so
+    /// only their header size is checked.
+    pub fn check(
+        &self,
+        converter: &ImageConverter,
+        image: &DynamicImage,
+        encoded: &[u8],
+        output_path: &Path,
+        format: SupportedFormat,
+    ) -> Result<(), String> {
+        check_written(encoded, output_path)?;
+
+        let expected = (image.width(), image.height());
+        let decoded = match format {
+            SupportedFormat::Avif | SupportedFormat::Ico => None,
+            SupportedFormat::Jxl => Some(jxl::decode(encoded).map_err(|e| format!("output doesn't decode: {}", e))?),
+            _ => {
+                let image_format = ImageFormat::from_extension(format.extension())
+                    .ok_or_else(|| format!("can't decode {} output", format.extension()))?;
+                Some(
+                    image::load_from_memory_with_format(encoded, image_format)
+                        .map_err(|e| format!("output doesn't decode: {}", e))?,
+                )
+            }
+        };
+        let dimensions = match &decoded {
+            Some(decoded) => (decoded.width(), decoded.height()),
+            None => header_dimensions(format, encoded)
+                .ok_or_else(|| format!("no image size in the {} output", format.extension().to_uppercase()))?,
+        };
+
+        if dimensions != expected {
+            // `--max-size` with `--allow-downscale` may shrink the image to
+            // fit, keeping its proportions.
+            let downscaled = converter.max_size.is_some()
+                && converter.allow_downscale
+                && dimensions.0 <= expected.0
+                && dimensions.1 <= expected.1
+                && (dimensions.0 as f64 / dimensions.1 as f64 - expected.0 as f64 / expected.1 as f64).abs() < 0.02;
+            if !downscaled {
+                return Err(format!(
+                    "output is {}x{}, expected {}x{}",
+                    dimensions.0, dimensions.1, expected.0, expected.1
+                ));
+            }
+            return Ok(());
+        }
+
+        if let (Some(min_psnr), Some(decoded)) = (self.min_psnr, decoded) {
+            // Alpha dropped by a format without it isn't a difference.
+            let (reference, decoded) = if format.has_alpha() {
+                (image.clone(), decoded)
+            } else {
+                (DynamicImage::ImageRgb8(image.to_rgb8()), DynamicImage::ImageRgb8(decoded.to_rgb8()))
+            };
+            let psnr = compare::psnr(&reference, &decoded);
+            if psnr < min_psnr {
+                return Err(format!("output PSNR {:.2} dB is below {} dB", psnr, min_psnr));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads an animated `output_path` back, makes sure it holds exactly
+    /// `encoded`, and decodes it to compare its frame count and canvas size
+    /// with what was encoded.
+    pub fn check_animation(&self, encoded: &[u8], output_path: &Path, frames: usize, canvas: (u32, u32)) -> Result<(), String> {
+        check_written(encoded, output_path)?;
+        let decoded = animation::decode(encoded).map_err(|e| format!("output doesn't decode: {}", e))?;
+        if decoded.frames.len() != frames {
+            return Err(format!("output has {} frames, expected {}", decoded.frames.len(), frames));
+        }
+        if let Some(frame) = decoded.frames.iter().find(|frame| frame.buffer().dimensions() != canvas) {
+            let (width, height) = frame.buffer().dimensions();
+            return Err(format!("output frame is {}x{}, expected {}x{}", width, height, canvas.0, canvas.1));
+        }
+        Ok(())
+    }
+}
+
+/// Makes sure the file at `output_path`, unless it's stdout, holds exactly
+/// `encoded`.
+fn check_written(encoded: &[u8], output_path: &Path) -> Result<(), String> {
+    if is_stdio(output_path) {
+        return Ok(());
+    }
+    let written = std::fs::read(output_path).map_err(|e| format!("can't read the output back: {}", e))?;
+    if written != encoded {
+        return Err(format!(
+            "the file on disk differs from what was encoded ({} bytes written, {} expected)",
+            written.len(),
+            encoded.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Width and height from the `ispe` property of an AVIF file, or the first
+/// directory entry of an ICO one.
+fn header_dimensions(format: SupportedFormat, data: &[u8]) -> Option<(u32, u32)> {
+    if format == SupportedFormat::Ico {
+        // A size byte of 0 stands for 256.
+        let size = |offset: usize| data.get(offset).map(|&size| if size == 0 { 256 } else { u32::from(size) });
+        return Some((size(6)?, size(7)?));
+    }
+    let start = data.windows(4).position(|window| window == b"ispe")? + 8;
+    let field = |offset: usize| {
+        data.get(start + offset..start + offset + 4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    Some((field(0)?, field(4)?))
+}
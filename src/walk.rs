@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+/// Why a directory link was left out of the walk.
+pub const LOOP: &str = "symbolic link loop";
+
+/// What batch runs do with symbolic links met while walking an input
+/// directory. A link given directly on the command line is always used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Treat links like the files and directories they point to. A
+    /// directory link back into one of its own parents is skipped rather
+    /// than walked forever.
+    #[default]
+    Follow,
+    /// Leave links out.
+    Skip,
+}
+
+/// Which entries of an input directory a batch run looks at.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub symlinks: Symlinks,
+    /// Levels of subdirectories descended into in recursive mode; 0 keeps
+    /// to the input directory itself.
+    pub max_depth: Option<usize>,
+    /// Whether files and directories whose names start with a dot count.
+    pub include_hidden: bool,
+}
+
+/// Every file under `dir`, descending into subdirectories when
+/// `recursive`. Entries left out because of `options`, or because they
+/// would loop, are added to `skipped` with the reason.
+pub fn files(
+    dir: &Path,
+    recursive: bool,
+    options: Options,
+    skipped: &mut Vec<(PathBuf, &'static str)>,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut walk = Walk {
+        recursive,
+        options,
+        ancestors: vec![std::fs::canonicalize(dir)?],
+        files: Vec::new(),
+        skipped,
+    };
+    walk.visit(dir, 0)?;
+    Ok(walk.files)
+}
+
+struct Walk<'a> {
+    recursive: bool,
+    options: Options,
+    /// Resolved paths of the directories being walked, to spot loops.
+    ancestors: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    skipped: &'a mut Vec<(PathBuf, &'static str)>,
+}
+
+impl Walk<'_> {
+    fn visit(&mut self, dir: &Path, depth: usize) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_dir() && !self.recursive {
+                continue;
+            }
+            if !self.options.include_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+                self.skipped.push((path, "hidden (--include-hidden)"));
+            } else if self.options.symlinks == Symlinks::Skip && entry.file_type()?.is_symlink() {
+                self.skipped.push((path, "symbolic link (--no-follow-symlinks)"));
+            } else if path.is_dir() {
+                if self.options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                    self.skipped.push((path, "deeper than --max-depth"));
+                    continue;
+                }
+                let target = std::fs::canonicalize(&path)?;
+                if self.ancestors.contains(&target) {
+                    self.skipped.push((path, LOOP));
+                    continue;
+                }
+                self.ancestors.push(target);
+                self.visit(&path, depth + 1)?;
+                self.ancestors.pop();
+            } else if path.is_file() {
+                self.files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
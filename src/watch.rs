@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::report;
+use crate::{ImageConverter, SupportedFormat};
+
+/// How long a file must go without further events before it is converted.
+/// Uploads and camera transfers usually arrive as a burst of writes.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Converts every image created or modified under `input_dir` until the
+/// process is interrupted.
+pub fn watch(
+    converter: &ImageConverter,
+    input_dir: &Path,
+    output_dir: &Path,
+    target_format: SupportedFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(output_dir)?;
+    // Event paths are absolute, so compare against canonical directories.
+    let input_dir = input_dir.canonicalize()?;
+    let output_dir = output_dir.canonicalize()?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mode = if converter.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(&input_dir, mode)?;
+
+    // With --json, stdout carries only events.
+    let banner = format!("Watching {} for new images (Ctrl+C to stop)...", input_dir.display());
+    if converter.json {
+        eprintln!("{}", banner);
+    } else {
+        println!("{}", banner);
+    }
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    // `{counter}` numbers the files converted while watching.
+    let mut converted = 0;
+    loop {
+        match rx.recv_timeout(SETTLE_TIME) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        // Our own outputs must not trigger further conversions.
+                        if !path.starts_with(&output_dir) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Ok(Err(e)) => eprintln!("Watch error: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, seen)| seen.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in settled {
+            pending.remove(&path);
+            let relative = path.strip_prefix(&input_dir).unwrap_or(&path);
+            if !path.is_file()
+                || !ImageConverter::is_supported(&path)
+                || !converter.is_selected(relative)
+            {
+                continue;
+            }
+
+            converted += 1;
+            let output_path = converter.mirrored_output_path(&input_dir, &output_dir, &path, converted, target_format);
+            match converter.convert_into(&path, &output_path, target_format) {
+                Ok(conversion) if converter.json => report::emit(&conversion.to_json()),
+                Ok(conversion) => println!(
+                    "✓ Converted: {} -> {}",
+                    relative.display(),
+                    conversion.outputs.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+                ),
+                Err(e) if converter.json => report::emit(&report::failure(&path, &e)),
+                Err(e) => eprintln!("✗ Failed to convert {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    Ok(())
+}